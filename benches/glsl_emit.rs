@@ -0,0 +1,103 @@
+//! Parses a large generated GLSL shader, reporting how long it takes and how
+//! wide the emitted expression ranges are, since backends that materialize
+//! emitted expressions keep every expression of a range alive together.
+//!
+//! Run with `cargo bench --features glsl-in --bench glsl_emit`.
+
+use naga::{front::glsl, Block, ShaderStage, Statement};
+use std::time::Instant;
+
+const STATEMENTS: usize = 10_000;
+const ITERATIONS: u32 = 10;
+
+/// Generates a fragment shader whose entry point has `STATEMENTS` statements
+/// mixing assignments, expression statements and branches
+fn generate() -> String {
+    let mut source = String::from(
+        "#version 450\n\
+         layout(location = 0) in vec4 i_color;\n\
+         layout(location = 0) out vec4 o_color;\n\
+         void main() {\n\
+         \x20   float a = i_color.x;\n\
+         \x20   vec4 v = i_color;\n",
+    );
+
+    for i in 0..STATEMENTS {
+        let statement = match i % 4 {
+            0 => format!("    a = a * 0.5 + float({});\n", i),
+            1 => format!("    v = v.yzwx * a + vec4(a, {}.0, a, 1.0);\n", i),
+            2 => "    a + v.x * v.y;\n".to_string(),
+            _ => format!("    if (a > {}.0) {{ v.x = dot(v, v) - a; }}\n", i),
+        };
+        source.push_str(&statement);
+    }
+
+    source.push_str("    o_color = v * a;\n}\n");
+    source
+}
+
+/// Collects the number of expressions of every `Emit` in `block`
+fn emit_widths(block: &Block, widths: &mut Vec<usize>) {
+    for stmt in block {
+        match *stmt {
+            Statement::Emit(ref range) => widths.push(range.clone().count()),
+            Statement::Block(ref block) => emit_widths(block, widths),
+            Statement::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                emit_widths(accept, widths);
+                emit_widths(reject, widths);
+            }
+            Statement::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    emit_widths(&case.body, widths);
+                }
+                emit_widths(default, widths);
+            }
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                emit_widths(body, widths);
+                emit_widths(continuing, widths);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn main() {
+    let source = generate();
+    let mut options = glsl::Options::default();
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Fragment);
+
+    let start = Instant::now();
+    let mut module = None;
+    for _ in 0..ITERATIONS {
+        module = Some(glsl::parse_str(&source, &options).unwrap());
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let module = module.unwrap();
+
+    let mut widths = Vec::new();
+    for (_, function) in module.functions.iter() {
+        emit_widths(&function.body, &mut widths);
+    }
+    let total: usize = widths.iter().sum();
+
+    println!("parsed {} statements in {:?}", STATEMENTS, elapsed);
+    println!(
+        "{} emits, {:.2} expressions on average, {} at most",
+        widths.len(),
+        total as f64 / widths.len() as f64,
+        widths.iter().max().unwrap_or(&0)
+    );
+}
@@ -165,7 +165,7 @@ fn main() {
                 &input,
                 &naga::front::glsl::Options {
                     entry_points,
-                    defines: Default::default(),
+                    ..Default::default()
                 },
             )
             .unwrap_or_else(|err| {
@@ -182,7 +182,7 @@ fn main() {
                 &input,
                 &naga::front::glsl::Options {
                     entry_points,
-                    defines: Default::default(),
+                    ..Default::default()
                 },
             )
             .unwrap_or_else(|err| {
@@ -199,7 +199,7 @@ fn main() {
                 &input,
                 &naga::front::glsl::Options {
                     entry_points,
-                    defines: Default::default(),
+                    ..Default::default()
                 },
             )
             .unwrap_or_else(|err| {
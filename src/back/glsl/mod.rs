@@ -1509,8 +1509,9 @@ impl<'a, W: Write> Writer<'a, W> {
                                             }
                                         }
 
+                                        let binding = member.binding.as_ref().unwrap();
                                         let varying_name = VaryingName {
-                                            binding: member.binding.as_ref().unwrap(),
+                                            binding,
                                             stage: ep.stage,
                                             output: true,
                                         };
@@ -1519,24 +1520,41 @@ impl<'a, W: Write> Writer<'a, W> {
                                             .clone();
                                         write!(self.out, "{} = ", varying_name)?;
 
+                                        let conversion = glsl_built_in_output_conversion(binding);
+                                        if let Some(ty) = conversion {
+                                            write!(self.out, "{}(", ty)?;
+                                        }
+
                                         if let Some(struct_name) = temp_struct_name {
                                             write!(self.out, "{}", struct_name)?;
                                         } else {
                                             self.write_expr(value, ctx)?;
                                         }
 
-                                        writeln!(self.out, ".{};", field_name)?;
+                                        write!(self.out, ".{}", field_name)?;
+                                        if conversion.is_some() {
+                                            write!(self.out, ")")?;
+                                        }
+                                        writeln!(self.out, ";")?;
                                         write!(self.out, "{}", INDENT.repeat(indent))?;
                                     }
                                 }
                                 _ => {
+                                    let binding = result.binding.as_ref().unwrap();
                                     let name = VaryingName {
-                                        binding: result.binding.as_ref().unwrap(),
+                                        binding,
                                         stage: ep.stage,
                                         output: true,
                                     };
                                     write!(self.out, "{} = ", name)?;
-                                    self.write_expr(value, ctx)?;
+                                    match glsl_built_in_output_conversion(binding) {
+                                        Some(ty) => {
+                                            write!(self.out, "{}(", ty)?;
+                                            self.write_expr(value, ctx)?;
+                                            write!(self.out, ")")?;
+                                        }
+                                        None => self.write_expr(value, ctx)?,
+                                    }
                                     writeln!(self.out, ";")?;
                                     write!(self.out, "{}", INDENT.repeat(indent))?;
                                 }
@@ -2420,6 +2438,17 @@ fn glsl_scalar(
     })
 }
 
+/// Helper function that returns the glsl type a value written to a builtin
+/// output must be converted to, if the builtin's type differs from the IR's
+fn glsl_built_in_output_conversion(binding: &crate::Binding) -> Option<&'static str> {
+    match *binding {
+        // These are `int` in glsl but `u32` in the IR
+        crate::Binding::BuiltIn(crate::BuiltIn::Layer)
+        | crate::Binding::BuiltIn(crate::BuiltIn::ViewportIndex) => Some("int"),
+        _ => None,
+    }
+}
+
 /// Helper function that returns the glsl variable name for a builtin
 fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
     use crate::BuiltIn as Bi;
@@ -2432,6 +2461,20 @@ fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
                 "gl_FragCoord"
             }
         }
+        Bi::Layer => {
+            if output {
+                "gl_Layer"
+            } else {
+                "uint(gl_Layer)"
+            }
+        }
+        Bi::ViewportIndex => {
+            if output {
+                "gl_ViewportIndex"
+            } else {
+                "uint(gl_ViewportIndex)"
+            }
+        }
         // vertex
         Bi::BaseInstance => "uint(gl_BaseInstance)",
         Bi::BaseVertex => "uint(gl_BaseVertex)",
@@ -2443,6 +2486,7 @@ fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
         // fragment
         Bi::FragDepth => "gl_FragDepth",
         Bi::FrontFacing => "gl_FrontFacing",
+        Bi::PrimitiveIndex => "uint(gl_PrimitiveID)",
         Bi::SampleIndex => "gl_SampleID",
         Bi::SampleMask => {
             if output {
@@ -2457,6 +2501,7 @@ fn glsl_built_in(built_in: crate::BuiltIn, output: bool) -> &'static str {
         Bi::LocalInvocationIndex => "gl_LocalInvocationIndex",
         Bi::WorkGroupId => "gl_WorkGroupID",
         Bi::WorkGroupSize => "gl_WorkGroupSize",
+        Bi::NumWorkGroups => "gl_NumWorkGroups",
     }
 }
 
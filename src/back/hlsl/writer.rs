@@ -24,6 +24,13 @@ struct EntryPointBinding {
     members: Vec<EpStructMember>,
 }
 
+/// Direction of an entry point interface variable
+#[derive(Clone, Copy, PartialEq)]
+enum Io {
+    Input,
+    Output,
+}
+
 struct EpStructMember {
     pub name: String,
     pub ty: Handle<crate::Type>,
@@ -97,7 +104,16 @@ impl<'a, W: Write> Writer<'a, W> {
                 ..
             } = ty.inner
             {
-                self.write_struct(module, handle, top_level, members)?;
+                // Structs returned from entry points need output semantics
+                let ep_result =
+                    module
+                        .entry_points
+                        .iter()
+                        .find_map(|ep| match ep.function.result {
+                            Some(ref result) if result.ty == handle => Some((ep.stage, Io::Output)),
+                            _ => None,
+                        });
+                self.write_struct(module, handle, top_level, members, ep_result)?;
                 writeln!(self.out)?;
             }
         }
@@ -170,13 +186,24 @@ impl<'a, W: Write> Writer<'a, W> {
         })
     }
 
-    fn write_binding(&mut self, binding: &crate::Binding) -> BackendResult {
+    /// Helper method used to write the semantic of a binding
+    ///
+    /// `stage` is the entry point stage and direction the binding belongs to, if any
+    fn write_binding(
+        &mut self,
+        binding: &crate::Binding,
+        stage: Option<(ShaderStage, Io)>,
+    ) -> BackendResult {
         match *binding {
             crate::Binding::BuiltIn(builtin) => {
-                write!(self.out, " : {}", builtin_str(builtin))?;
+                write!(self.out, " : {}", builtin_str(builtin)?)?;
             }
             crate::Binding::Location { location, .. } => {
-                write!(self.out, " : {}{}", LOCATION_SEMANTIC, location)?;
+                if stage == Some((ShaderStage::Fragment, Io::Output)) {
+                    write!(self.out, " : SV_Target{}", location)?;
+                } else {
+                    write!(self.out, " : {}{}", LOCATION_SEMANTIC, location)?;
+                }
             }
         }
 
@@ -220,7 +247,7 @@ impl<'a, W: Write> Writer<'a, W> {
                 self.write_type(module, member.ty)?;
                 write!(self.out, " {}", &member.name)?;
                 if let Some(ref binding) = member.binding {
-                    self.write_binding(binding)?;
+                    self.write_binding(binding, Some((stage, Io::Input)))?;
                 }
                 write!(self.out, ";")?;
                 writeln!(self.out)?;
@@ -370,6 +397,7 @@ impl<'a, W: Write> Writer<'a, W> {
         handle: Handle<crate::Type>,
         _block: bool,
         members: &[crate::StructMember],
+        stage: Option<(ShaderStage, Io)>,
     ) -> BackendResult {
         // Write struct name
         write!(self.out, "struct {}", self.names[&NameKey::Type(handle)])?;
@@ -415,7 +443,7 @@ impl<'a, W: Write> Writer<'a, W> {
             }
 
             if let Some(ref binding) = member.binding {
-                self.write_binding(binding)?;
+                self.write_binding(binding, stage)?;
             };
             write!(self.out, ";")?;
             writeln!(self.out)?;
@@ -580,22 +608,13 @@ impl<'a, W: Write> Writer<'a, W> {
         // Write semantic if it present
         let stage = match func_ctx.ty {
             back::FunctionType::EntryPoint(index) => {
-                Some(module.entry_points[index as usize].stage)
+                Some((module.entry_points[index as usize].stage, Io::Output))
             }
             _ => None,
         };
         if let Some(ref result) = func.result {
             if let Some(ref binding) = result.binding {
-                match *binding {
-                    crate::Binding::BuiltIn(builtin) => {
-                        write!(self.out, " : {}", builtin_str(builtin))?;
-                    }
-                    crate::Binding::Location { location, .. } => {
-                        if stage == Some(ShaderStage::Fragment) {
-                            write!(self.out, " : SV_Target{}", location)?;
-                        }
-                    }
-                }
+                self.write_binding(binding, stage)?;
             }
         }
 
@@ -1294,11 +1313,13 @@ fn image_dimension_str(dim: crate::ImageDimension) -> &'static str {
     }
 }
 
-fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
+fn builtin_str(built_in: crate::BuiltIn) -> Result<&'static str, Error> {
     use crate::BuiltIn as Bi;
 
-    match built_in {
+    Ok(match built_in {
         Bi::Position => "SV_Position",
+        Bi::Layer => "SV_RenderTargetArrayIndex",
+        Bi::ViewportIndex => "SV_ViewportArrayIndex",
         // vertex
         Bi::ClipDistance => "SV_ClipDistance",
         Bi::CullDistance => "SV_CullDistance",
@@ -1310,6 +1331,7 @@ fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
         // fragment
         Bi::FragDepth => "SV_Depth",
         Bi::FrontFacing => "SV_IsFrontFace",
+        Bi::PrimitiveIndex => "SV_PrimitiveID",
         Bi::SampleIndex => "SV_SampleIndex",
         Bi::SampleMask => "SV_Coverage",
         // compute
@@ -1317,8 +1339,8 @@ fn builtin_str(built_in: crate::BuiltIn) -> &'static str {
         Bi::LocalInvocationId => "SV_GroupThreadID",
         Bi::LocalInvocationIndex => "SV_GroupIndex",
         Bi::WorkGroupId => "SV_GroupID",
-        _ => todo!("builtin_str {:?}", built_in),
-    }
+        _ => return Err(Error::UnsupportedBuiltIn(built_in)),
+    })
 }
 
 /// Helper function that returns scalar related strings
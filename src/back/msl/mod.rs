@@ -338,6 +338,8 @@ impl ResolvedBinding {
                 use crate::BuiltIn as Bi;
                 let name = match built_in {
                     Bi::Position => "position",
+                    Bi::Layer => "render_target_array_index",
+                    Bi::ViewportIndex => "viewport_array_index",
                     // vertex
                     Bi::BaseInstance => "base_instance",
                     Bi::BaseVertex => "base_vertex",
@@ -348,6 +350,7 @@ impl ResolvedBinding {
                     // fragment
                     Bi::FragDepth => "depth(any)",
                     Bi::FrontFacing => "front_facing",
+                    Bi::PrimitiveIndex => "primitive_id",
                     Bi::SampleIndex => "sample_id",
                     Bi::SampleMask => "sample_mask",
                     // compute
@@ -356,6 +359,7 @@ impl ResolvedBinding {
                     Bi::LocalInvocationIndex => "thread_index_in_threadgroup",
                     Bi::WorkGroupId => "threadgroup_position_in_grid",
                     Bi::WorkGroupSize => "dispatch_threads_per_threadgroup",
+                    Bi::NumWorkGroups => "threadgroups_per_grid",
                     _ => return Err(Error::UnsupportedBuiltIn(built_in)),
                 };
                 write!(out, "{}", name)?;
@@ -1120,6 +1120,24 @@ impl<W: Write> Writer<W> {
                     write!(self.out, " - ")?;
                     self.put_expression(arg1.unwrap(), context, false)?;
                     write!(self.out, ")")?;
+                } else if fun == Mf::Sign
+                    && context.resolve_type(arg).scalar_kind() == Some(crate::ScalarKind::Sint)
+                {
+                    // Metal has no integer `sign`, clamping gives the same result
+                    write!(self.out, "{}::clamp(", NAMESPACE)?;
+                    self.put_expression(arg, context, true)?;
+                    match *context.resolve_type(arg) {
+                        crate::TypeInner::Vector { size, .. } => {
+                            let ty = scalar_kind_string(crate::ScalarKind::Sint);
+                            let size = back::vector_size_str(size);
+                            write!(
+                                self.out,
+                                ", {}::{}{}(-1), {}::{}{}(1))",
+                                NAMESPACE, ty, size, NAMESPACE, ty, size
+                            )?;
+                        }
+                        _ => write!(self.out, ", -1, 1)")?,
+                    }
                 } else {
                     write!(self.out, "{}::{}", NAMESPACE, fun_name)?;
                     self.put_call_parameters(iter::once(arg).chain(arg1).chain(arg2), context)?;
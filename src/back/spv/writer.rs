@@ -973,6 +973,22 @@ impl Writer {
                             BuiltIn::FragCoord
                         }
                     }
+                    Bi::Layer => {
+                        if class == spirv::StorageClass::Output {
+                            self.check(&[spirv::Capability::ShaderLayer])?;
+                        } else {
+                            self.check(&[spirv::Capability::Geometry])?;
+                        }
+                        BuiltIn::Layer
+                    }
+                    Bi::ViewportIndex => {
+                        if class == spirv::StorageClass::Output {
+                            self.check(&[spirv::Capability::ShaderViewportIndex])?;
+                        } else {
+                            self.check(&[spirv::Capability::MultiViewport])?;
+                        }
+                        BuiltIn::ViewportIndex
+                    }
                     // vertex
                     Bi::BaseInstance => BuiltIn::BaseInstance,
                     Bi::BaseVertex => BuiltIn::BaseVertex,
@@ -984,6 +1000,10 @@ impl Writer {
                     // fragment
                     Bi::FragDepth => BuiltIn::FragDepth,
                     Bi::FrontFacing => BuiltIn::FrontFacing,
+                    Bi::PrimitiveIndex => {
+                        self.check(&[spirv::Capability::Geometry])?;
+                        BuiltIn::PrimitiveId
+                    }
                     Bi::SampleIndex => BuiltIn::SampleId,
                     Bi::SampleMask => BuiltIn::SampleMask,
                     // compute
@@ -992,6 +1012,7 @@ impl Writer {
                     Bi::LocalInvocationIndex => BuiltIn::LocalInvocationIndex,
                     Bi::WorkGroupId => BuiltIn::WorkgroupId,
                     Bi::WorkGroupSize => BuiltIn::WorkgroupSize,
+                    Bi::NumWorkGroups => BuiltIn::NumWorkgroups,
                 };
 
                 self.decorate(id, Decoration::BuiltIn, &[built_in as u32]);
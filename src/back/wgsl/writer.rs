@@ -1196,17 +1196,35 @@ impl<W: Write> Writer<W> {
                     }
                 };
 
-                write!(self.out, "{}(", fun_name)?;
-                self.write_expr(module, arg, func_ctx)?;
-                if let Some(arg) = arg1 {
-                    write!(self.out, ", ")?;
+                let arg_inner = func_ctx.info[arg].ty.inner_with(&module.types);
+                if fun == Mf::Sign && arg_inner.scalar_kind() == Some(crate::ScalarKind::Sint) {
+                    // WGSL's `sign` is float only, so integers are clamped to [-1, 1]
+                    write!(self.out, "clamp(")?;
                     self.write_expr(module, arg, func_ctx)?;
-                }
-                if let Some(arg) = arg2 {
-                    write!(self.out, ", ")?;
+                    match *arg_inner {
+                        TypeInner::Vector { .. } => {
+                            for bound in ["-1", "1"].iter() {
+                                write!(self.out, ", ")?;
+                                self.write_value_type(module, arg_inner)?;
+                                write!(self.out, "({})", bound)?;
+                            }
+                        }
+                        _ => write!(self.out, ", -1, 1")?,
+                    }
+                    write!(self.out, ")")?
+                } else {
+                    write!(self.out, "{}(", fun_name)?;
                     self.write_expr(module, arg, func_ctx)?;
+                    if let Some(arg) = arg1 {
+                        write!(self.out, ", ")?;
+                        self.write_expr(module, arg, func_ctx)?;
+                    }
+                    if let Some(arg) = arg2 {
+                        write!(self.out, ", ")?;
+                        self.write_expr(module, arg, func_ctx)?;
+                    }
+                    write!(self.out, ")")?
                 }
-                write!(self.out, ")")?
             }
             Expression::Swizzle {
                 size,
@@ -1472,11 +1490,13 @@ fn builtin_str(built_in: crate::BuiltIn) -> Option<&'static str> {
         Bi::Position => Some("position"),
         Bi::FrontFacing => Some("front_facing"),
         Bi::FragDepth => Some("frag_depth"),
+        Bi::PrimitiveIndex => Some("primitive_index"),
         Bi::LocalInvocationId => Some("local_invocation_id"),
         Bi::LocalInvocationIndex => Some("local_invocation_index"),
         Bi::GlobalInvocationId => Some("global_invocation_id"),
         Bi::WorkGroupId => Some("workgroup_id"),
         Bi::WorkGroupSize => Some("workgroup_size"),
+        Bi::NumWorkGroups => Some("num_workgroups"),
         Bi::SampleIndex => Some("sample_index"),
         Bi::SampleMask => Some("sample_mask"),
         _ => None,
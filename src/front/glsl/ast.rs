@@ -1,14 +1,14 @@
 use super::{
     super::{Emitter, Typifier},
-    constants::ConstantSolver,
-    error::ErrorKind,
-    SourceMetadata,
+    constants::{is_constant_expression, ConstantSolver},
+    error::{ErrorKind, Warning},
+    Intrinsics, SourceMetadata,
 };
 use crate::{
     proc::ResolveContext, Arena, BinaryOperator, Binding, Block, Constant, Expression, FastHashMap,
-    Function, FunctionArgument, GlobalVariable, Handle, Interpolation, LocalVariable, Module,
-    RelationalFunction, ResourceBinding, Sampling, ScalarKind, ScalarValue, ShaderStage, Statement,
-    StorageClass, Type, TypeInner, UnaryOperator, VectorSize,
+    FastHashSet, Function, FunctionArgument, GlobalVariable, Handle, Interpolation, LocalVariable,
+    Module, RelationalFunction, ResourceBinding, Sampling, ScalarKind, ScalarValue, ShaderStage,
+    Statement, StorageClass, StorageFormat, Type, TypeInner, UnaryOperator, VectorSize,
 };
 use core::convert::TryFrom;
 
@@ -71,6 +71,10 @@ pub struct EntryArg {
     pub prologue: PrologueStage,
 }
 
+/// The versions of a function with unsized array parameters called with sized
+/// arrays and the types of their parameters
+pub type Specializations = Vec<(Vec<Handle<Type>>, Handle<Function>)>;
+
 #[derive(Debug)]
 pub struct Program<'a> {
     pub version: u16,
@@ -79,6 +83,20 @@ pub struct Program<'a> {
 
     pub workgroup_size: [u32; 3],
     pub early_fragment_tests: bool,
+    /// Extensions enabled by `#extension` directives
+    pub extensions: FastHashSet<String>,
+    /// Disables the pruning of entry arguments that aren't read or written
+    pub keep_unused_entry_args: bool,
+    /// Replaces switches with constant selectors by the selected case
+    pub fold_constant_switches: bool,
+    /// Custom intrinsics that are used if no declared function matches a call
+    pub intrinsics: Option<&'a Intrinsics>,
+    /// Maximum nesting depth of expressions and statements
+    pub max_nesting_depth: usize,
+    /// Names builtin entry arguments and argument temporaries after the source
+    pub preserve_names: bool,
+    /// Overrides the names of the generated entry points
+    pub entry_point_names: Option<&'a FastHashMap<String, String>>,
 
     pub lookup_function: FastHashMap<String, Vec<FunctionDeclaration>>,
     pub lookup_type: FastHashMap<String, Handle<Type>>,
@@ -89,6 +107,27 @@ pub struct Program<'a> {
     pub entries: Vec<(String, ShaderStage, Handle<Function>)>,
     // TODO: More efficient representation
     pub function_arg_use: Vec<Vec<EntryArgUse>>,
+    /// Locations of the `barrier` calls reached by each function
+    pub function_barriers: FastHashMap<Handle<Function>, SourceMetadata>,
+    /// Integer and double inputs not qualified as flat by their entry argument
+    /// index, they can't be used by fragment entry points
+    pub non_flat_inputs: Vec<(usize, SourceMetadata)>,
+    /// Builtins only available in a single stage by their entry argument index
+    /// along with their name, stage and first use, they can't be used by
+    /// entry points of other stages
+    pub stage_only_inputs: Vec<(usize, String, ShaderStage, SourceMetadata)>,
+    /// Locals the arguments of each function are copied to, only kept for
+    /// functions that have any
+    pub function_arg_locals: FastHashMap<Handle<Function>, Vec<(usize, Handle<LocalVariable>)>>,
+    /// Versions of the functions with unsized array parameters
+    pub specializations: FastHashMap<Handle<Function>, Specializations>,
+    /// Non-fatal issues found so far
+    pub warnings: Vec<Warning>,
+    /// Textures combined with regular samplers, they can't become depth
+    /// textures by being combined with shadow samplers
+    pub sampled_textures: FastHashSet<Handle<GlobalVariable>>,
+    /// Initializers of the uniforms which are only their default values
+    pub uniform_defaults: FastHashMap<Handle<GlobalVariable>, Handle<Constant>>,
 
     pub module: Module,
 }
@@ -102,6 +141,13 @@ impl<'a> Program<'a> {
 
             workgroup_size: [1; 3],
             early_fragment_tests: false,
+            extensions: FastHashSet::default(),
+            keep_unused_entry_args: false,
+            fold_constant_switches: false,
+            intrinsics: None,
+            max_nesting_depth: 256,
+            preserve_names: false,
+            entry_point_names: None,
 
             lookup_function: FastHashMap::default(),
             lookup_type: FastHashMap::default(),
@@ -110,6 +156,14 @@ impl<'a> Program<'a> {
             entry_args: Vec::new(),
             entries: Vec::new(),
             function_arg_use: Vec::new(),
+            function_barriers: FastHashMap::default(),
+            function_arg_locals: FastHashMap::default(),
+            non_flat_inputs: Vec::new(),
+            stage_only_inputs: Vec::new(),
+            specializations: FastHashMap::default(),
+            warnings: Vec::new(),
+            sampled_textures: FastHashSet::default(),
+            uniform_defaults: FastHashMap::default(),
 
             module: Module::default(),
         }
@@ -138,6 +192,28 @@ impl<'a> Program<'a> {
             })
     }
 
+    /// Returns the type of an array of `len` elements of `base`, used when
+    /// the size of an array is inferred from its initializer
+    pub fn sized_array(&mut self, base: Handle<Type>, len: usize) -> Handle<Type> {
+        let size = self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Sint(len as i64),
+            },
+        });
+
+        self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Array {
+                base,
+                size: crate::ArraySize::Constant(size),
+                stride: self.module.types[base].inner.span(&self.module.constants),
+            },
+        })
+    }
+
     pub fn resolve_type<'b>(
         &'b self,
         context: &'b mut Context,
@@ -155,18 +231,35 @@ impl<'a> Program<'a> {
         meta: SourceMetadata,
     ) -> Result<Handle<Constant>, ErrorKind> {
         let mut solver = ConstantSolver {
-            types: &self.module.types,
+            types: &mut self.module.types,
             expressions: ctx.expressions,
             constants: &mut self.module.constants,
         };
 
         solver.solve(root).map_err(|e| (meta, e).into())
     }
+
+    /// Solves `root` if it's a constant expression, for initializers that
+    /// fall back to runtime values otherwise
+    pub fn try_solve_constant(
+        &mut self,
+        ctx: &Context,
+        root: Handle<Expression>,
+        meta: SourceMetadata,
+    ) -> Option<Handle<Constant>> {
+        if !is_constant_expression(ctx.expressions, root) {
+            return None;
+        }
+
+        self.solve_constant(ctx, root, meta).ok()
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Profile {
     Core,
+    Compatibility,
+    Es,
 }
 
 #[derive(Debug)]
@@ -175,15 +268,28 @@ pub struct Context<'function> {
     pub locals: &'function mut Arena<LocalVariable>,
     pub arguments: &'function mut Vec<FunctionArgument>,
     pub arg_use: Vec<EntryArgUse>,
+    /// Location of the first `barrier` call, which is only allowed if the
+    /// function is reached from compute entry points
+    pub barrier: Option<SourceMetadata>,
+    /// Locals the arguments passed by value are copied to, by argument index
+    pub arg_locals: Vec<(usize, Handle<LocalVariable>)>,
+    /// The declared result type of the function being parsed, `None` if void
+    pub result: Option<Handle<Type>>,
 
     //TODO: Find less allocation heavy representation
     pub scopes: Vec<FastHashMap<String, VariableReference>>,
     pub lookup_global_var_exps: FastHashMap<String, VariableReference>,
+    /// Named locals in declaration order, used to warn about unused ones
+    pub local_decls: Vec<(Handle<Expression>, String, SourceMetadata)>,
+    /// Variable expressions referenced by any expression so far
+    pub used_vars: FastHashSet<Handle<Expression>>,
     pub samplers: FastHashMap<Handle<Expression>, Handle<Expression>>,
     pub typifier: Typifier,
 
     pub hir_exprs: Arena<HirExpr>,
     emitter: Emitter,
+    /// Current nesting depth of the expressions being lowered
+    depth: usize,
 }
 
 impl<'function> Context<'function> {
@@ -199,17 +305,23 @@ impl<'function> Context<'function> {
             locals,
             arguments,
             arg_use: vec![EntryArgUse::empty(); program.entry_args.len()],
+            barrier: None,
+            arg_locals: Vec::new(),
+            result: None,
 
             scopes: vec![FastHashMap::default()],
             lookup_global_var_exps: FastHashMap::with_capacity_and_hasher(
                 program.global_variables.len(),
                 Default::default(),
             ),
+            local_decls: Vec::new(),
+            used_vars: FastHashSet::default(),
             typifier: Typifier::new(),
             samplers: FastHashMap::default(),
 
             hir_exprs: Arena::default(),
             emitter: Emitter::default(),
+            depth: 0,
         };
 
         this.emit_start();
@@ -274,6 +386,11 @@ impl<'function> Context<'function> {
             this.lookup_global_var_exps.insert(name.into(), var);
         }
 
+        // The block selects of the last globals must be emitted even if the
+        // function body never adds another expression
+        this.emit_flush(body);
+        this.emit_start();
+
         this
     }
 
@@ -333,6 +450,17 @@ impl<'function> Context<'function> {
         }
     }
 
+    /// Warns about the named locals that were never referenced
+    pub fn warn_unused_locals(&self, program: &mut Program) {
+        for &(expr, ref name, meta) in self.local_decls.iter() {
+            if !self.used_vars.contains(&expr) {
+                program
+                    .warnings
+                    .push(Warning::UnusedVariable(meta, name.clone()))
+            }
+        }
+    }
+
     /// Add function argument to current scope
     pub fn add_function_arg(
         &mut self,
@@ -379,6 +507,7 @@ impl<'function> Context<'function> {
                     ty,
                     init: None,
                 });
+                self.arg_locals.push((index, handle));
                 let local_expr = self.add_expression(Expression::LocalVariable(handle), body);
 
                 self.emit_flush(body);
@@ -423,6 +552,79 @@ impl<'function> Context<'function> {
         self.scopes.pop();
     }
 
+    /// Marks the entry argument (if any) at the root of `expr` as both read
+    /// and written, used when it's passed to a function by pointer since the
+    /// callee may do either with it
+    pub fn mark_pointer_use(&mut self, mut expr: Handle<HirExpr>) {
+        loop {
+            match self.hir_exprs[expr].kind {
+                HirExprKind::Access { base, .. } => expr = base,
+                HirExprKind::Select { base, .. } => expr = base,
+                HirExprKind::Variable(ref var) => {
+                    if let Some(idx) = var.entry_arg {
+                        self.arg_use[idx] |= EntryArgUse::READ | EntryArgUse::WRITE
+                    }
+                    break;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Returns an error if `expr` is rooted in a variable that can't be
+    /// written to, like a `const` local or an input
+    pub fn check_writable(&self, mut expr: Handle<HirExpr>) -> Result<(), ErrorKind> {
+        loop {
+            match self.hir_exprs[expr].kind {
+                HirExprKind::Access { base, .. } | HirExprKind::Select { base, .. } => expr = base,
+                HirExprKind::Variable(ref var) if !var.mutable => {
+                    return Err(ErrorKind::SemanticError(
+                        self.hir_exprs[expr].meta,
+                        "Variable cannot be used in LHS position".into(),
+                    ))
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Returns true if `expr` is rooted in a variable and so can be lowered
+    /// to a pointer, r-values like call results need to be spilled first
+    fn is_addressable(&self, mut expr: Handle<HirExpr>) -> bool {
+        loop {
+            match self.hir_exprs[expr].kind {
+                HirExprKind::Access { base, .. } | HirExprKind::Select { base, .. } => expr = base,
+                HirExprKind::Variable(_) => return true,
+                _ => return false,
+            }
+        }
+    }
+
+    /// Stores `value` in a new temporary local and returns a pointer to it
+    fn spill(
+        &mut self,
+        program: &mut Program,
+        value: Handle<Expression>,
+        meta: SourceMetadata,
+        body: &mut Block,
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        program.typifier_grow(self, value, meta)?;
+        let ty = self.typifier.get_handle(value, &mut program.module.types);
+        let local = self.locals.append(LocalVariable {
+            name: None,
+            ty,
+            init: None,
+        });
+        let pointer = self.add_expression(Expression::LocalVariable(local), body);
+
+        self.emit_flush(body);
+        self.emit_start();
+
+        body.push(Statement::Store { pointer, value });
+
+        Ok(pointer)
+    }
+
     pub fn lower_expect(
         &mut self,
         program: &mut Program,
@@ -451,14 +653,59 @@ impl<'function> Context<'function> {
         expr: Handle<HirExpr>,
         lhs: bool,
         body: &mut Block,
+    ) -> Result<(Option<Handle<Expression>>, SourceMetadata), ErrorKind> {
+        // Long chains of binary operators are parsed iteratively but still
+        // produce deep trees so the lowering needs its own bound
+        if self.depth >= program.max_nesting_depth {
+            return Err(ErrorKind::SemanticError(
+                self.hir_exprs[expr].meta,
+                "Maximum nesting depth exceeded".into(),
+            ));
+        }
+
+        self.depth += 1;
+        let res = self.lower_inner(program, expr, lhs, body);
+        self.depth -= 1;
+        res
+    }
+
+    fn lower_inner(
+        &mut self,
+        program: &mut Program,
+        expr: Handle<HirExpr>,
+        lhs: bool,
+        body: &mut Block,
     ) -> Result<(Option<Handle<Expression>>, SourceMetadata), ErrorKind> {
         let HirExpr { kind, meta } = self.hir_exprs[expr].clone();
 
         let handle = match kind {
             HirExprKind::Access { base, index } => {
-                let base = self.lower_expect(program, base, true, body)?.0;
+                let base = if lhs || self.is_addressable(base) {
+                    self.lower_expect(program, base, true, body)?.0
+                } else {
+                    let (value, base_meta) = self.lower_expect(program, base, false, body)?;
+                    self.spill(program, value, base_meta, body)?
+                };
                 let (index, index_meta) = self.lower_expect(program, index, false, body)?;
 
+                // Both signed and unsigned indices can be used directly
+                match *program.resolve_type(self, index, index_meta)? {
+                    TypeInner::Scalar {
+                        kind: ScalarKind::Sint,
+                        ..
+                    }
+                    | TypeInner::Scalar {
+                        kind: ScalarKind::Uint,
+                        ..
+                    } => {}
+                    _ => {
+                        return Err(ErrorKind::SemanticError(
+                            index_meta,
+                            "Array index must be an integer".into(),
+                        ))
+                    }
+                }
+
                 let pointer = program
                     .solve_constant(self, index, index_meta)
                     .ok()
@@ -485,7 +732,11 @@ impl<'function> Context<'function> {
                         self.add_expression(Expression::Access { base, index }, body)
                     });
 
-                if let TypeInner::Pointer { .. } = *program.resolve_type(self, pointer, meta)? {
+                // Dynamically indexing a vector behind a pointer yields a
+                // `ValuePointer` which also needs to be loaded
+                if let TypeInner::Pointer { .. } | TypeInner::ValuePointer { .. } =
+                    *program.resolve_type(self, pointer, meta)?
+                {
                     if !lhs {
                         return Ok((
                             Some(self.add_expression(Expression::Load { pointer }, body)),
@@ -505,72 +756,25 @@ impl<'function> Context<'function> {
                 self.add_expression(Expression::Constant(constant), body)
             }
             HirExprKind::Binary { left, op, right } if !lhs => {
-                let (mut left, left_meta) = self.lower_expect(program, left, false, body)?;
-                let (mut right, right_meta) = self.lower_expect(program, right, false, body)?;
-
-                self.binary_implicit_conversion(
-                    program, &mut left, left_meta, &mut right, right_meta,
-                )?;
-
-                program.typifier_grow(self, left, left_meta)?;
-                program.typifier_grow(self, right, right_meta)?;
-
-                let left_inner = self.typifier.get(left, &program.module.types);
-                let right_inner = self.typifier.get(right, &program.module.types);
-
-                match (left_inner, right_inner) {
-                    (&TypeInner::Vector { .. }, &TypeInner::Vector { .. })
-                    | (&TypeInner::Matrix { .. }, &TypeInner::Matrix { .. }) => match op {
-                        BinaryOperator::Equal | BinaryOperator::NotEqual => {
-                            let equals = op == BinaryOperator::Equal;
-
-                            let (op, fun) = match equals {
-                                true => (BinaryOperator::Equal, RelationalFunction::All),
-                                false => (BinaryOperator::NotEqual, RelationalFunction::Any),
-                            };
-
-                            let argument =
-                                self.expressions
-                                    .append(Expression::Binary { op, left, right });
+                // Chains of left associative operators are lowered in a loop
+                // so that they don't count towards the nesting depth
+                let mut chain = vec![(op, right, meta)];
+                let mut leftmost = left;
+                while let HirExprKind::Binary { left, op, right } = self.hir_exprs[leftmost].kind {
+                    chain.push((op, right, self.hir_exprs[leftmost].meta));
+                    leftmost = left;
+                }
 
-                            self.add_expression(Expression::Relational { fun, argument }, body)
-                        }
-                        _ => self.add_expression(Expression::Binary { left, op, right }, body),
-                    },
-                    (&TypeInner::Vector { size, .. }, &TypeInner::Scalar { .. }) => match op {
-                        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
-                            let scalar_vector =
-                                self.add_expression(Expression::Splat { size, value: right }, body);
-
-                            self.add_expression(
-                                Expression::Binary {
-                                    op,
-                                    left,
-                                    right: scalar_vector,
-                                },
-                                body,
-                            )
-                        }
-                        _ => self.add_expression(Expression::Binary { left, op, right }, body),
-                    },
-                    (&TypeInner::Scalar { .. }, &TypeInner::Vector { size, .. }) => match op {
-                        BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
-                            let scalar_vector =
-                                self.add_expression(Expression::Splat { size, value: left }, body);
-
-                            self.add_expression(
-                                Expression::Binary {
-                                    op,
-                                    left: scalar_vector,
-                                    right,
-                                },
-                                body,
-                            )
-                        }
-                        _ => self.add_expression(Expression::Binary { left, op, right }, body),
-                    },
-                    _ => self.add_expression(Expression::Binary { left, op, right }, body),
+                let mut left = self.lower_expect(program, leftmost, false, body)?;
+                for (op, right, meta) in chain.into_iter().rev() {
+                    let right = self.lower_expect(program, right, false, body)?;
+                    left = (
+                        self.lower_binary(program, body, op, left, right, meta)?,
+                        meta,
+                    );
                 }
+
+                left.0
             }
             HirExprKind::Unary { op, expr } if !lhs => {
                 let expr = self.lower_expect(program, expr, false, body)?.0;
@@ -578,16 +782,17 @@ impl<'function> Context<'function> {
                 self.add_expression(Expression::Unary { op, expr }, body)
             }
             HirExprKind::Variable(var) => {
-                if lhs {
-                    if !var.mutable {
-                        return Err(ErrorKind::SemanticError(
-                            meta,
-                            "Variable cannot be used in LHS position".into(),
-                        ));
-                    }
+                self.used_vars.insert(var.expr);
 
+                // Immutable variables are also lowered as lhs to be indexed but
+                // writes to them were rejected by `check_writable`
+                if lhs {
                     if let Some(idx) = var.entry_arg {
-                        self.arg_use[idx] |= EntryArgUse::WRITE
+                        self.arg_use[idx] |= if var.mutable {
+                            EntryArgUse::WRITE
+                        } else {
+                            EntryArgUse::READ
+                        }
                     }
 
                     var.expr
@@ -607,6 +812,11 @@ impl<'function> Context<'function> {
                 let maybe_expr = program.function_call(self, body, call.kind, &call.args, meta)?;
                 return Ok((maybe_expr, meta));
             }
+            HirExprKind::Sequence { left, right } if !lhs => {
+                self.lower(program, left, false, body)?;
+                let maybe_expr = self.lower(program, right, false, body)?.0;
+                return Ok((maybe_expr, meta));
+            }
             HirExprKind::Conditional {
                 condition,
                 accept,
@@ -634,6 +844,7 @@ impl<'function> Context<'function> {
                 )
             }
             HirExprKind::Assign { tgt, value } if !lhs => {
+                self.check_writable(tgt)?;
                 let (pointer, ptr_meta) = self.lower_expect(program, tgt, true, body)?;
                 let (mut value, value_meta) = self.lower_expect(program, value, false, body)?;
 
@@ -711,6 +922,7 @@ impl<'function> Context<'function> {
                     false => BinaryOperator::Subtract,
                 };
 
+                self.check_writable(expr)?;
                 let pointer = self.lower_expect(program, expr, true, body)?.0;
                 let left = self.add_expression(Expression::Load { pointer }, body);
 
@@ -801,24 +1013,334 @@ impl<'function> Context<'function> {
         Ok((Some(handle), meta))
     }
 
-    pub fn expr_scalar_kind(
+    /// Lowers a binary operation between two already lowered operands
+    fn lower_binary(
         &mut self,
         program: &mut Program,
-        expr: Handle<Expression>,
+        body: &mut Block,
+        op: BinaryOperator,
+        (mut left, left_meta): (Handle<Expression>, SourceMetadata),
+        (mut right, right_meta): (Handle<Expression>, SourceMetadata),
         meta: SourceMetadata,
-    ) -> Result<Option<ScalarKind>, ErrorKind> {
-        Ok(program.resolve_type(self, expr, meta)?.scalar_kind())
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        self.binary_implicit_conversion(program, &mut left, left_meta, &mut right, right_meta)?;
+
+        program.typifier_grow(self, left, left_meta)?;
+        program.typifier_grow(self, right, right_meta)?;
+
+        let left_inner = self.typifier.get(left, &program.module.types);
+        let right_inner = self.typifier.get(right, &program.module.types);
+
+        if op == BinaryOperator::Modulo {
+            let is_integer = |inner: &TypeInner| {
+                matches!(
+                    inner.scalar_kind(),
+                    Some(ScalarKind::Sint) | Some(ScalarKind::Uint)
+                )
+            };
+
+            if !is_integer(left_inner) || !is_integer(right_inner) {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    "The % operator only works on integers, use mod for floats".into(),
+                ));
+            }
+        }
+
+        if op == BinaryOperator::Multiply {
+            let inner_dimensions = match (left_inner, right_inner) {
+                (&TypeInner::Matrix { columns, .. }, &TypeInner::Vector { size, .. }) => {
+                    Some((columns, size))
+                }
+                (&TypeInner::Vector { size, .. }, &TypeInner::Matrix { rows, .. }) => {
+                    Some((size, rows))
+                }
+                (&TypeInner::Matrix { columns, .. }, &TypeInner::Matrix { rows, .. }) => {
+                    Some((columns, rows))
+                }
+                _ => None,
+            };
+
+            if let Some((left_size, right_size)) = inner_dimensions {
+                if left_size != right_size {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!(
+                            "Cannot multiply operands with inner dimensions {} and {}",
+                            left_size as u8, right_size as u8
+                        )
+                        .into(),
+                    ));
+                }
+            }
+        }
+
+        if let BinaryOperator::Equal | BinaryOperator::NotEqual = op {
+            // Matrices and aggregates can't be compared directly by the IR
+            let columns = match *left_inner {
+                TypeInner::Matrix { columns, .. } => Some(columns),
+                _ => None,
+            };
+            let components = aggregate_components(&program.module, left_inner, meta)?;
+
+            if columns.is_some() || components.is_some() {
+                if left_inner != right_inner {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        "Cannot compare values of different types".into(),
+                    ));
+                }
+
+                let expr = match (columns, components) {
+                    (Some(columns), _) => self.matrix_equality(body, op, left, right, columns),
+                    (None, components) => self.aggregate_equality(
+                        program,
+                        body,
+                        op,
+                        left,
+                        right,
+                        components.unwrap_or_default(),
+                        meta,
+                    )?,
+                };
+                return Ok(expr);
+            }
+        }
+
+        Ok(match (left_inner, right_inner) {
+            (&TypeInner::Vector { .. }, &TypeInner::Vector { .. })
+            | (&TypeInner::Matrix { .. }, &TypeInner::Matrix { .. }) => match op {
+                BinaryOperator::Equal | BinaryOperator::NotEqual => {
+                    let equals = op == BinaryOperator::Equal;
+
+                    let (op, fun) = match equals {
+                        true => (BinaryOperator::Equal, RelationalFunction::All),
+                        false => (BinaryOperator::NotEqual, RelationalFunction::Any),
+                    };
+
+                    let argument = self
+                        .expressions
+                        .append(Expression::Binary { op, left, right });
+
+                    self.add_expression(Expression::Relational { fun, argument }, body)
+                }
+                _ => self.add_expression(Expression::Binary { left, op, right }, body),
+            },
+            (&TypeInner::Vector { size, .. }, &TypeInner::Scalar { .. }) => match op {
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
+                    let scalar_vector =
+                        self.add_expression(Expression::Splat { size, value: right }, body);
+
+                    self.add_expression(
+                        Expression::Binary {
+                            op,
+                            left,
+                            right: scalar_vector,
+                        },
+                        body,
+                    )
+                }
+                _ => self.add_expression(Expression::Binary { left, op, right }, body),
+            },
+            (&TypeInner::Scalar { .. }, &TypeInner::Vector { size, .. }) => match op {
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
+                    let scalar_vector =
+                        self.add_expression(Expression::Splat { size, value: left }, body);
+
+                    self.add_expression(
+                        Expression::Binary {
+                            op,
+                            left: scalar_vector,
+                            right,
+                        },
+                        body,
+                    )
+                }
+                _ => self.add_expression(Expression::Binary { left, op, right }, body),
+            },
+            (
+                &TypeInner::Matrix {
+                    columns,
+                    rows,
+                    width,
+                },
+                &TypeInner::Scalar { .. },
+            ) => match op {
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
+                    let matrix = (left, columns, rows, width);
+                    self.matrix_scalar_binary(program, body, op, matrix, right, false)
+                }
+                _ => self.add_expression(Expression::Binary { left, op, right }, body),
+            },
+            (
+                &TypeInner::Scalar { .. },
+                &TypeInner::Matrix {
+                    columns,
+                    rows,
+                    width,
+                },
+            ) => match op {
+                BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Divide => {
+                    let matrix = (right, columns, rows, width);
+                    self.matrix_scalar_binary(program, body, op, matrix, left, true)
+                }
+                _ => self.add_expression(Expression::Binary { left, op, right }, body),
+            },
+            _ => self.add_expression(Expression::Binary { left, op, right }, body),
+        })
+    }
+
+    /// Applies a component-wise operation between a matrix and a scalar, the
+    /// IR only allows multiplication so it's done column by column
+    fn matrix_scalar_binary(
+        &mut self,
+        program: &mut Program,
+        body: &mut Block,
+        op: BinaryOperator,
+        (matrix, columns, rows, width): (Handle<Expression>, VectorSize, VectorSize, u8),
+        scalar: Handle<Expression>,
+        scalar_left: bool,
+    ) -> Handle<Expression> {
+        let ty = program.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Matrix {
+                columns,
+                rows,
+                width,
+            },
+        });
+        let splat = self.add_expression(
+            Expression::Splat {
+                size: rows,
+                value: scalar,
+            },
+            body,
+        );
+
+        let components = (0..columns as u32)
+            .map(|index| {
+                let column = self.add_expression(
+                    Expression::AccessIndex {
+                        base: matrix,
+                        index,
+                    },
+                    body,
+                );
+                let (left, right) = if scalar_left {
+                    (splat, column)
+                } else {
+                    (column, splat)
+                };
+
+                self.add_expression(Expression::Binary { op, left, right }, body)
+            })
+            .collect();
+
+        self.add_expression(Expression::Compose { ty, components }, body)
+    }
+
+    /// Expands the comparison of two structs or arrays into the conjunction
+    /// (or disjunction for `!=`) of the comparisons of their `components`
+    #[allow(clippy::too_many_arguments)]
+    fn aggregate_equality(
+        &mut self,
+        program: &mut Program,
+        body: &mut Block,
+        op: BinaryOperator,
+        left: Handle<Expression>,
+        right: Handle<Expression>,
+        components: Vec<Handle<Type>>,
+        meta: SourceMetadata,
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        let (logical_op, fun) = match op {
+            BinaryOperator::Equal => (BinaryOperator::LogicalAnd, RelationalFunction::All),
+            _ => (BinaryOperator::LogicalOr, RelationalFunction::Any),
+        };
+
+        let mut result = None;
+        for (index, ty) in components.into_iter().enumerate() {
+            let index = index as u32;
+            let left = self.add_expression(Expression::AccessIndex { base: left, index }, body);
+            let right = self.add_expression(Expression::AccessIndex { base: right, index }, body);
+
+            let inner = &program.module.types[ty].inner;
+            let comparison = if let Some(components) =
+                aggregate_components(&program.module, inner, meta)?
+            {
+                self.aggregate_equality(program, body, op, left, right, components, meta)?
+            } else if let TypeInner::Matrix { columns, .. } = *inner {
+                self.matrix_equality(body, op, left, right, columns)
+            } else if let TypeInner::Vector { .. } = *inner {
+                let argument = self.add_expression(Expression::Binary { op, left, right }, body);
+                self.add_expression(Expression::Relational { fun, argument }, body)
+            } else {
+                self.add_expression(Expression::Binary { op, left, right }, body)
+            };
+
+            result = Some(match result {
+                Some(acc) => self.add_expression(
+                    Expression::Binary {
+                        op: logical_op,
+                        left: acc,
+                        right: comparison,
+                    },
+                    body,
+                ),
+                None => comparison,
+            });
+        }
+
+        result
+            .ok_or_else(|| ErrorKind::SemanticError(meta, "Cannot compare empty aggregates".into()))
+    }
+
+    /// Compares two matrices column by column since the IR can't compare
+    /// matrices directly
+    fn matrix_equality(
+        &mut self,
+        body: &mut Block,
+        op: BinaryOperator,
+        left: Handle<Expression>,
+        right: Handle<Expression>,
+        columns: VectorSize,
+    ) -> Handle<Expression> {
+        let (logical_op, fun) = match op {
+            BinaryOperator::Equal => (BinaryOperator::LogicalAnd, RelationalFunction::All),
+            _ => (BinaryOperator::LogicalOr, RelationalFunction::Any),
+        };
+
+        let mut result = None;
+        for index in 0..columns as u32 {
+            let left = self.add_expression(Expression::AccessIndex { base: left, index }, body);
+            let right = self.add_expression(Expression::AccessIndex { base: right, index }, body);
+
+            let argument = self.add_expression(Expression::Binary { op, left, right }, body);
+            let comparison = self.add_expression(Expression::Relational { fun, argument }, body);
+
+            result = Some(match result {
+                Some(acc) => self.add_expression(
+                    Expression::Binary {
+                        op: logical_op,
+                        left: acc,
+                        right: comparison,
+                    },
+                    body,
+                ),
+                None => comparison,
+            });
+        }
+
+        // Matrices have at least two columns
+        result.unwrap()
     }
 
-    pub fn expr_power(
+    pub fn expr_scalar_kind(
         &mut self,
         program: &mut Program,
         expr: Handle<Expression>,
         meta: SourceMetadata,
-    ) -> Result<Option<u32>, ErrorKind> {
-        Ok(self
-            .expr_scalar_kind(program, expr, meta)?
-            .and_then(type_power))
+    ) -> Result<Option<ScalarKind>, ErrorKind> {
+        Ok(program.resolve_type(self, expr, meta)?.scalar_kind())
     }
 
     pub fn get_expression(&self, expr: Handle<Expression>) -> &Expression {
@@ -832,15 +1354,11 @@ impl<'function> Context<'function> {
         meta: SourceMetadata,
         kind: ScalarKind,
     ) -> Result<(), ErrorKind> {
-        if let (Some(tgt_power), Some(expr_power)) =
-            (type_power(kind), self.expr_power(program, *expr, meta)?)
-        {
-            if tgt_power > expr_power {
-                *expr = self.expressions.append(Expression::As {
-                    expr: *expr,
-                    kind,
-                    convert: None,
-                })
+        if let Some(expr_kind) = self.expr_scalar_kind(program, *expr, meta)? {
+            if let (Some(tgt_power), Some(expr_power)) = (type_power(kind), type_power(expr_kind)) {
+                if tgt_power > expr_power {
+                    *expr = self.conversion(program, *expr, meta, expr_kind, kind)
+                }
             }
         }
 
@@ -864,25 +1382,80 @@ impl<'function> Context<'function> {
         ) {
             match left_power.cmp(&right_power) {
                 std::cmp::Ordering::Less => {
-                    *left = self.expressions.append(Expression::As {
-                        expr: *left,
-                        kind: right_kind,
-                        convert: None,
-                    })
+                    *left = self.conversion(program, *left, left_meta, left_kind, right_kind)
                 }
                 std::cmp::Ordering::Equal => {}
                 std::cmp::Ordering::Greater => {
-                    *right = self.expressions.append(Expression::As {
-                        expr: *right,
-                        kind: left_kind,
-                        convert: None,
-                    })
+                    *right = self.conversion(program, *right, right_meta, right_kind, left_kind)
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Appends the implicit conversion of `expr` from `from` to `to`, warning
+    /// if a non constant value might not be representable after it since
+    /// floats can't hold every integer and unsigned integers aren't negative
+    fn conversion(
+        &mut self,
+        program: &mut Program,
+        expr: Handle<Expression>,
+        meta: SourceMetadata,
+        from: ScalarKind,
+        to: ScalarKind,
+    ) -> Handle<Expression> {
+        let lossy = match (from, to) {
+            (ScalarKind::Sint, ScalarKind::Uint) => true,
+            (ScalarKind::Sint, ScalarKind::Float) | (ScalarKind::Uint, ScalarKind::Float) => true,
+            _ => false,
+        };
+
+        if lossy && !matches!(self.expressions[expr], Expression::Constant(_)) {
+            program
+                .warnings
+                .push(Warning::LossyConversion(meta, from, to))
+        }
+
+        self.expressions.append(Expression::As {
+            expr,
+            kind: to,
+            convert: None,
+        })
+    }
+}
+
+/// Returns the types of the components of a struct or fixed size array, or
+/// `None` if `inner` isn't an aggregate
+fn aggregate_components(
+    module: &Module,
+    inner: &TypeInner,
+    meta: SourceMetadata,
+) -> Result<Option<Vec<Handle<Type>>>, ErrorKind> {
+    Ok(match *inner {
+        TypeInner::Struct { ref members, .. } => {
+            Some(members.iter().map(|member| member.ty).collect())
+        }
+        TypeInner::Array { base, size, .. } => {
+            let len = match size {
+                crate::ArraySize::Constant(constant) => {
+                    module.constants[constant].to_array_length()
+                }
+                crate::ArraySize::Dynamic => None,
+            }
+            .ok_or_else(|| {
+                ErrorKind::SemanticError(meta, "Cannot compare runtime sized arrays".into())
+            })?;
+
+            Some(vec![base; len as usize])
+        }
+        // Runtime sized arrays are never loaded so they are seen through a pointer
+        TypeInner::Pointer { base, .. } => {
+            aggregate_components(module, &module.types[base].inner, meta)?;
+            None
+        }
+        _ => None,
+    })
 }
 
 pub fn type_power(kind: ScalarKind) -> Option<u32> {
@@ -894,6 +1467,28 @@ pub fn type_power(kind: ScalarKind) -> Option<u32> {
     })
 }
 
+/// Returns true if both types are scalars, vectors of the same size or
+/// matrices of the same dimensions, implicit conversions never change these
+pub fn same_shape(a: &TypeInner, b: &TypeInner) -> bool {
+    match (a, b) {
+        (&TypeInner::Scalar { .. }, &TypeInner::Scalar { .. }) => true,
+        (&TypeInner::Vector { size: a, .. }, &TypeInner::Vector { size: b, .. }) => a == b,
+        (
+            &TypeInner::Matrix {
+                columns: a_columns,
+                rows: a_rows,
+                ..
+            },
+            &TypeInner::Matrix {
+                columns: b_columns,
+                rows: b_rows,
+                ..
+            },
+        ) => a_columns == b_columns && a_rows == b_rows,
+        _ => false,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VariableReference {
     pub expr: Handle<Expression>,
@@ -944,9 +1539,14 @@ pub enum HirExprKind {
         postfix: bool,
         expr: Handle<HirExpr>,
     },
+    /// The comma operator, `left` is only evaluated for its side effects
+    Sequence {
+        left: Handle<HirExpr>,
+        right: Handle<HirExpr>,
+    },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TypeQualifier {
     StorageQualifier(StorageQualifier),
     Interpolation(Interpolation),
@@ -956,6 +1556,7 @@ pub enum TypeQualifier {
     Sampling(Sampling),
     Layout(StructLayout),
     Precision(Precision),
+    StorageFormat(StorageFormat),
     EarlyFragmentTests,
 }
 
@@ -1,12 +1,12 @@
 use crate::{
     arena::{Arena, Handle},
-    BinaryOperator, Constant, ConstantInner, Expression, ScalarKind, ScalarValue, Type, TypeInner,
-    UnaryOperator,
+    BinaryOperator, Constant, ConstantInner, Expression, MathFunction, ScalarKind, ScalarValue,
+    Type, TypeInner, UnaryOperator,
 };
 
 #[derive(Debug)]
 pub struct ConstantSolver<'a> {
-    pub types: &'a Arena<Type>,
+    pub types: &'a mut Arena<Type>,
     pub expressions: &'a Arena<Expression>,
     pub constants: &'a mut Arena<Constant>,
 }
@@ -29,12 +29,14 @@ pub enum ConstantSolvingError {
     Relational,
     #[error("Constants don't support derivative functions")]
     Derivative,
-    #[error("Constants don't support select expressions")]
+    #[error("Cannot select with the condition")]
     Select,
     #[error("Constants don't support load expressions")]
     Load,
     #[error("Constants don't support image expressions")]
     ImageExpression,
+    #[error("Texture sampling is not a constant expression")]
+    ImageSample,
     #[error("Cannot access the type")]
     InvalidAccessBase,
     #[error("Cannot access at the index")]
@@ -49,6 +51,8 @@ pub enum ConstantSolvingError {
     InvalidUnaryOpArg,
     #[error("Cannot apply the binary op to the arguments")]
     InvalidBinaryOpArgs,
+    #[error("Cannot apply the math function to the argument")]
+    InvalidMathArg,
     #[error("Splat/swizzle type is not registered")]
     DestinationTypeNotFound,
     #[error("Not implemented: {0}")]
@@ -122,6 +126,7 @@ impl<'a> ConstantSolver<'a> {
                 let ty = ty.ok_or(ConstantSolvingError::DestinationTypeNotFound)?;
                 let components = pattern
                     .iter()
+                    .take(size as usize)
                     .map(|&sc| src_components[sc as usize])
                     .collect();
 
@@ -154,6 +159,16 @@ impl<'a> ConstantSolver<'a> {
 
                 self.binary_op(op, left_constant, right_constant)
             }
+            Expression::Math {
+                fun,
+                arg,
+                arg1: None,
+                arg2: None,
+            } => {
+                let arg_constant = self.solve(arg)?;
+
+                self.math(fun, arg_constant)
+            }
             Expression::Math { fun, .. } => {
                 Err(ConstantSolvingError::NotImplemented(format!("{:?}", fun)))
             }
@@ -188,17 +203,29 @@ impl<'a> ConstantSolver<'a> {
                 }
             }
 
+            Expression::Select {
+                condition,
+                accept,
+                reject,
+            } => {
+                let condition_constant = self.solve(condition)?;
+                let accept_constant = self.solve(accept)?;
+                let reject_constant = self.solve(reject)?;
+
+                self.select(condition_constant, accept_constant, reject_constant)
+            }
+
             Expression::Load { .. } => Err(ConstantSolvingError::Load),
-            Expression::Select { .. } => Err(ConstantSolvingError::Select),
             Expression::LocalVariable(_) => Err(ConstantSolvingError::LocalVariable),
             Expression::Derivative { .. } => Err(ConstantSolvingError::Derivative),
             Expression::Relational { .. } => Err(ConstantSolvingError::Relational),
             Expression::Call { .. } => Err(ConstantSolvingError::Call),
             Expression::FunctionArgument(_) => Err(ConstantSolvingError::FunctionArg),
             Expression::GlobalVariable(_) => Err(ConstantSolvingError::GlobalVariable),
-            Expression::ImageSample { .. }
-            | Expression::ImageLoad { .. }
-            | Expression::ImageQuery { .. } => Err(ConstantSolvingError::ImageExpression),
+            Expression::ImageSample { .. } => Err(ConstantSolvingError::ImageSample),
+            Expression::ImageLoad { .. } | Expression::ImageQuery { .. } => {
+                Err(ConstantSolvingError::ImageExpression)
+            }
         }
     }
 
@@ -334,6 +361,130 @@ impl<'a> ConstantSolver<'a> {
         }))
     }
 
+    fn math(
+        &mut self,
+        fun: MathFunction,
+        constant: Handle<Constant>,
+    ) -> Result<Handle<Constant>, ConstantSolvingError> {
+        let inner = match fun {
+            MathFunction::Abs | MathFunction::Sqrt => {
+                let mut inner = self.constants[constant].inner.clone();
+
+                match inner {
+                    ConstantInner::Scalar { ref mut value, .. } => match (fun, *value) {
+                        (MathFunction::Abs, ScalarValue::Sint(v)) => {
+                            *value = ScalarValue::Sint(v.wrapping_abs())
+                        }
+                        (MathFunction::Abs, ScalarValue::Uint(_)) => {}
+                        (MathFunction::Abs, ScalarValue::Float(v)) => {
+                            *value = ScalarValue::Float(v.abs())
+                        }
+                        (MathFunction::Sqrt, ScalarValue::Float(v)) => {
+                            *value = ScalarValue::Float(v.sqrt())
+                        }
+                        _ => return Err(ConstantSolvingError::InvalidMathArg),
+                    },
+                    ConstantInner::Composite {
+                        ty,
+                        ref mut components,
+                    } => {
+                        match self.types[ty].inner {
+                            TypeInner::Vector { .. } => (),
+                            _ => return Err(ConstantSolvingError::InvalidMathArg),
+                        }
+
+                        for component in components {
+                            *component = self.math(fun, *component)?
+                        }
+                    }
+                }
+
+                inner
+            }
+            MathFunction::Length => {
+                let (values, width) = self.float_components(constant)?;
+
+                ConstantInner::Scalar {
+                    width,
+                    value: ScalarValue::Float(values.iter().map(|v| v * v).sum::<f64>().sqrt()),
+                }
+            }
+            MathFunction::Determinant => {
+                let (components, size, width) = match self.constants[constant].inner {
+                    ConstantInner::Composite { ty, ref components } => match self.types[ty].inner {
+                        TypeInner::Matrix {
+                            columns,
+                            rows,
+                            width,
+                        } if columns == rows => (components.clone(), rows as usize, width),
+                        _ => return Err(ConstantSolvingError::InvalidMathArg),
+                    },
+                    ConstantInner::Scalar { .. } => {
+                        return Err(ConstantSolvingError::InvalidMathArg)
+                    }
+                };
+
+                // Matrices built from scalars have scalar components instead
+                // of columns, so flatten them before splitting into columns
+                let mut values = Vec::with_capacity(size * size);
+                for component in components {
+                    values.extend(self.float_components(component)?.0);
+                }
+                if values.len() != size * size {
+                    return Err(ConstantSolvingError::InvalidMathArg);
+                }
+                let matrix: Vec<_> = values.chunks(size).map(|column| column.to_vec()).collect();
+
+                ConstantInner::Scalar {
+                    width,
+                    value: ScalarValue::Float(determinant(&matrix)),
+                }
+            }
+            _ => return Err(ConstantSolvingError::NotImplemented(format!("{:?}", fun))),
+        };
+
+        Ok(self.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner,
+        }))
+    }
+
+    /// Returns the values of a float scalar or vector constant and its width
+    fn float_components(
+        &self,
+        constant: Handle<Constant>,
+    ) -> Result<(Vec<f64>, crate::Bytes), ConstantSolvingError> {
+        match self.constants[constant].inner {
+            ConstantInner::Scalar {
+                value: ScalarValue::Float(v),
+                width,
+            } => Ok((vec![v], width)),
+            ConstantInner::Composite { ty, ref components } => match self.types[ty].inner {
+                TypeInner::Vector {
+                    kind: ScalarKind::Float,
+                    width,
+                    ..
+                } => {
+                    let values = components
+                        .iter()
+                        .map(|&c| match self.constants[c].inner {
+                            ConstantInner::Scalar {
+                                value: ScalarValue::Float(v),
+                                ..
+                            } => Ok(v),
+                            _ => Err(ConstantSolvingError::InvalidMathArg),
+                        })
+                        .collect::<Result<_, _>>()?;
+
+                    Ok((values, width))
+                }
+                _ => Err(ConstantSolvingError::InvalidMathArg),
+            },
+            _ => Err(ConstantSolvingError::InvalidMathArg),
+        }
+    }
+
     fn binary_op(
         &mut self,
         op: BinaryOperator,
@@ -414,6 +565,11 @@ impl<'a> ConstantSolver<'a> {
                     },
                 };
 
+                let width = match value {
+                    ScalarValue::Bool(_) => crate::BOOL_WIDTH,
+                    _ => width,
+                };
+
                 ConstantInner::Scalar { value, width }
             }
             (&ConstantInner::Composite { ref components, ty }, &ConstantInner::Scalar { .. }) => {
@@ -421,6 +577,7 @@ impl<'a> ConstantSolver<'a> {
                 for comp in components.iter_mut() {
                     *comp = self.binary_op(op, *comp, right)?;
                 }
+                let ty = self.binary_op_type(op, ty)?;
                 ConstantInner::Composite { ty, components }
             }
             (&ConstantInner::Scalar { .. }, &ConstantInner::Composite { ref components, ty }) => {
@@ -428,6 +585,32 @@ impl<'a> ConstantSolver<'a> {
                 for comp in components.iter_mut() {
                     *comp = self.binary_op(op, left, *comp)?;
                 }
+                let ty = self.binary_op_type(op, ty)?;
+                ConstantInner::Composite { ty, components }
+            }
+            (
+                &ConstantInner::Composite {
+                    components: ref left_components,
+                    ty,
+                },
+                &ConstantInner::Composite {
+                    components: ref right_components,
+                    ty: right_ty,
+                },
+            ) if matches!(self.types[ty].inner, TypeInner::Vector { .. })
+                && matches!(self.types[right_ty].inner, TypeInner::Vector { .. })
+                && left_components.len() == right_components.len() =>
+            {
+                let pairs: Vec<_> = left_components
+                    .iter()
+                    .copied()
+                    .zip(right_components.iter().copied())
+                    .collect();
+                let components = pairs
+                    .into_iter()
+                    .map(|(left, right)| self.binary_op(op, left, right))
+                    .collect::<Result<_, _>>()?;
+                let ty = self.binary_op_type(op, ty)?;
                 ConstantInner::Composite { ty, components }
             }
             _ => return Err(ConstantSolvingError::InvalidBinaryOpArgs),
@@ -439,6 +622,144 @@ impl<'a> ConstantSolver<'a> {
             inner,
         }))
     }
+
+    /// Returns the type of a binary operation applied component-wise to a
+    /// vector, comparisons produce a boolean vector of the same size
+    fn binary_op_type(
+        &mut self,
+        op: BinaryOperator,
+        ty: Handle<Type>,
+    ) -> Result<Handle<Type>, ConstantSolvingError> {
+        match op {
+            BinaryOperator::Equal
+            | BinaryOperator::NotEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual
+            | BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual => match self.types[ty].inner {
+                TypeInner::Vector { size, .. } => Ok(self.types.fetch_or_append(Type {
+                    name: None,
+                    inner: TypeInner::Vector {
+                        size,
+                        kind: ScalarKind::Bool,
+                        width: crate::BOOL_WIDTH,
+                    },
+                })),
+                _ => Err(ConstantSolvingError::InvalidBinaryOpArgs),
+            },
+            _ => Ok(ty),
+        }
+    }
+
+    /// Picks the components of `accept` where `condition` is true and those
+    /// of `reject` elsewhere
+    fn select(
+        &mut self,
+        condition: Handle<Constant>,
+        accept: Handle<Constant>,
+        reject: Handle<Constant>,
+    ) -> Result<Handle<Constant>, ConstantSolvingError> {
+        match self.constants[condition].inner {
+            ConstantInner::Scalar {
+                value: ScalarValue::Bool(condition),
+                ..
+            } => Ok(if condition { accept } else { reject }),
+            ConstantInner::Composite { ref components, .. } => {
+                let conditions = components.clone();
+                let (ty, accept_components, reject_components) =
+                    match (&self.constants[accept].inner, &self.constants[reject].inner) {
+                        (
+                            &ConstantInner::Composite {
+                                ty,
+                                components: ref accept_components,
+                            },
+                            &ConstantInner::Composite {
+                                ty: reject_ty,
+                                components: ref reject_components,
+                            },
+                        ) if ty == reject_ty && accept_components.len() == conditions.len() => {
+                            (ty, accept_components.clone(), reject_components.clone())
+                        }
+                        _ => return Err(ConstantSolvingError::Select),
+                    };
+
+                let components = conditions
+                    .into_iter()
+                    .zip(accept_components.into_iter().zip(reject_components))
+                    .map(|(condition, (accept, reject))| self.select(condition, accept, reject))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(self.constants.fetch_or_append(Constant {
+                    name: None,
+                    specialization: None,
+                    inner: ConstantInner::Composite { ty, components },
+                }))
+            }
+            _ => Err(ConstantSolvingError::Select),
+        }
+    }
+}
+
+/// Returns whether every leaf of `expr` is a constant, solving an expression
+/// that isn't would still append the constants folded from its operands
+pub fn is_constant_expression(expressions: &Arena<Expression>, expr: Handle<Expression>) -> bool {
+    let is_constant = |expr| is_constant_expression(expressions, expr);
+
+    match expressions[expr] {
+        Expression::Constant(_) => true,
+        Expression::AccessIndex { base, .. } => is_constant(base),
+        Expression::Access { base, index } => is_constant(base) && is_constant(index),
+        Expression::Splat { value, .. } => is_constant(value),
+        Expression::Swizzle { vector, .. } => is_constant(vector),
+        Expression::Compose { ref components, .. } => components.iter().all(|&c| is_constant(c)),
+        Expression::Unary { expr, .. } => is_constant(expr),
+        Expression::Binary { left, right, .. } => is_constant(left) && is_constant(right),
+        Expression::Math {
+            arg,
+            arg1: None,
+            arg2: None,
+            ..
+        } => is_constant(arg),
+        Expression::As {
+            expr,
+            convert: Some(_),
+            ..
+        } => is_constant(expr),
+        Expression::ArrayLength(expr) => is_constant(expr),
+        Expression::Select {
+            condition,
+            accept,
+            reject,
+        } => is_constant(condition) && is_constant(accept) && is_constant(reject),
+        _ => false,
+    }
+}
+
+/// Computes the determinant of a square matrix stored in column major order
+/// using cofactor expansion along the first column
+fn determinant(matrix: &[Vec<f64>]) -> f64 {
+    if matrix.len() == 1 {
+        return matrix[0][0];
+    }
+
+    (0..matrix.len())
+        .map(|row| {
+            let minor: Vec<Vec<f64>> = matrix[1..]
+                .iter()
+                .map(|column| {
+                    column
+                        .iter()
+                        .enumerate()
+                        .filter(|&(i, _)| i != row)
+                        .map(|(_, &v)| v)
+                        .collect()
+                })
+                .collect();
+            let sign = if row % 2 == 0 { 1.0 } else { -1.0 };
+
+            sign * matrix[0][row] * determinant(&minor)
+        })
+        .sum()
 }
 
 #[cfg(test)]
@@ -446,8 +767,8 @@ mod tests {
     use std::vec;
 
     use crate::{
-        Arena, Constant, ConstantInner, Expression, ScalarKind, ScalarValue, Type, TypeInner,
-        UnaryOperator, VectorSize,
+        Arena, Constant, ConstantInner, Expression, MathFunction, ScalarKind, ScalarValue, Type,
+        TypeInner, UnaryOperator, VectorSize,
     };
 
     use super::ConstantSolver;
@@ -513,7 +834,7 @@ mod tests {
         });
 
         let mut solver = ConstantSolver {
-            types: &types,
+            types: &mut types,
             expressions: &expressions,
             constants: &mut constants,
         };
@@ -587,7 +908,7 @@ mod tests {
         });
 
         let mut solver = ConstantSolver {
-            types: &Arena::new(),
+            types: &mut Arena::new(),
             expressions: &expressions,
             constants: &mut constants,
         };
@@ -691,7 +1012,7 @@ mod tests {
         });
 
         let mut solver = ConstantSolver {
-            types: &types,
+            types: &mut types,
             expressions: &expressions,
             constants: &mut constants,
         };
@@ -739,4 +1060,129 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn math() {
+        let mut types = Arena::new();
+        let mut expressions = Arena::new();
+        let mut constants = Arena::new();
+
+        let vec_ty = types.append(Type {
+            name: None,
+            inner: TypeInner::Vector {
+                size: VectorSize::Bi,
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
+
+        let matrix_ty = types.append(Type {
+            name: None,
+            inner: TypeInner::Matrix {
+                columns: VectorSize::Bi,
+                rows: VectorSize::Bi,
+                width: 4,
+            },
+        });
+
+        let mut scalar = |value| {
+            constants.append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar {
+                    width: 4,
+                    value: ScalarValue::Float(value),
+                },
+            })
+        };
+
+        let (h1, h2, h3, h4) = (scalar(3.0), scalar(-4.0), scalar(1.0), scalar(2.0));
+
+        let vec1 = constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: vec_ty,
+                components: vec![h1, h2],
+            },
+        });
+
+        let vec2 = constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: vec_ty,
+                components: vec![h3, h4],
+            },
+        });
+
+        let matrix = constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: matrix_ty,
+                components: vec![vec1, vec2],
+            },
+        });
+
+        let vec_expr = expressions.append(Expression::Constant(vec1));
+        let matrix_expr = expressions.append(Expression::Constant(matrix));
+
+        let root1 = expressions.append(Expression::Math {
+            fun: MathFunction::Length,
+            arg: vec_expr,
+            arg1: None,
+            arg2: None,
+        });
+
+        let root2 = expressions.append(Expression::Math {
+            fun: MathFunction::Determinant,
+            arg: matrix_expr,
+            arg1: None,
+            arg2: None,
+        });
+
+        let root3 = expressions.append(Expression::Math {
+            fun: MathFunction::Abs,
+            arg: vec_expr,
+            arg1: None,
+            arg2: None,
+        });
+
+        let mut solver = ConstantSolver {
+            types: &mut types,
+            expressions: &expressions,
+            constants: &mut constants,
+        };
+
+        let res1 = solver.solve(root1).unwrap();
+        let res2 = solver.solve(root2).unwrap();
+        let res3 = solver.solve(root3).unwrap();
+
+        assert_eq!(
+            constants[res1].inner,
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(5.),
+            },
+        );
+
+        assert_eq!(
+            constants[res2].inner,
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(10.),
+            },
+        );
+
+        assert!(matches!(
+            constants[res3].inner,
+            ConstantInner::Composite { ty, ref components } if ty == vec_ty
+                && constants[components[1]].inner
+                    == ConstantInner::Scalar {
+                        width: 4,
+                        value: ScalarValue::Float(4.),
+                    }
+        ));
+    }
 }
@@ -2,7 +2,16 @@ use super::{
     constants::ConstantSolvingError,
     token::{SourceMetadata, Token, TokenValue},
 };
-use std::borrow::Cow;
+use crate::ScalarKind;
+use codespan_reporting::{
+    diagnostic::{Diagnostic, Label},
+    files::{Files, SimpleFile},
+    term::{
+        self,
+        termcolor::{ColorChoice, NoColor, StandardStream},
+    },
+};
+use std::{borrow::Cow, ops::Range};
 use thiserror::Error;
 
 fn join_with_comma(list: &[ExpectedToken]) -> String {
@@ -115,13 +124,101 @@ impl From<(SourceMetadata, ConstantSolvingError)> for ErrorKind {
 }
 
 #[derive(Debug, Error)]
-#[error("{kind}")]
 pub struct ParseError {
     pub kind: ErrorKind,
+    /// 1-based line number and column of the error, if the source was known
+    /// when it was produced
+    line_column: Option<(usize, usize)>,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.line_column {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl ParseError {
+    /// Creates an error which displays its location in `source`
+    pub(crate) fn with_source(kind: ErrorKind, source: &str) -> Self {
+        let mut error = ParseError::from(kind);
+        error.line_column = error.location(source);
+        error
+    }
+
+    /// Returns the byte range of the source that caused the error if available
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.kind.metadata().map(Into::into)
+    }
+
+    fn diagnostic(&self) -> Diagnostic<()> {
+        let diagnostic = Diagnostic::error().with_message(self.kind.to_string());
+
+        match self.span() {
+            Some(span) => diagnostic.with_labels(vec![Label::primary((), span)]),
+            None => diagnostic,
+        }
+    }
+
+    /// Emits a summary of the error to standard error stream.
+    pub fn emit_to_stderr(&self, source: &str) {
+        let files = SimpleFile::new("glsl", source);
+        let config = term::Config::default();
+        let writer = StandardStream::stderr(ColorChoice::Auto);
+        term::emit(&mut writer.lock(), &config, &files, &self.diagnostic())
+            .expect("cannot write error");
+    }
+
+    /// Emits a summary of the error to a string.
+    pub fn emit_to_string(&self, source: &str) -> String {
+        let files = SimpleFile::new("glsl", source);
+        let config = term::Config::default();
+        let mut writer = NoColor::new(Vec::new());
+        term::emit(&mut writer, &config, &files, &self.diagnostic()).expect("cannot write error");
+        String::from_utf8(writer.into_inner()).unwrap()
+    }
+
+    /// Returns the 1-based line number and column of the error if available
+    pub fn location(&self, source: &str) -> Option<(usize, usize)> {
+        let files = SimpleFile::new("glsl", source);
+        let location = files.location((), self.span()?.start).ok()?;
+        Some((location.line_number, location.column_number))
+    }
 }
 
 impl From<ErrorKind> for ParseError {
     fn from(kind: ErrorKind) -> Self {
-        ParseError { kind }
+        ParseError {
+            kind,
+            line_column: None,
+        }
+    }
+}
+
+/// Non-fatal issues found while parsing, they don't stop the module from
+/// being produced
+#[derive(Debug, Error, Clone)]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum Warning {
+    #[error("Unused variable: {1}")]
+    UnusedVariable(SourceMetadata, String),
+    /// Only raised for the `texture2D` and `textureCube` functions of the
+    /// compatibility profile
+    #[error("\"{1}\" is deprecated")]
+    Deprecated(SourceMetadata, String),
+    #[error("Implicit conversion from {1:?} to {2:?} may lose information")]
+    LossyConversion(SourceMetadata, ScalarKind, ScalarKind),
+}
+
+impl Warning {
+    /// Returns the source location the warning refers to
+    pub fn metadata(&self) -> SourceMetadata {
+        match *self {
+            Warning::UnusedVariable(metadata, _)
+            | Warning::Deprecated(metadata, _)
+            | Warning::LossyConversion(metadata, _, _) => metadata,
+        }
     }
 }
@@ -1,11 +1,55 @@
 use crate::{
-    proc::ensure_block_returns, Arena, BinaryOperator, Block, EntryPoint, Expression, Function,
-    FunctionArgument, FunctionResult, Handle, ImageQuery, LocalVariable, MathFunction,
-    RelationalFunction, SampleLevel, ScalarKind, Statement, StructMember, SwizzleComponent, Type,
-    TypeInner, VectorSize,
+    proc::{ensure_block_returns, ResolveContext},
+    Arena, ArraySize, Barrier, BinaryOperator, Binding, Block, BuiltIn, Constant, ConstantInner,
+    DerivativeAxis, EntryPoint, Expression, FastHashMap, Function, FunctionArgument,
+    FunctionResult, Handle, ImageClass, ImageDimension, ImageQuery, LocalVariable, MathFunction,
+    RelationalFunction, SampleLevel, ScalarKind, ScalarValue, Statement, StructMember,
+    SwizzleComponent, Type, TypeInner, VectorSize,
 };
 
-use super::{ast::*, error::ErrorKind, SourceMetadata};
+use super::{
+    super::Typifier,
+    ast::*,
+    error::{ErrorKind, Warning},
+    SourceMetadata,
+};
+
+/// Gives the callback of a custom intrinsic access to the function being built
+pub struct IntrinsicContext<'a, 'program, 'function> {
+    program: &'a mut Program<'program>,
+    ctx: &'a mut Context<'function>,
+    body: &'a mut Block,
+    meta: SourceMetadata,
+}
+
+impl IntrinsicContext<'_, '_, '_> {
+    /// The module being built, types and constants can be added to it
+    pub fn module(&mut self) -> &mut crate::Module {
+        &mut self.program.module
+    }
+
+    /// The span of the intrinsic call
+    pub fn meta(&self) -> SourceMetadata {
+        self.meta
+    }
+
+    /// Adds an expression to the function
+    pub fn add_expression(&mut self, expr: Expression) -> Handle<Expression> {
+        self.ctx.add_expression(expr, self.body)
+    }
+
+    /// Adds a statement to the function after all the expressions added so far
+    pub fn add_statement(&mut self, statement: Statement) {
+        self.ctx.emit_flush(self.body);
+        self.body.push(statement);
+        self.ctx.emit_start();
+    }
+
+    /// Resolves the type of an expression of the function
+    pub fn resolve_type(&mut self, expr: Handle<Expression>) -> Result<&TypeInner, ErrorKind> {
+        self.program.resolve_type(self.ctx, expr, self.meta)
+    }
+}
 
 impl Program<'_> {
     pub fn function_call(
@@ -23,27 +67,136 @@ impl Program<'_> {
 
         match fc {
             FunctionCallKind::TypeConstructor(ty) => {
+                if let TypeInner::Array { base, size, .. } = self.module.types[ty].inner {
+                    let ty = match size {
+                        ArraySize::Constant(constant) => {
+                            let len = self.module.constants[constant].to_array_length();
+                            if len != Some(args.len() as u32) {
+                                return Err(ErrorKind::SemanticError(
+                                    meta,
+                                    format!(
+                                        "Array constructor expects {} elements but got {}",
+                                        len.unwrap_or_default(),
+                                        args.len()
+                                    )
+                                    .into(),
+                                ));
+                            }
+
+                            ty
+                        }
+                        // `T[](...)` is sized by its arguments
+                        ArraySize::Dynamic => self.sized_array(base, args.len()),
+                    };
+
+                    let mut components = Vec::with_capacity(args.len());
+                    for (mut arg, meta) in args.iter().copied() {
+                        if let Some(kind) = self.module.types[base].inner.scalar_kind() {
+                            ctx.implicit_conversion(self, &mut arg, meta, kind)?;
+                        }
+
+                        // The elements of arrays of arrays must have the same size
+                        if let TypeInner::Array { .. } = self.module.types[base].inner {
+                            self.typifier_grow(ctx, arg, meta)?;
+                            if *ctx.typifier.get(arg, &self.module.types)
+                                != self.module.types[base].inner
+                            {
+                                return Err(ErrorKind::SemanticError(
+                                    meta,
+                                    "Array constructor element doesn't match the element type"
+                                        .into(),
+                                ));
+                            }
+                        }
+
+                        components.push(arg);
+                    }
+
+                    let h = ctx.add_expression(Expression::Compose { ty, components }, body);
+                    return Ok(Some(h));
+                }
+
                 let h = if args.len() == 1 {
-                    let is_vec = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
-                        TypeInner::Vector { .. } => true,
-                        _ => false,
+                    let (is_vec, matrix) = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                        TypeInner::Vector { .. } => (true, None),
+                        TypeInner::Matrix { rows, width, .. } => (false, Some((rows, width))),
+                        _ => (false, None),
                     };
 
+                    if let (&TypeInner::Vector { size, kind, width }, Some(matrix)) =
+                        (&self.module.types[ty].inner, matrix)
+                    {
+                        let h = self.vector_from_matrix(
+                            ctx,
+                            body,
+                            args[0].0,
+                            matrix,
+                            (size, kind, width),
+                        );
+                        return Ok(Some(h));
+                    }
+
                     match self.module.types[ty].inner {
-                        TypeInner::Vector { size, kind, .. } if !is_vec => {
+                        TypeInner::Vector { size, kind, width } if !is_vec => {
                             let (mut value, meta) = args[0];
-                            ctx.implicit_conversion(self, &mut value, meta, kind)?;
+                            if ctx.expr_scalar_kind(self, value, meta)? == Some(ScalarKind::Bool)
+                                || kind == ScalarKind::Bool
+                            {
+                                value = self.conversion(ctx, body, (value, meta), kind, width)?;
+                            } else {
+                                ctx.implicit_conversion(self, &mut value, meta, kind)?;
+                            }
+
+                            // Splats of constants are folded into a constant composite
+                            // so that they can be used in constant expressions
+                            let constant = match *self.resolve_type(ctx, value, meta)? {
+                                TypeInner::Scalar {
+                                    kind: value_kind,
+                                    width: value_width,
+                                } if value_kind == kind && value_width == width => {
+                                    self.try_solve_constant(ctx, value, meta)
+                                }
+                                _ => None,
+                            };
+
+                            match constant {
+                                Some(mut constant) => {
+                                    // Folds are done with `f64` precision, the splatted
+                                    // value is rounded to the width of the vector
+                                    if let ConstantInner::Scalar {
+                                        value: ScalarValue::Float(value),
+                                        width: 4,
+                                    } = self.module.constants[constant].inner
+                                    {
+                                        constant =
+                                            self.module.constants.fetch_or_append(Constant {
+                                                name: None,
+                                                specialization: None,
+                                                inner: ConstantInner::Scalar {
+                                                    value: ScalarValue::Float(value as f32 as f64),
+                                                    width: 4,
+                                                },
+                                            });
+                                    }
+
+                                    let constant =
+                                        self.module.constants.fetch_or_append(Constant {
+                                            name: None,
+                                            specialization: None,
+                                            inner: ConstantInner::Composite {
+                                                ty,
+                                                components: vec![constant; size as usize],
+                                            },
+                                        });
 
-                            ctx.add_expression(Expression::Splat { size, value }, body)
+                                    ctx.add_expression(Expression::Constant(constant), body)
+                                }
+                                None => ctx.add_expression(Expression::Splat { size, value }, body),
+                            }
+                        }
+                        TypeInner::Scalar { kind, width } => {
+                            self.conversion(ctx, body, args[0], kind, width)?
                         }
-                        TypeInner::Scalar { kind, width } => ctx.add_expression(
-                            Expression::As {
-                                kind,
-                                expr: args[0].0,
-                                convert: Some(width),
-                            },
-                            body,
-                        ),
                         TypeInner::Vector { size, kind, width } => {
                             let expr = ctx.add_expression(
                                 Expression::Swizzle {
@@ -54,14 +207,7 @@ impl Program<'_> {
                                 body,
                             );
 
-                            ctx.add_expression(
-                                Expression::As {
-                                    kind,
-                                    expr,
-                                    convert: Some(width),
-                                },
-                                body,
-                            )
+                            self.conversion(ctx, body, (expr, args[0].1), kind, width)?
                         }
                         TypeInner::Matrix { columns, rows, .. } => {
                             // TODO: casts
@@ -128,13 +274,99 @@ impl Program<'_> {
                         _ => return Err(ErrorKind::SemanticError(meta, "Bad cast".into())),
                     }
                 } else {
+                    if let TypeInner::Vector { size, .. } = self.module.types[ty].inner {
+                        let mut count = 0;
+                        let mut last = 0;
+
+                        for &(arg, meta) in args.iter() {
+                            last = match *self.resolve_type(ctx, arg, meta)? {
+                                TypeInner::Vector { size, .. } => size as usize,
+                                TypeInner::Matrix { columns, rows, .. } => {
+                                    columns as usize * rows as usize
+                                }
+                                _ => 1,
+                            };
+                            count += last;
+                        }
+
+                        // The last argument can have more components than
+                        // needed but every argument has to be used
+                        if count < size as usize || count - last >= size as usize {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!(
+                                    "Vector constructor expects {} components but got {}",
+                                    size as usize, count
+                                )
+                                .into(),
+                            ));
+                        }
+                    }
+
                     let mut components = Vec::with_capacity(args.len());
+                    // Components of the vector left to fill
+                    let mut remaining = match self.module.types[ty].inner {
+                        TypeInner::Vector { size, .. } => Some(size as u32),
+                        _ => None,
+                    };
 
-                    for (mut arg, meta) in args.iter().copied() {
-                        if let Some(kind) = self.module.types[ty].inner.scalar_kind() {
-                            ctx.implicit_conversion(self, &mut arg, meta, kind)?;
+                    for (arg, meta) in args.iter().copied() {
+                        // Matrices used in vector constructors are consumed column by column
+                        let columns = match (
+                            &self.module.types[ty].inner,
+                            self.resolve_type(ctx, arg, meta)?,
+                        ) {
+                            (&TypeInner::Vector { .. }, &TypeInner::Matrix { columns, .. }) => {
+                                Some(columns as u32)
+                            }
+                            _ => None,
+                        };
+                        let columns: Vec<_> = if let Some(columns) = columns {
+                            (0..columns)
+                                .map(|index| {
+                                    let access = Expression::AccessIndex { base: arg, index };
+                                    ctx.add_expression(access, body)
+                                })
+                                .collect()
+                        } else {
+                            vec![arg]
+                        };
+
+                        for mut arg in columns {
+                            if let Some(ref mut remaining) = remaining {
+                                if *remaining == 0 {
+                                    break;
+                                }
+
+                                let arg_size = match *self.resolve_type(ctx, arg, meta)? {
+                                    TypeInner::Vector { size, .. } => size as u32,
+                                    _ => 1,
+                                };
+                                if arg_size > *remaining {
+                                    let access = match *remaining {
+                                        1 => Expression::AccessIndex {
+                                            base: arg,
+                                            index: 0,
+                                        },
+                                        size => Expression::Swizzle {
+                                            size: match size {
+                                                2 => VectorSize::Bi,
+                                                _ => VectorSize::Tri,
+                                            },
+                                            vector: arg,
+                                            pattern: SwizzleComponent::XYZW,
+                                        },
+                                    };
+                                    arg = ctx.add_expression(access, body);
+                                }
+                                *remaining -= arg_size.min(*remaining);
+                            }
+
+                            if let Some(kind) = self.module.types[ty].inner.scalar_kind() {
+                                ctx.implicit_conversion(self, &mut arg, meta, kind)?;
+                            }
+                            components.push(arg)
                         }
-                        components.push(arg)
                     }
 
                     ctx.add_expression(Expression::Compose { ty, components }, body)
@@ -144,29 +376,174 @@ impl Program<'_> {
             }
             FunctionCallKind::Function(name) => {
                 match name.as_str() {
-                    "sampler2D" => {
+                    "sampler1D" | "sampler1DArray" | "sampler2D" | "sampler2DArray"
+                    | "sampler2DMS" | "sampler2DMSArray" | "sampler3D" | "samplerCube"
+                    | "samplerCubeArray" | "isampler1D" | "isampler1DArray" | "isampler2D"
+                    | "isampler2DArray" | "isampler2DMS" | "isampler2DMSArray" | "isampler3D"
+                    | "isamplerCube" | "isamplerCubeArray" | "usampler1D" | "usampler1DArray"
+                    | "usampler2D" | "usampler2DArray" | "usampler2DMS" | "usampler2DMSArray"
+                    | "usampler3D" | "usamplerCube" | "usamplerCubeArray" => {
+                        if args.len() != 2 {
+                            return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
+                        }
+                        self.check_sampler_comparison(ctx, &name, args[1], false)?;
+
+                        // The sampled type of the texture decides the result
+                        // type of the sampling so it must match the sampler's
+                        let kind = match name.as_bytes()[0] {
+                            b'i' => ScalarKind::Sint,
+                            b'u' => ScalarKind::Uint,
+                            _ => ScalarKind::Float,
+                        };
+                        match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Image {
+                                class: ImageClass::Sampled { kind: sampled, .. },
+                                ..
+                            } if sampled != kind => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!(
+                                        "\"{}\" doesn't match the sampled type of the texture",
+                                        name
+                                    )
+                                    .into(),
+                                ))
+                            }
+                            TypeInner::Image {
+                                class: ImageClass::Depth,
+                                ..
+                            } => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!(
+                                        "\"{}\" can't combine a texture used with a shadow sampler",
+                                        name
+                                    )
+                                    .into(),
+                                ))
+                            }
+                            _ => {}
+                        }
+                        if let Expression::GlobalVariable(handle) = *ctx.get_expression(args[0].0) {
+                            self.sampled_textures.insert(handle);
+                        }
+
+                        ctx.samplers.insert(args[0].0, args[1].0);
+                        Ok(Some(args[0].0))
+                    }
+                    "sampler1DShadow"
+                    | "sampler1DArrayShadow"
+                    | "sampler2DShadow"
+                    | "sampler2DArrayShadow"
+                    | "samplerCubeShadow"
+                    | "samplerCubeArrayShadow" => {
                         if args.len() != 2 {
                             return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
                         }
+                        self.check_sampler_comparison(ctx, &name, args[1], true)?;
+
+                        // The IR only does comparisons on depth textures but the
+                        // textures are declared as sampled, so they become depth
+                        // textures once they're combined with a shadow sampler
+                        let handle = match *ctx.get_expression(args[0].0) {
+                            Expression::GlobalVariable(handle) => handle,
+                            _ => {
+                                return Err(ErrorKind::NotImplemented(
+                                    args[0].1,
+                                    "shadow samplers of texture arguments",
+                                ))
+                            }
+                        };
+                        if self.sampled_textures.contains(&handle) {
+                            return Err(ErrorKind::SemanticError(
+                                args[0].1,
+                                format!(
+                                    "\"{}\" can't combine a texture used with a regular sampler",
+                                    name
+                                )
+                                .into(),
+                            ));
+                        }
+
+                        let ty = self.module.global_variables[handle].ty;
+                        match self.module.types[ty].inner {
+                            TypeInner::Image {
+                                dim,
+                                arrayed,
+                                class:
+                                    ImageClass::Sampled {
+                                        kind: ScalarKind::Float,
+                                        multi: false,
+                                    },
+                            } => {
+                                let depth = self.module.types.fetch_or_append(Type {
+                                    name: None,
+                                    inner: TypeInner::Image {
+                                        dim,
+                                        arrayed,
+                                        class: ImageClass::Depth,
+                                    },
+                                });
+                                self.module.global_variables.get_mut(handle).ty = depth;
+                                // The texture may already be resolved with its old type
+                                ctx.typifier = Typifier::new();
+                            }
+                            TypeInner::Image {
+                                class: ImageClass::Depth,
+                                ..
+                            } => {}
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!("\"{}\" needs a single sampled float texture", name)
+                                        .into(),
+                                ))
+                            }
+                        }
+
                         ctx.samplers.insert(args[0].0, args[1].0);
                         Ok(Some(args[0].0))
                     }
-                    "texture" => {
+                    "texture" | "texture2D" | "textureCube" => {
+                        if name != "texture" && self.profile != Profile::Compatibility {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!(
+                                    "\"{}\" is only available in the compatibility profile",
+                                    name
+                                )
+                                .into(),
+                            ));
+                        }
+                        if name != "texture" {
+                            self.warnings.push(Warning::Deprecated(meta, name.clone()))
+                        }
                         if !(2..=3).contains(&args.len()) {
                             return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
                         }
                         if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, coord_meta) =
+                                self.sample_coordinate(ctx, body, args[1])?;
+                            let (coordinate, depth_ref) =
+                                self.depth_reference(ctx, body, args[0], (coordinate, coord_meta))?;
+                            let (coordinate, array_index) = self.image_coordinate(
+                                ctx,
+                                body,
+                                args[0],
+                                (coordinate, coord_meta),
+                            )?;
+
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageSample {
                                     image: args[0].0,
                                     sampler,
-                                    coordinate: args[1].0,
-                                    array_index: None, //TODO
-                                    offset: None,      //TODO
+                                    coordinate,
+                                    array_index,
+                                    offset: None, //TODO
                                     level: args.get(2).map_or(SampleLevel::Auto, |&(expr, _)| {
                                         SampleLevel::Bias(expr)
                                     }),
-                                    depth_ref: None,
+                                    depth_ref,
                                 },
                                 body,
                             )))
@@ -174,6 +551,170 @@ impl Program<'_> {
                             Err(ErrorKind::SemanticError(meta, "Bad call to texture".into()))
                         }
                     }
+                    "textureOffset" => {
+                        if !(3..=4).contains(&args.len()) {
+                            return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
+                        }
+                        if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, coord_meta) =
+                                self.sample_coordinate(ctx, body, args[1])?;
+                            let (coordinate, depth_ref) =
+                                self.depth_reference(ctx, body, args[0], (coordinate, coord_meta))?;
+                            let (coordinate, array_index) = self.image_coordinate(
+                                ctx,
+                                body,
+                                args[0],
+                                (coordinate, coord_meta),
+                            )?;
+                            let offset = self.texel_offset(ctx, args[2])?;
+
+                            Ok(Some(ctx.add_expression(
+                                Expression::ImageSample {
+                                    image: args[0].0,
+                                    sampler,
+                                    coordinate,
+                                    array_index,
+                                    offset: Some(offset),
+                                    level: args.get(3).map_or(SampleLevel::Auto, |&(expr, _)| {
+                                        SampleLevel::Bias(expr)
+                                    }),
+                                    depth_ref,
+                                },
+                                body,
+                            )))
+                        } else {
+                            Err(ErrorKind::SemanticError(
+                                meta,
+                                "Bad call to textureOffset".into(),
+                            ))
+                        }
+                    }
+                    "textureGather" | "textureGatherOffset" | "textureGatherOffsets" => {
+                        let min_args = match name.as_str() {
+                            "textureGather" => 2,
+                            _ => 3,
+                        };
+                        if !(min_args..=min_args + 1).contains(&args.len()) {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                min_args,
+                                args.len(),
+                                meta,
+                            ));
+                        }
+                        if !ctx.samplers.contains_key(&args[0].0) {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!("Bad call to {}", name).into(),
+                            ));
+                        }
+
+                        // A float after the coordinate is the reference of the
+                        // depth comparison form, the component select is an int
+                        let depth_ref = match args.get(2) {
+                            Some(&(expr, ref_meta)) => {
+                                ctx.expr_scalar_kind(self, expr, ref_meta)?
+                                    == Some(ScalarKind::Float)
+                            }
+                            None => false,
+                        };
+                        if depth_ref {
+                            let shadow = matches!(
+                                *self.resolve_type(ctx, args[0].0, args[0].1)?,
+                                TypeInner::Image {
+                                    class: ImageClass::Depth,
+                                    ..
+                                }
+                            );
+                            if !shadow {
+                                return Err(ErrorKind::SemanticError(
+                                    args[2].1,
+                                    format!(
+                                        "The depth comparison form of {} needs a shadow sampler",
+                                        name
+                                    )
+                                    .into(),
+                                ));
+                            }
+                        }
+
+                        let offset_arg = if depth_ref { 3 } else { 2 };
+                        if name != "textureGather" {
+                            if offset_arg >= args.len() {
+                                return Err(ErrorKind::wrong_function_args(
+                                    name,
+                                    offset_arg + 1,
+                                    args.len(),
+                                    meta,
+                                ));
+                            }
+
+                            let (offset, offset_meta) = args[offset_arg];
+                            let offset = self.texel_offset(ctx, (offset, offset_meta))?;
+                            if name == "textureGatherOffsets" {
+                                let count = match self.module.constants[offset].inner {
+                                    ConstantInner::Composite { ty, ref components } => {
+                                        match self.module.types[ty].inner {
+                                            TypeInner::Array { .. } => Some(components.len()),
+                                            _ => None,
+                                        }
+                                    }
+                                    _ => None,
+                                };
+
+                                if count != Some(4) {
+                                    return Err(ErrorKind::SemanticError(
+                                        offset_meta,
+                                        "textureGatherOffsets expects an array of four offsets"
+                                            .into(),
+                                    ));
+                                }
+                            }
+                        }
+
+                        // The IR has no way to express gathers
+                        Err(ErrorKind::NotImplemented(meta, "texture gathering"))
+                    }
+                    "textureClampARB"
+                    | "textureOffsetClampARB"
+                    | "textureGradClampARB"
+                    | "textureGradOffsetClampARB" => {
+                        if !self.extensions.contains("GL_ARB_sparse_texture_clamp") {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!(
+                                    "\"{}\" requires the GL_ARB_sparse_texture_clamp extension",
+                                    name
+                                )
+                                .into(),
+                            ));
+                        }
+
+                        let (min, max) = match name.as_str() {
+                            "textureClampARB" => (3, 4),
+                            "textureOffsetClampARB" => (4, 5),
+                            "textureGradClampARB" => (5, 5),
+                            _ => (6, 6),
+                        };
+                        if !(min..=max).contains(&args.len()) {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                min,
+                                args.len(),
+                                meta,
+                            ));
+                        }
+                        if !ctx.samplers.contains_key(&args[0].0) {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!("Bad call to {}", name).into(),
+                            ));
+                        }
+
+                        // Sampling has no minimum level of detail in the IR and
+                        // dropping the clamp would change the sampled result
+                        Err(ErrorKind::NotImplemented(meta, "level of detail clamping"))
+                    }
                     "textureLod" => {
                         if args.len() != 3 {
                             return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
@@ -187,15 +728,26 @@ impl Program<'_> {
                             body,
                         );
                         if let Some(sampler) = ctx.samplers.get(&args[0].0).copied() {
+                            let (coordinate, coord_meta) =
+                                self.sample_coordinate(ctx, body, args[1])?;
+                            let (coordinate, depth_ref) =
+                                self.depth_reference(ctx, body, args[0], (coordinate, coord_meta))?;
+                            let (coordinate, array_index) = self.image_coordinate(
+                                ctx,
+                                body,
+                                args[0],
+                                (coordinate, coord_meta),
+                            )?;
+
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageSample {
                                     image: args[0].0,
                                     sampler,
-                                    coordinate: args[1].0,
-                                    array_index: None, //TODO
-                                    offset: None,      //TODO
+                                    coordinate,
+                                    array_index,
+                                    offset: None, //TODO
                                     level: SampleLevel::Exact(exact),
-                                    depth_ref: None,
+                                    depth_ref,
                                 },
                                 body,
                             )))
@@ -206,6 +758,19 @@ impl Program<'_> {
                             ))
                         }
                     }
+                    "textureQueryLod" => {
+                        if args.len() != 2 {
+                            return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
+                        }
+                        if !ctx.samplers.contains_key(&args[0].0) {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                "Bad call to textureQueryLod".into(),
+                            ));
+                        }
+
+                        Ok(Some(self.query_lod(ctx, body, args[0], args[1])?))
+                    }
                     "textureSize" => {
                         if !(1..=2).contains(&args.len()) {
                             return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
@@ -221,60 +786,63 @@ impl Program<'_> {
                             body,
                         )))
                     }
-                    "texelFetch" => {
-                        if args.len() != 3 {
-                            return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
+                    "texelFetch" | "texelFetchOffset" => {
+                        let expected = if name == "texelFetch" { 3 } else { 4 };
+                        if args.len() != expected {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                expected,
+                                args.len(),
+                                meta,
+                            ));
                         }
                         if ctx.samplers.get(&args[0].0).is_some() {
-                            let (arrayed, dims) =
-                                match *self.resolve_type(ctx, args[0].0, args[0].1)? {
-                                    TypeInner::Image { arrayed, dim, .. } => (arrayed, dim),
-                                    _ => (false, crate::ImageDimension::D1),
+                            let (mut coordinate, array_index) =
+                                self.image_coordinate(ctx, body, args[0], args[1])?;
+
+                            // Loads have no offset so it's added to the texel coordinate
+                            if let Some(&(offset, offset_meta)) = args.get(3) {
+                                self.texel_offset(ctx, (offset, offset_meta))?;
+
+                                let size = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                                    TypeInner::Image {
+                                        dim: ImageDimension::D1,
+                                        ..
+                                    } => None,
+                                    TypeInner::Image {
+                                        dim: ImageDimension::D2,
+                                        ..
+                                    } => Some(VectorSize::Bi),
+                                    _ => Some(VectorSize::Tri),
                                 };
+                                let matches = match *self.resolve_type(ctx, offset, offset_meta)? {
+                                    TypeInner::Scalar {
+                                        kind: ScalarKind::Sint,
+                                        ..
+                                    } => size.is_none(),
+                                    TypeInner::Vector {
+                                        kind: ScalarKind::Sint,
+                                        size: offset_size,
+                                        ..
+                                    } => size == Some(offset_size),
+                                    _ => false,
+                                };
+                                if !matches {
+                                    return Err(ErrorKind::SemanticError(
+                                        offset_meta,
+                                        "Texel offset doesn't match the image dimension".into(),
+                                    ));
+                                }
 
-                            let (coordinate, array_index) = if arrayed {
-                                (
-                                    match dims {
-                                        crate::ImageDimension::D1 => ctx.add_expression(
-                                            Expression::AccessIndex {
-                                                base: args[1].0,
-                                                index: 0,
-                                            },
-                                            body,
-                                        ),
-                                        crate::ImageDimension::D2 => ctx.add_expression(
-                                            Expression::Swizzle {
-                                                size: VectorSize::Bi,
-                                                vector: args[1].0,
-                                                pattern: SwizzleComponent::XYZW,
-                                            },
-                                            body,
-                                        ),
-                                        _ => ctx.add_expression(
-                                            Expression::Swizzle {
-                                                size: VectorSize::Tri,
-                                                vector: args[1].0,
-                                                pattern: SwizzleComponent::XYZW,
-                                            },
-                                            body,
-                                        ),
+                                coordinate = ctx.add_expression(
+                                    Expression::Binary {
+                                        op: BinaryOperator::Add,
+                                        left: coordinate,
+                                        right: offset,
                                     },
-                                    Some(ctx.add_expression(
-                                        Expression::AccessIndex {
-                                            base: args[1].0,
-                                            index: match dims {
-                                                crate::ImageDimension::D1 => 1,
-                                                crate::ImageDimension::D2 => 2,
-                                                crate::ImageDimension::D3 => 3,
-                                                crate::ImageDimension::Cube => 2,
-                                            },
-                                        },
-                                        body,
-                                    )),
-                                )
-                            } else {
-                                (args[1].0, None)
-                            };
+                                    body,
+                                );
+                            }
 
                             Ok(Some(ctx.add_expression(
                                 Expression::ImageLoad {
@@ -288,33 +856,218 @@ impl Program<'_> {
                         } else {
                             Err(ErrorKind::SemanticError(
                                 meta,
-                                "Bad call to texelFetch".into(),
+                                format!("Bad call to {}", name).into(),
                             ))
                         }
                     }
-                    "ceil" | "round" | "floor" | "fract" | "trunc" | "sin" | "abs" | "sqrt"
-                    | "inversesqrt" | "exp" | "exp2" | "sign" | "transpose" | "inverse"
-                    | "normalize" | "sinh" | "cos" | "cosh" | "tan" | "tanh" | "acos" | "asin"
-                    | "log" | "log2" | "length" | "determinant" | "bitCount"
-                    | "bitfieldReverse" => {
-                        if args.len() != 1 {
-                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                    "barrier"
+                    | "memoryBarrier"
+                    | "memoryBarrierShared"
+                    | "memoryBarrierBuffer"
+                    | "groupMemoryBarrier" => {
+                        if !args.is_empty() {
+                            return Err(ErrorKind::wrong_function_args(name, 0, args.len(), meta));
                         }
-                        Ok(Some(ctx.add_expression(
-                            Expression::Math {
-                                fun: match name.as_str() {
-                                    "ceil" => MathFunction::Ceil,
-                                    "round" => MathFunction::Round,
-                                    "floor" => MathFunction::Floor,
-                                    "fract" => MathFunction::Fract,
-                                    "trunc" => MathFunction::Trunc,
-                                    "sin" => MathFunction::Sin,
-                                    "abs" => MathFunction::Abs,
+
+                        // The stages of the entry points reaching the call are
+                        // only known once they are added
+                        if name == "barrier" && ctx.barrier.is_none() {
+                            ctx.barrier = Some(meta);
+                        }
+
+                        let barrier = match name.as_str() {
+                            "memoryBarrierShared" => Barrier::WORK_GROUP,
+                            "memoryBarrierBuffer" => Barrier::STORAGE,
+                            "memoryBarrier" | "groupMemoryBarrier" => {
+                                Barrier::STORAGE | Barrier::WORK_GROUP
+                            }
+                            _ => Barrier::WORK_GROUP,
+                        };
+
+                        ctx.emit_flush(body);
+                        body.push(Statement::Barrier(barrier));
+                        ctx.emit_start();
+
+                        Ok(None)
+                    }
+                    "imageLoad" | "imageStore" => {
+                        let expected = if name == "imageLoad" { 2 } else { 3 };
+                        if args.len() != expected {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                expected,
+                                args.len(),
+                                meta,
+                            ));
+                        }
+
+                        let kind = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Image {
+                                class: ImageClass::Storage(format),
+                                ..
+                            } => ScalarKind::from(format),
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!("\"{}\" expects a storage image", name).into(),
+                                ))
+                            }
+                        };
+                        let (coordinate, array_index) =
+                            self.image_coordinate(ctx, body, args[0], args[1])?;
+
+                        if name == "imageLoad" {
+                            return Ok(Some(ctx.add_expression(
+                                Expression::ImageLoad {
+                                    image: args[0].0,
+                                    coordinate,
+                                    array_index,
+                                    index: None,
+                                },
+                                body,
+                            )));
+                        }
+
+                        // The stored value must match the texel type of the image format
+                        let (mut value, value_meta) = args[2];
+                        ctx.implicit_conversion(self, &mut value, value_meta, kind)?;
+                        match *self.resolve_type(ctx, value, value_meta)? {
+                            TypeInner::Vector {
+                                size: VectorSize::Quad,
+                                kind: value_kind,
+                                ..
+                            } if value_kind == kind => {}
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    value_meta,
+                                    "imageStore value doesn't match the image format".into(),
+                                ))
+                            }
+                        }
+
+                        ctx.emit_flush(body);
+                        body.push(Statement::ImageStore {
+                            image: args[0].0,
+                            coordinate,
+                            array_index,
+                            value,
+                        });
+                        ctx.emit_start();
+
+                        Ok(None)
+                    }
+                    "subpassLoad" => {
+                        if !(1..=2).contains(&args.len()) {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+
+                        let multi = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Image {
+                                class: ImageClass::Sampled { multi, .. },
+                                ..
+                            } => multi,
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    "Bad call to subpassLoad".into(),
+                                ))
+                            }
+                        };
+                        if multi != (args.len() == 2) {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                if multi { 2 } else { 1 },
+                                args.len(),
+                                meta,
+                            ));
+                        }
+
+                        // The attachment is read at the position of the fragment
+                        let frag_coord = self
+                            .lookup_variable(ctx, body, "gl_FragCoord", meta)?
+                            .ok_or_else(|| {
+                                ErrorKind::SemanticError(meta, "Bad call to subpassLoad".into())
+                            })?;
+                        let frag_coord = ctx.hir_exprs.append(HirExpr {
+                            kind: HirExprKind::Variable(frag_coord),
+                            meta,
+                        });
+                        let frag_coord = ctx.lower_expect(self, frag_coord, false, body)?.0;
+                        let position = ctx.add_expression(
+                            Expression::Swizzle {
+                                size: VectorSize::Bi,
+                                vector: frag_coord,
+                                pattern: [
+                                    SwizzleComponent::X,
+                                    SwizzleComponent::Y,
+                                    SwizzleComponent::X,
+                                    SwizzleComponent::X,
+                                ],
+                            },
+                            body,
+                        );
+                        let coordinate = ctx.add_expression(
+                            Expression::As {
+                                expr: position,
+                                kind: ScalarKind::Sint,
+                                convert: Some(4),
+                            },
+                            body,
+                        );
+
+                        let index = match args.get(1) {
+                            Some(&(sample, _)) => sample,
+                            None => {
+                                let level = self.module.constants.fetch_or_append(Constant {
+                                    name: None,
+                                    specialization: None,
+                                    inner: ConstantInner::Scalar {
+                                        width: 4,
+                                        value: ScalarValue::Sint(0),
+                                    },
+                                });
+                                ctx.add_expression(Expression::Constant(level), body)
+                            }
+                        };
+
+                        Ok(Some(ctx.add_expression(
+                            Expression::ImageLoad {
+                                image: args[0].0,
+                                coordinate,
+                                array_index: None,
+                                index: Some(index),
+                            },
+                            body,
+                        )))
+                    }
+                    "ceil" | "round" | "floor" | "fract" | "trunc" | "sin" | "sqrt"
+                    | "inversesqrt" | "exp" | "exp2" | "transpose" | "inverse" | "normalize"
+                    | "sinh" | "cos" | "cosh" | "tan" | "tanh" | "acos" | "asin" | "log"
+                    | "log2" | "length" | "determinant" | "bitCount" | "bitfieldReverse" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+
+                        // The length of a scalar is its absolute value, emit
+                        // that since not all backends have a scalar `length`
+                        let length = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Scalar { .. } => MathFunction::Abs,
+                            _ => MathFunction::Length,
+                        };
+
+                        Ok(Some(ctx.add_expression(
+                            Expression::Math {
+                                fun: match name.as_str() {
+                                    "ceil" => MathFunction::Ceil,
+                                    "round" => MathFunction::Round,
+                                    "floor" => MathFunction::Floor,
+                                    "fract" => MathFunction::Fract,
+                                    "trunc" => MathFunction::Trunc,
+                                    "sin" => MathFunction::Sin,
                                     "sqrt" => MathFunction::Sqrt,
                                     "inversesqrt" => MathFunction::InverseSqrt,
                                     "exp" => MathFunction::Exp,
                                     "exp2" => MathFunction::Exp2,
-                                    "sign" => MathFunction::Sign,
                                     "transpose" => MathFunction::Transpose,
                                     "inverse" => MathFunction::Inverse,
                                     "normalize" => MathFunction::Normalize,
@@ -327,7 +1080,7 @@ impl Program<'_> {
                                     "asin" => MathFunction::Asin,
                                     "log" => MathFunction::Log,
                                     "log2" => MathFunction::Log2,
-                                    "length" => MathFunction::Length,
+                                    "length" => length,
                                     "determinant" => MathFunction::Determinant,
                                     "bitCount" => MathFunction::CountOneBits,
                                     "bitfieldReverse" => MathFunction::ReverseBits,
@@ -340,7 +1093,45 @@ impl Program<'_> {
                             body,
                         )))
                     }
+                    "abs" | "sign" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+
+                        // Both keep the kind of their argument so it must be signed
+                        match ctx.expr_scalar_kind(self, args[0].0, args[0].1)? {
+                            Some(ScalarKind::Sint) | Some(ScalarKind::Float) => {}
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!(
+                                        "\"{}\" expects a signed integer or float argument",
+                                        name
+                                    )
+                                    .into(),
+                                ))
+                            }
+                        }
+
+                        Ok(Some(ctx.add_expression(
+                            Expression::Math {
+                                fun: match name.as_str() {
+                                    "abs" => MathFunction::Abs,
+                                    _ => MathFunction::Sign,
+                                },
+                                arg: args[0].0,
+                                arg1: None,
+                                arg2: None,
+                            },
+                            body,
+                        )))
+                    }
                     "atan" => {
+                        let mut args = args;
+                        if args.len() == 2 {
+                            self.same_float_operands(ctx, &name, &mut args, meta)?;
+                        }
+
                         let expr = match args.len() {
                             1 => Expression::Math {
                                 fun: MathFunction::Atan,
@@ -377,6 +1168,28 @@ impl Program<'_> {
                             self, &mut left, left_meta, &mut right, right_meta,
                         )?;
 
+                        if ctx.expr_scalar_kind(self, left, left_meta)? != Some(ScalarKind::Float)
+                            || ctx.expr_scalar_kind(self, right, right_meta)?
+                                != Some(ScalarKind::Float)
+                        {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                "mod only works on floats, use the % operator for integers".into(),
+                            ));
+                        }
+
+                        // `mod(vec, float)` divides every component by the scalar
+                        if let TypeInner::Vector { size, .. } =
+                            *self.resolve_type(ctx, left, left_meta)?
+                        {
+                            if let TypeInner::Scalar { .. } =
+                                *self.resolve_type(ctx, right, right_meta)?
+                            {
+                                right = ctx
+                                    .add_expression(Expression::Splat { size, value: right }, body);
+                            }
+                        }
+
                         Ok(Some(ctx.add_expression(
                             Expression::Binary {
                                 op: BinaryOperator::Modulo,
@@ -386,19 +1199,20 @@ impl Program<'_> {
                             body,
                         )))
                     }
-                    "pow" | "dot" | "max" | "min" | "reflect" | "cross" | "outerProduct"
-                    | "distance" | "step" | "modf" | "frexp" | "ldexp" => {
+                    "pow" | "dot" | "cross" | "outerProduct" | "distance" | "step" | "modf"
+                    | "frexp" | "ldexp" => {
                         if args.len() != 2 {
                             return Err(ErrorKind::wrong_function_args(name, 2, args.len(), meta));
                         }
+                        let mut args = args;
+                        if name == "pow" {
+                            self.same_float_operands(ctx, &name, &mut args, meta)?;
+                        }
                         Ok(Some(ctx.add_expression(
                             Expression::Math {
                                 fun: match name.as_str() {
                                     "pow" => MathFunction::Pow,
                                     "dot" => MathFunction::Dot,
-                                    "max" => MathFunction::Max,
-                                    "min" => MathFunction::Min,
-                                    "reflect" => MathFunction::Reflect,
                                     "cross" => MathFunction::Cross,
                                     "outerProduct" => MathFunction::Outer,
                                     "distance" => MathFunction::Distance,
@@ -415,6 +1229,79 @@ impl Program<'_> {
                             body,
                         )))
                     }
+                    "min" | "max" | "clamp" => {
+                        let expected = if name == "clamp" { 3 } else { 2 };
+                        if args.len() != expected {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                expected,
+                                args.len(),
+                                meta,
+                            ));
+                        }
+
+                        // All operands are promoted to the highest ranked kind among them
+                        let mut kind = ScalarKind::Sint;
+                        for &(expr, meta) in args.iter() {
+                            match ctx.expr_scalar_kind(self, expr, meta)? {
+                                Some(arg_kind @ ScalarKind::Sint)
+                                | Some(arg_kind @ ScalarKind::Uint)
+                                | Some(arg_kind @ ScalarKind::Float) => {
+                                    if type_power(arg_kind) > type_power(kind) {
+                                        kind = arg_kind
+                                    }
+                                }
+                                _ => {
+                                    return Err(ErrorKind::SemanticError(
+                                        meta,
+                                        format!("\"{}\" expects numeric arguments", name).into(),
+                                    ))
+                                }
+                            }
+                        }
+
+                        let mut args = args;
+                        for &mut (ref mut expr, meta) in args.iter_mut() {
+                            ctx.implicit_conversion(self, expr, meta, kind)?;
+                        }
+
+                        // The scalar overloads of the bounds apply to all the vector components
+                        if let TypeInner::Vector { size, .. } =
+                            *self.resolve_type(ctx, args[0].0, args[0].1)?
+                        {
+                            for &mut (ref mut value, meta) in args[1..].iter_mut() {
+                                if let TypeInner::Scalar { .. } =
+                                    *self.resolve_type(ctx, *value, meta)?
+                                {
+                                    *value = ctx.add_expression(
+                                        Expression::Splat {
+                                            size,
+                                            value: *value,
+                                        },
+                                        body,
+                                    );
+                                }
+                            }
+                        }
+
+                        // `clamp` is undefined when the minimum is greater than the
+                        // maximum, the operands are kept in source order so every
+                        // backend gets the same (value, min, max) call
+                        Ok(Some(ctx.add_expression(
+                            Expression::Math {
+                                fun: match name.as_str() {
+                                    "min" => MathFunction::Min,
+                                    "max" => MathFunction::Max,
+                                    "clamp" => MathFunction::Clamp,
+                                    _ => unreachable!(),
+                                },
+                                arg: args[0].0,
+                                arg1: Some(args[1].0),
+                                arg2: args.get(2).map(|&(expr, _)| expr),
+                            },
+                            body,
+                        )))
+                    }
                     "mix" => {
                         if args.len() != 3 {
                             return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
@@ -423,37 +1310,149 @@ impl Program<'_> {
                             if let Some(ScalarKind::Bool) =
                                 self.resolve_type(ctx, args[2].0, args[2].1)?.scalar_kind()
                             {
+                                // The components of `y` are picked where the
+                                // selector is true, this works for any operand type
                                 ctx.add_expression(
                                     Expression::Select {
                                         condition: args[2].0,
-                                        accept: args[0].0,
-                                        reject: args[1].0,
+                                        accept: args[1].0,
+                                        reject: args[0].0,
                                     },
                                     body,
                                 )
                             } else {
+                                if self.resolve_type(ctx, args[0].0, args[0].1)?.scalar_kind()
+                                    != Some(ScalarKind::Float)
+                                {
+                                    return Err(ErrorKind::SemanticError(
+                                        meta,
+                                        "\"mix\" with non float operands requires a boolean selector"
+                                            .into(),
+                                    ));
+                                }
+
+                                let mut selector = args[2].0;
+
+                                // A scalar interpolant applies to all the vector components
+                                let size = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                                    TypeInner::Vector { size, .. } => Some(size),
+                                    _ => None,
+                                };
+                                if let Some(size) = size {
+                                    if let TypeInner::Scalar { .. } =
+                                        *self.resolve_type(ctx, selector, args[2].1)?
+                                    {
+                                        selector = ctx.add_expression(
+                                            Expression::Splat {
+                                                size,
+                                                value: selector,
+                                            },
+                                            body,
+                                        );
+                                    }
+                                }
+
                                 ctx.add_expression(
                                     Expression::Math {
                                         fun: MathFunction::Mix,
                                         arg: args[0].0,
                                         arg1: Some(args[1].0),
-                                        arg2: Some(args[2].0),
+                                        arg2: Some(selector),
                                     },
                                     body,
                                 )
                             },
                         ))
                     }
-                    "clamp" | "faceforward" | "refract" | "fma" | "smoothstep" => {
+                    "reflect" | "refract" => {
+                        let expected = if name == "reflect" { 2 } else { 3 };
+                        if args.len() != expected {
+                            return Err(ErrorKind::wrong_function_args(
+                                name,
+                                expected,
+                                args.len(),
+                                meta,
+                            ));
+                        }
+
+                        let (size, width) = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Vector {
+                                size,
+                                kind: ScalarKind::Float,
+                                width,
+                            } => (size, width),
+                            // The IR only has the vector versions
+                            TypeInner::Scalar {
+                                kind: ScalarKind::Float,
+                                ..
+                            } => {
+                                return Err(ErrorKind::NotImplemented(
+                                    meta,
+                                    "reflect and refract of scalars",
+                                ))
+                            }
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[0].1,
+                                    format!("{} expects a float vector", name).into(),
+                                ))
+                            }
+                        };
+
+                        match *self.resolve_type(ctx, args[1].0, args[1].1)? {
+                            TypeInner::Vector {
+                                size: normal_size,
+                                kind: ScalarKind::Float,
+                                width: normal_width,
+                            } if normal_size == size && normal_width == width => {}
+                            _ => {
+                                return Err(ErrorKind::SemanticError(
+                                    args[1].1,
+                                    "The normal must have the same type as the incident vector"
+                                        .into(),
+                                ))
+                            }
+                        }
+
+                        let mut args = args;
+                        if let Some(&mut (ref mut eta, eta_meta)) = args.get_mut(2) {
+                            ctx.implicit_conversion(self, eta, eta_meta, ScalarKind::Float)?;
+
+                            match *self.resolve_type(ctx, *eta, eta_meta)? {
+                                TypeInner::Scalar {
+                                    kind: ScalarKind::Float,
+                                    width: eta_width,
+                                } if eta_width == width => {}
+                                _ => {
+                                    return Err(ErrorKind::SemanticError(
+                                        eta_meta,
+                                        "eta must be a float scalar".into(),
+                                    ))
+                                }
+                            }
+                        }
+
+                        Ok(Some(ctx.add_expression(
+                            Expression::Math {
+                                fun: match name.as_str() {
+                                    "reflect" => MathFunction::Reflect,
+                                    _ => MathFunction::Refract,
+                                },
+                                arg: args[0].0,
+                                arg1: Some(args[1].0),
+                                arg2: args.get(2).map(|&(eta, _)| eta),
+                            },
+                            body,
+                        )))
+                    }
+                    "faceforward" | "fma" | "smoothstep" => {
                         if args.len() != 3 {
                             return Err(ErrorKind::wrong_function_args(name, 3, args.len(), meta));
                         }
                         Ok(Some(ctx.add_expression(
                             Expression::Math {
                                 fun: match name.as_str() {
-                                    "clamp" => MathFunction::Clamp,
                                     "faceforward" => MathFunction::FaceForward,
-                                    "refract" => MathFunction::Refract,
                                     "fma" => MathFunction::Fma,
                                     "smoothstep" => MathFunction::SmoothStep,
                                     _ => unreachable!(),
@@ -487,6 +1486,68 @@ impl Program<'_> {
                             body,
                         )))
                     }
+                    "imulExtended" | "umulExtended" => {
+                        if args.len() != 4 {
+                            return Err(ErrorKind::wrong_function_args(name, 4, args.len(), meta));
+                        }
+
+                        // The most significant bits of the product can't be
+                        // computed without 64-bit integers, so instead of
+                        // silently truncating the product error out
+                        Err(ErrorKind::NotImplemented(
+                            meta,
+                            "extended multiplication (requires 64-bit integers)",
+                        ))
+                    }
+                    "packDouble2x32" | "unpackDouble2x32" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+
+                        let valid = match *self.resolve_type(ctx, args[0].0, args[0].1)? {
+                            TypeInner::Vector {
+                                size: VectorSize::Bi,
+                                kind: ScalarKind::Uint,
+                                width: 4,
+                            } => name == "packDouble2x32",
+                            TypeInner::Scalar {
+                                kind: ScalarKind::Float,
+                                width: 8,
+                            } => name == "unpackDouble2x32",
+                            _ => false,
+                        };
+
+                        if !valid {
+                            return Err(ErrorKind::SemanticError(
+                                args[0].1,
+                                format!(
+                                    "\"{}\" expects {}",
+                                    name,
+                                    match name.as_str() {
+                                        "packDouble2x32" => "an uvec2",
+                                        _ => "a double",
+                                    }
+                                )
+                                .into(),
+                            ));
+                        }
+
+                        // The IR has no way to reinterpret a double as two
+                        // uints since bitcasts must preserve the vector size
+                        Err(ErrorKind::NotImplemented(meta, "double packing"))
+                    }
+                    "atomicCounterIncrement" | "atomicCounterDecrement" | "atomicCounter" => {
+                        if args.len() != 1 {
+                            return Err(ErrorKind::wrong_function_args(name, 1, args.len(), meta));
+                        }
+
+                        // The IR has no atomic operations, so there's nothing
+                        // an `atomic_uint` counter could be lowered to
+                        Err(ErrorKind::NotImplemented(
+                            meta,
+                            "atomic counters (requires atomic operations)",
+                        ))
+                    }
                     "isinf" | "isnan" | "all" | "any" => {
                         let fun = match name.as_str() {
                             "isinf" => RelationalFunction::IsInf,
@@ -501,12 +1562,10 @@ impl Program<'_> {
                         ))
                     }
                     _ => {
-                        let declarations = self.lookup_function.get(&name).ok_or_else(|| {
-                            ErrorKind::SemanticError(
-                                meta,
-                                format!("Unknown function '{}'", name).into(),
-                            )
-                        })?;
+                        let declarations = self
+                            .lookup_function
+                            .get(&name)
+                            .map_or(&[][..], |declarations| &declarations[..]);
 
                         let mut maybe_decl = None;
                         let mut ambiguous = false;
@@ -518,19 +1577,55 @@ impl Program<'_> {
 
                             let mut exact = true;
 
-                            for (decl_arg, call_arg) in decl.parameters.iter().zip(args.iter()) {
+                            for ((decl_arg, qualifier), call_arg) in decl
+                                .parameters
+                                .iter()
+                                .zip(decl.qualifiers.iter())
+                                .zip(args.iter())
+                            {
                                 let decl_inner = &self.module.types[*decl_arg].inner;
                                 let call_inner = self.resolve_type(ctx, call_arg.0, call_arg.1)?;
 
                                 if decl_inner != call_inner {
                                     exact = false;
 
+                                    // Sized arrays can be passed to unsized array parameters
+                                    // with the same element type, the function is then
+                                    // specialized for the size of the argument
+                                    if let (
+                                        &TypeInner::Array {
+                                            base: decl_base,
+                                            size: ArraySize::Dynamic,
+                                            ..
+                                        },
+                                        &TypeInner::Array {
+                                            base: call_base, ..
+                                        },
+                                    ) = (decl_inner, call_inner)
+                                    {
+                                        if decl_base != call_base || qualifier.is_lhs() {
+                                            continue 'outer;
+                                        }
+                                        continue;
+                                    }
+
+                                    if !same_shape(decl_inner, call_inner) {
+                                        continue 'outer;
+                                    }
+
                                     match (
                                         decl_inner.scalar_kind().and_then(type_power),
                                         call_inner.scalar_kind().and_then(type_power),
                                     ) {
+                                        // Inputs are converted to the parameter type and
+                                        // outputs are converted back to the argument type
                                         (Some(decl_power), Some(call_power)) => {
-                                            if decl_power < call_power {
+                                            let convertible = match *qualifier {
+                                                ParameterQualifier::Out => decl_power <= call_power,
+                                                ParameterQualifier::InOut => false,
+                                                _ => decl_power >= call_power,
+                                            };
+                                            if !convertible {
                                                 continue 'outer;
                                             }
                                         }
@@ -550,116 +1645,1038 @@ impl Program<'_> {
                             }
                         }
 
-                        if ambiguous {
-                            return Err(ErrorKind::SemanticError(
-                                meta,
-                                format!("Ambiguous best function for '{}'", name).into(),
-                            ));
-                        }
+                        if ambiguous {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                format!("Ambiguous best function for '{}'", name).into(),
+                            ));
+                        }
+
+                        let decl = match maybe_decl {
+                            Some(decl) => decl,
+                            // Intrinsics are only used if no declared function matches
+                            None => match self.intrinsics.and_then(|i| i.get(&name)) {
+                                Some(lower) => {
+                                    let args: Vec<_> = args.iter().map(|&(expr, _)| expr).collect();
+                                    let mut intrinsic_ctx = IntrinsicContext {
+                                        program: self,
+                                        ctx,
+                                        body,
+                                        meta,
+                                    };
+                                    return lower(&mut intrinsic_ctx, &args);
+                                }
+                                None => {
+                                    return Err(ErrorKind::SemanticError(
+                                        meta,
+                                        format!("Unknown function '{}'", name).into(),
+                                    ))
+                                }
+                            },
+                        };
+
+                        let qualifiers = decl.qualifiers.clone();
+                        let mut parameters = decl.parameters.clone();
+                        let mut function = decl.handle;
+                        let is_void = decl.void;
+
+                        // Unsized array parameters take the type of their argument
+                        let mut specialized = false;
+                        for (parameter, &(expr, meta)) in parameters.iter_mut().zip(args.iter()) {
+                            if let TypeInner::Array {
+                                size: ArraySize::Dynamic,
+                                ..
+                            } = self.module.types[*parameter].inner
+                            {
+                                self.typifier_grow(ctx, expr, meta)?;
+                                let ty = ctx.typifier.get_handle(expr, &mut self.module.types);
+                                specialized |= ty != *parameter;
+                                *parameter = ty;
+                            }
+                        }
+                        if specialized {
+                            function = self.specialize_function(function, &parameters);
+                        }
+
+                        let mut arguments = Vec::with_capacity(args.len());
+                        let mut proxy_writes = Vec::new();
+                        for (index, (qualifier, (expr, parameter))) in qualifiers
+                            .iter()
+                            .zip(raw_args.iter().zip(parameters.iter()))
+                            .enumerate()
+                        {
+                            if qualifier.is_lhs() {
+                                ctx.check_writable(*expr)?;
+                                ctx.mark_pointer_use(*expr)
+                            }
+
+                            let (mut handle, meta) =
+                                ctx.lower_expect(self, *expr, qualifier.is_lhs(), body)?;
+
+                            // Only whole local variables and pointer arguments of the
+                            // parameter's type can be passed directly, everything else
+                            // (swizzles, globals, struct members, array elements and
+                            // values that need a conversion) goes through a temporary
+                            // that is written back after the call
+                            let direct = match *ctx.get_expression(handle) {
+                                Expression::LocalVariable(var) => ctx.locals[var].ty == *parameter,
+                                Expression::FunctionArgument(index) => {
+                                    match self.module.types[ctx.arguments[index as usize].ty].inner
+                                    {
+                                        TypeInner::Pointer { base, .. } => base == *parameter,
+                                        _ => false,
+                                    }
+                                }
+                                _ => false,
+                            };
+
+                            if qualifier.is_lhs() && !direct {
+                                // Named after the parameter it's passed to
+                                let name = if self.preserve_names {
+                                    self.module.functions[function].arguments[index]
+                                        .name
+                                        .clone()
+                                } else {
+                                    None
+                                };
+                                let temp_var = ctx.locals.append(LocalVariable {
+                                    name,
+                                    ty: *parameter,
+                                    init: None,
+                                });
+                                let temp_expr =
+                                    ctx.add_expression(Expression::LocalVariable(temp_var), body);
+
+                                // `out` parameters start undefined so only `inout`
+                                // parameters need the current value
+                                if *qualifier == ParameterQualifier::InOut {
+                                    let value = match *ctx.get_expression(handle) {
+                                        Expression::Swizzle { .. } => handle,
+                                        _ => ctx.add_expression(
+                                            Expression::Load { pointer: handle },
+                                            body,
+                                        ),
+                                    };
+
+                                    ctx.emit_flush(body);
+                                    ctx.emit_start();
+
+                                    body.push(Statement::Store {
+                                        pointer: temp_expr,
+                                        value,
+                                    });
+                                }
+
+                                arguments.push(temp_expr);
+                                proxy_writes.push((*expr, temp_expr));
+                                continue;
+                            }
+
+                            if let Some(kind) = self.module.types[*parameter].inner.scalar_kind() {
+                                ctx.implicit_conversion(self, &mut handle, meta, kind)?;
+                            }
+
+                            arguments.push(handle)
+                        }
+
+                        ctx.emit_flush(body);
+
+                        let result = if !is_void {
+                            Some(ctx.add_expression(Expression::Call(function), body))
+                        } else {
+                            None
+                        };
+
+                        body.push(crate::Statement::Call {
+                            function,
+                            arguments,
+                            result,
+                        });
+
+                        ctx.emit_start();
+                        for (tgt, pointer) in proxy_writes {
+                            let temp_ref = ctx.hir_exprs.append(HirExpr {
+                                kind: HirExprKind::Variable(VariableReference {
+                                    expr: pointer,
+                                    load: true,
+                                    mutable: true,
+                                    entry_arg: None,
+                                }),
+                                meta,
+                            });
+                            let assign = ctx.hir_exprs.append(HirExpr {
+                                kind: HirExprKind::Assign {
+                                    tgt,
+                                    value: temp_ref,
+                                },
+                                meta,
+                            });
+
+                            let _ = ctx.lower_expect(self, assign, false, body)?;
+                        }
+                        ctx.emit_flush(body);
+                        ctx.emit_start();
+
+                        Ok(result)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Converts both operands of `pow` or `atan(y, x)` to floats and checks
+    /// that they have the same type
+    ///
+    /// GLSL only declares them for two operands of the same float scalar or
+    /// vector type, unlike `min` or `mix` there are no overloads taking a
+    /// scalar for a vector so nothing is broadcast
+    fn same_float_operands(
+        &mut self,
+        ctx: &mut Context,
+        name: &str,
+        args: &mut [(Handle<Expression>, SourceMetadata)],
+        meta: SourceMetadata,
+    ) -> Result<(), ErrorKind> {
+        for &mut (ref mut expr, meta) in args.iter_mut() {
+            ctx.implicit_conversion(self, expr, meta, ScalarKind::Float)?;
+
+            match *self.resolve_type(ctx, *expr, meta)? {
+                TypeInner::Scalar {
+                    kind: ScalarKind::Float,
+                    ..
+                }
+                | TypeInner::Vector {
+                    kind: ScalarKind::Float,
+                    ..
+                } => {}
+                _ => {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!("\"{}\" expects float scalars or vectors", name).into(),
+                    ))
+                }
+            }
+        }
+
+        self.typifier_grow(ctx, args[0].0, args[0].1)?;
+        self.typifier_grow(ctx, args[1].0, args[1].1)?;
+        if ctx.typifier.get(args[0].0, &self.module.types)
+            != ctx.typifier.get(args[1].0, &self.module.types)
+        {
+            return Err(ErrorKind::SemanticError(
+                meta,
+                format!("\"{}\" expects both arguments to have the same type", name).into(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the version of `function` taking `parameters`, where its unsized
+    /// array parameters are replaced by the sized arrays passed to them
+    ///
+    /// The first version reuses the function itself, the others are only
+    /// placeholders until [`specialize_functions`](Self::specialize_functions)
+    /// copies the body since the function might still be a prototype
+    fn specialize_function(
+        &mut self,
+        function: Handle<Function>,
+        parameters: &[Handle<Type>],
+    ) -> Handle<Function> {
+        let instances = self.specializations.entry(function).or_default();
+        if let Some(&(_, handle)) = instances
+            .iter()
+            .find(|instance| instance.0[..] == *parameters)
+        {
+            return handle;
+        }
+
+        let handle = if instances.is_empty() {
+            function
+        } else {
+            let generic = &self.module.functions[function];
+            let placeholder = Function {
+                name: generic.name.clone(),
+                arguments: generic.arguments.clone(),
+                result: generic.result.clone(),
+                ..Default::default()
+            };
+            self.function_arg_use.push(Vec::new());
+            self.module.functions.append(placeholder)
+        };
+        instances.push((parameters.to_vec(), handle));
+
+        handle
+    }
+
+    /// Fills the versions of the functions with unsized array parameters that
+    /// were called with sized arrays, retyping those parameters
+    ///
+    /// Calls in the filled versions that forward the retyped parameters to
+    /// other functions with unsized array parameters are redirected to the
+    /// versions of those functions taking the sized arrays, which are then
+    /// filled too
+    pub fn specialize_functions(&mut self) {
+        let mut pending: Vec<_> = self
+            .specializations
+            .iter()
+            .flat_map(|(&function, instances)| (0..instances.len()).map(move |i| (function, i)))
+            .collect();
+        // The first version is the function itself so the generic function
+        // is copied before any of its versions is filled
+        let mut generics = FastHashMap::default();
+
+        while let Some((function, i)) = pending.pop() {
+            let (parameters, handle) = self.specializations[&function][i].clone();
+            let generic = generics
+                .entry(function)
+                .or_insert_with(|| copy_function(&self.module.functions[function]));
+            let mut specialized = copy_function(generic);
+
+            for (arg, &ty) in specialized.arguments.iter_mut().zip(parameters.iter()) {
+                if let TypeInner::Array {
+                    size: ArraySize::Dynamic,
+                    ..
+                } = self.module.types[arg.ty].inner
+                {
+                    arg.ty = ty;
+                }
+            }
+            if let Some(arg_locals) = self.function_arg_locals.get(&function) {
+                for &(index, local) in arg_locals.iter() {
+                    specialized.local_variables.get_mut(local).ty = specialized.arguments[index].ty;
+                }
+            }
+
+            let Function {
+                ref mut body,
+                ref expressions,
+                ref local_variables,
+                ref arguments,
+                ..
+            } = specialized;
+            let mut typifier = Typifier::new();
+            let mut results = Vec::new();
+
+            for (callee, args, result) in calls_mut(body) {
+                // Calls to versions made while parsing are redirected through
+                // their generic function as well
+                let generic_callee = self
+                    .specializations
+                    .iter()
+                    .find(|&(_, instances)| instances.iter().any(|instance| instance.1 == *callee))
+                    .map_or(*callee, |(&generic, _)| generic);
+                let callee_arguments = &generics
+                    .get(&generic_callee)
+                    .unwrap_or(&self.module.functions[generic_callee])
+                    .arguments;
+
+                let mut callee_parameters = Vec::with_capacity(callee_arguments.len());
+                let mut retyped = false;
+                for (callee_arg, &arg) in callee_arguments.iter().zip(args.iter()) {
+                    let parameter = match self.module.types[callee_arg.ty].inner {
+                        TypeInner::Pointer { base, .. } => base,
+                        TypeInner::Array {
+                            size: ArraySize::Dynamic,
+                            ..
+                        } => {
+                            let resolve_ctx = ResolveContext {
+                                constants: &self.module.constants,
+                                types: &self.module.types,
+                                global_vars: &self.module.global_variables,
+                                local_vars: local_variables,
+                                functions: &self.module.functions,
+                                arguments,
+                            };
+
+                            // Unresolved arguments are left to the validator
+                            if typifier.grow(arg, expressions, &resolve_ctx).is_ok() {
+                                let ty = typifier.get_handle(arg, &mut self.module.types);
+                                retyped |= ty != callee_arg.ty;
+                                ty
+                            } else {
+                                callee_arg.ty
+                            }
+                        }
+                        _ => callee_arg.ty,
+                    };
+                    callee_parameters.push(parameter);
+                }
+
+                if retyped {
+                    let filled = self
+                        .specializations
+                        .get(&generic_callee)
+                        .map_or(0, Vec::len);
+                    *callee = self.specialize_function(generic_callee, &callee_parameters);
+                    results.extend(result.map(|result| (result, *callee)));
+                    let instances = self.specializations[&generic_callee].len();
+                    pending.extend((filled..instances).map(|i| (generic_callee, i)));
+                }
+            }
+
+            for (result, callee) in results {
+                *specialized.expressions.get_mut(result) = Expression::Call(callee);
+            }
+
+            self.function_arg_use[handle.index()] = self.function_arg_use[function.index()].clone();
+            if let Some(&meta) = self.function_barriers.get(&function) {
+                self.function_barriers.insert(handle, meta);
+            }
+            *self.module.functions.get_mut(handle) = specialized;
+        }
+
+        self.sort_functions();
+    }
+
+    /// Sorts the functions so that callees come before their callers, which
+    /// the validator requires but versions filled by
+    /// [`specialize_functions`](Self::specialize_functions) can break since
+    /// they might call versions added after them
+    fn sort_functions(&mut self) {
+        let callees: Vec<Vec<usize>> = self
+            .module
+            .functions
+            .iter_mut()
+            .map(|(_, function)| {
+                calls_mut(&mut function.body)
+                    .into_iter()
+                    .map(|(callee, _, _)| callee.index())
+                    .collect()
+            })
+            .collect();
+
+        // Depth first post order, which keeps the order of functions that
+        // already come after their callees
+        let mut order = Vec::with_capacity(callees.len());
+        let mut visited = vec![false; callees.len()];
+        for root in 0..callees.len() {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+
+            let mut stack = vec![(root, 0)];
+            while let Some(top) = stack.last_mut() {
+                let (function, next) = *top;
+                match callees[function].get(next) {
+                    Some(&callee) => {
+                        top.1 += 1;
+                        if !visited[callee] {
+                            visited[callee] = true;
+                            stack.push((callee, 0));
+                        }
+                    }
+                    None => {
+                        order.push(function);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+
+        if order.iter().enumerate().all(|(i, &function)| i == function) {
+            return;
+        }
+
+        let mut functions: Vec<_> = std::mem::take(&mut self.module.functions)
+            .into_inner()
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut function_arg_use = vec![Vec::new(); functions.len()];
+        let mut remap = vec![None; functions.len()];
+        for &old in order.iter() {
+            if let Some(function) = functions[old].take() {
+                let handle = self.module.functions.append(function);
+                function_arg_use[handle.index()] = std::mem::take(&mut self.function_arg_use[old]);
+                remap[old] = Some(handle);
+            }
+        }
+        let remap: Vec<_> = remap.into_iter().flatten().collect();
+
+        for (_, function) in self.module.functions.iter_mut() {
+            for (callee, _, _) in calls_mut(&mut function.body) {
+                *callee = remap[callee.index()];
+            }
+            for (_, expr) in function.expressions.iter_mut() {
+                if let Expression::Call(ref mut callee) = *expr {
+                    *callee = remap[callee.index()];
+                }
+            }
+        }
+
+        self.function_arg_use = function_arg_use;
+        for entry in self.entries.iter_mut() {
+            entry.2 = remap[entry.2.index()];
+        }
+        for decl in self.lookup_function.values_mut().flatten() {
+            decl.handle = remap[decl.handle.index()];
+        }
+        self.function_barriers = self
+            .function_barriers
+            .drain()
+            .map(|(function, meta)| (remap[function.index()], meta))
+            .collect();
+        self.function_arg_locals = self
+            .function_arg_locals
+            .drain()
+            .map(|(function, locals)| (remap[function.index()], locals))
+            .collect();
+        self.specializations = self
+            .specializations
+            .drain()
+            .map(|(function, mut instances)| {
+                for instance in instances.iter_mut() {
+                    instance.1 = remap[instance.1.index()];
+                }
+                (remap[function.index()], instances)
+            })
+            .collect();
+    }
+
+    /// Converts a scalar or vector to `kind`
+    ///
+    /// Booleans can't be cast so conversions to them are lowered to a
+    /// comparison with zero and conversions from them to a select of one and
+    /// zero.
+    fn conversion(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (expr, meta): (Handle<Expression>, SourceMetadata),
+        kind: ScalarKind,
+        width: crate::Bytes,
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        let (size, src_kind, src_width) = match *self.resolve_type(ctx, expr, meta)? {
+            TypeInner::Scalar { kind, width } => (None, kind, width),
+            TypeInner::Vector { size, kind, width } => (Some(size), kind, width),
+            _ => return Err(ErrorKind::SemanticError(meta, "Bad cast".into())),
+        };
+
+        let mut constant = |ctx: &mut Context, value, width| {
+            let constant = self.module.constants.fetch_or_append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar { width, value },
+            });
+            let value = ctx.add_expression(Expression::Constant(constant), body);
+
+            match size {
+                Some(size) => ctx.add_expression(Expression::Splat { size, value }, body),
+                None => value,
+            }
+        };
+        let zero = |kind| match kind {
+            ScalarKind::Sint => ScalarValue::Sint(0),
+            ScalarKind::Uint => ScalarValue::Uint(0),
+            _ => ScalarValue::Float(0.0),
+        };
+
+        Ok(match (src_kind, kind) {
+            (ScalarKind::Bool, ScalarKind::Bool) => expr,
+            (_, ScalarKind::Bool) => {
+                let right = constant(ctx, zero(src_kind), src_width);
+
+                ctx.add_expression(
+                    Expression::Binary {
+                        op: BinaryOperator::NotEqual,
+                        left: expr,
+                        right,
+                    },
+                    body,
+                )
+            }
+            (ScalarKind::Bool, _) => {
+                let one = match kind {
+                    ScalarKind::Sint => ScalarValue::Sint(1),
+                    ScalarKind::Uint => ScalarValue::Uint(1),
+                    _ => ScalarValue::Float(1.0),
+                };
+                let accept = constant(ctx, one, width);
+                let reject = constant(ctx, zero(kind), width);
+
+                ctx.add_expression(
+                    Expression::Select {
+                        condition: expr,
+                        accept,
+                        reject,
+                    },
+                    body,
+                )
+            }
+            _ => ctx.add_expression(
+                Expression::As {
+                    kind,
+                    expr,
+                    convert: Some(width),
+                },
+                body,
+            ),
+        })
+    }
+
+    /// Builds a vector out of the first components of a matrix, taken in
+    /// column-major order
+    fn vector_from_matrix(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        matrix: Handle<Expression>,
+        (rows, matrix_width): (VectorSize, crate::Bytes),
+        (size, kind, width): (VectorSize, ScalarKind, crate::Bytes),
+    ) -> Handle<Expression> {
+        // Even the smallest matrix has enough components for any vector
+        let mut components = Vec::with_capacity(size as usize);
+        for i in 0..size as u32 {
+            let column = ctx.add_expression(
+                Expression::AccessIndex {
+                    base: matrix,
+                    index: i / rows as u32,
+                },
+                body,
+            );
+            components.push(ctx.add_expression(
+                Expression::AccessIndex {
+                    base: column,
+                    index: i % rows as u32,
+                },
+                body,
+            ));
+        }
+
+        let ty = self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Vector {
+                size,
+                kind: ScalarKind::Float,
+                width: matrix_width,
+            },
+        });
+        let vector = ctx.add_expression(Expression::Compose { ty, components }, body);
+
+        if kind != ScalarKind::Float || width != matrix_width {
+            ctx.add_expression(
+                Expression::As {
+                    kind,
+                    expr: vector,
+                    convert: Some(width),
+                },
+                body,
+            )
+        } else {
+            vector
+        }
+    }
 
-                        let decl = maybe_decl.ok_or_else(|| {
-                            ErrorKind::SemanticError(
-                                meta,
-                                format!("Unknown function '{}'", name).into(),
-                            )
-                        })?;
+    /// The IR can't query the level of detail so it's computed from the screen
+    /// space derivatives of the coordinate in texels, the result holds the
+    /// level clamped to the mipmaps of the image followed by the unclamped one
+    fn query_lod(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (image, image_meta): (Handle<Expression>, SourceMetadata),
+        (coordinate, coord_meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<Handle<Expression>, ErrorKind> {
+        let expected = match *self.resolve_type(ctx, image, image_meta)? {
+            TypeInner::Image {
+                dim: ImageDimension::Cube,
+                ..
+            } => {
+                return Err(ErrorKind::NotImplemented(
+                    image_meta,
+                    "level of detail queries of cube images",
+                ))
+            }
+            TypeInner::Image { dim, .. } => match dim {
+                ImageDimension::D1 => 1,
+                ImageDimension::D2 => 2,
+                _ => 3,
+            },
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    image_meta,
+                    "Expected an image".into(),
+                ))
+            }
+        };
+        let components = match *self.resolve_type(ctx, coordinate, coord_meta)? {
+            TypeInner::Scalar {
+                kind: ScalarKind::Float,
+                ..
+            } => 1,
+            TypeInner::Vector {
+                size,
+                kind: ScalarKind::Float,
+                ..
+            } => size as usize,
+            _ => 0,
+        };
 
-                        let qualifiers = decl.qualifiers.clone();
-                        let parameters = decl.parameters.clone();
-                        let function = decl.handle;
-                        let is_void = decl.void;
+        if components != expected {
+            return Err(ErrorKind::SemanticError(
+                coord_meta,
+                format!(
+                    "Level of detail queries need a float coordinate with {} components",
+                    expected
+                )
+                .into(),
+            ));
+        }
 
-                        let mut arguments = Vec::with_capacity(args.len());
-                        let mut proxy_writes = Vec::new();
-                        for (qualifier, (expr, parameter)) in qualifiers
-                            .iter()
-                            .zip(raw_args.iter().zip(parameters.iter()))
-                        {
-                            let (mut handle, meta) =
-                                ctx.lower_expect(self, *expr, qualifier.is_lhs(), body)?;
+        let size = ctx.add_expression(
+            Expression::ImageQuery {
+                image,
+                query: ImageQuery::Size { level: None },
+            },
+            body,
+        );
+        let size = ctx.add_expression(
+            Expression::As {
+                kind: ScalarKind::Float,
+                expr: size,
+                convert: Some(4),
+            },
+            body,
+        );
+        let texels = ctx.add_expression(
+            Expression::Binary {
+                op: BinaryOperator::Multiply,
+                left: coordinate,
+                right: size,
+            },
+            body,
+        );
+
+        // Same as in `length` not all backends have a scalar length
+        let length = match expected {
+            1 => MathFunction::Abs,
+            _ => MathFunction::Length,
+        };
+        let math = |fun, arg, arg1, arg2| Expression::Math {
+            fun,
+            arg,
+            arg1,
+            arg2,
+        };
+
+        let dx = ctx.add_expression(
+            Expression::Derivative {
+                axis: DerivativeAxis::X,
+                expr: texels,
+            },
+            body,
+        );
+        let dy = ctx.add_expression(
+            Expression::Derivative {
+                axis: DerivativeAxis::Y,
+                expr: texels,
+            },
+            body,
+        );
+        let dx = ctx.add_expression(math(length, dx, None, None), body);
+        let dy = ctx.add_expression(math(length, dy, None, None), body);
+        let rho = ctx.add_expression(math(MathFunction::Max, dx, Some(dy), None), body);
+        let lod = ctx.add_expression(math(MathFunction::Log2, rho, None, None), body);
+
+        let mut constant = |value| {
+            self.module.constants.fetch_or_append(Constant {
+                name: None,
+                specialization: None,
+                inner: ConstantInner::Scalar {
+                    width: 4,
+                    value: ScalarValue::Float(value),
+                },
+            })
+        };
+        let (zero, one) = (constant(0.0), constant(1.0));
+        let zero = ctx.add_expression(Expression::Constant(zero), body);
+        let one = ctx.add_expression(Expression::Constant(one), body);
+
+        let levels = ctx.add_expression(
+            Expression::ImageQuery {
+                image,
+                query: ImageQuery::NumLevels,
+            },
+            body,
+        );
+        let levels = ctx.add_expression(
+            Expression::As {
+                kind: ScalarKind::Float,
+                expr: levels,
+                convert: Some(4),
+            },
+            body,
+        );
+        let max_level = ctx.add_expression(
+            Expression::Binary {
+                op: BinaryOperator::Subtract,
+                left: levels,
+                right: one,
+            },
+            body,
+        );
+        let clamped = ctx.add_expression(
+            math(MathFunction::Clamp, lod, Some(zero), Some(max_level)),
+            body,
+        );
+
+        let ty = self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Vector {
+                size: VectorSize::Bi,
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
 
-                            if let TypeInner::Vector { size, kind, width } =
-                                *self.resolve_type(ctx, handle, meta)?
-                            {
-                                if qualifier.is_lhs()
-                                    && matches!(
-                                        *ctx.get_expression(handle),
-                                        Expression::Swizzle { .. }
-                                    )
-                                {
-                                    let ty = self.module.types.append(Type {
-                                        name: None,
-                                        inner: TypeInner::Vector { size, kind, width },
-                                    });
-                                    let temp_var = ctx.locals.append(LocalVariable {
-                                        name: None,
-                                        ty,
-                                        init: None,
-                                    });
-                                    let temp_expr = ctx
-                                        .add_expression(Expression::LocalVariable(temp_var), body);
+        Ok(ctx.add_expression(
+            Expression::Compose {
+                ty,
+                components: vec![clamped, lod],
+            },
+            body,
+        ))
+    }
 
-                                    body.push(Statement::Store {
-                                        pointer: temp_expr,
-                                        value: handle,
-                                    });
+    /// Checks that the sampler combined by the `name` constructor has the
+    /// comparison mode of its form, only the shadow forms take a
+    /// `samplerShadow` and backends reject samplers used with the other mode
+    fn check_sampler_comparison(
+        &mut self,
+        ctx: &mut Context,
+        name: &str,
+        (sampler, meta): (Handle<Expression>, SourceMetadata),
+        shadow: bool,
+    ) -> Result<(), ErrorKind> {
+        match *self.resolve_type(ctx, sampler, meta)? {
+            TypeInner::Sampler { comparison } if comparison == shadow => Ok(()),
+            TypeInner::Sampler { .. } if shadow => Err(ErrorKind::SemanticError(
+                meta,
+                format!("\"{}\" needs a samplerShadow to do depth comparisons", name).into(),
+            )),
+            TypeInner::Sampler { .. } => Err(ErrorKind::SemanticError(
+                meta,
+                format!(
+                    "\"{}\" can't use a samplerShadow, it has no depth comparison form",
+                    name
+                )
+                .into(),
+            )),
+            _ => Err(ErrorKind::SemanticError(meta, "Expected a sampler".into())),
+        }
+    }
 
-                                    arguments.push(temp_expr);
-                                    proxy_writes.push((*expr, temp_expr));
-                                    continue;
-                                }
-                            }
+    /// Solves the offset argument of a texture call, which must be a
+    /// constant expression
+    fn texel_offset(
+        &mut self,
+        ctx: &Context,
+        (offset, meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<Handle<Constant>, ErrorKind> {
+        self.solve_constant(ctx, offset, meta).map_err(|_| {
+            ErrorKind::SemanticError(meta, "offset must be a constant expression".into())
+        })
+    }
 
-                            if let Some(kind) = self.module.types[*parameter].inner.scalar_kind() {
-                                ctx.implicit_conversion(self, &mut handle, meta, kind)?;
-                            }
+    /// Sampling functions take normalized coordinates, so integer coordinates
+    /// are converted to floats, unlike the ones passed to `texelFetch`
+    fn sample_coordinate(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (coordinate, meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<(Handle<Expression>, SourceMetadata), ErrorKind> {
+        match ctx.expr_scalar_kind(self, coordinate, meta)? {
+            Some(ScalarKind::Sint) | Some(ScalarKind::Uint) => Ok((
+                ctx.add_expression(
+                    Expression::As {
+                        expr: coordinate,
+                        kind: ScalarKind::Float,
+                        convert: Some(4),
+                    },
+                    body,
+                ),
+                meta,
+            )),
+            _ => Ok((coordinate, meta)),
+        }
+    }
 
-                            arguments.push(handle)
-                        }
+    /// Splits the depth reference of the shadow forms of the sampling
+    /// functions off their coordinate, where it's the last component
+    ///
+    /// One dimensional textures take a three component coordinate whose second
+    /// component is unused, like the two dimensional ones.
+    fn depth_reference(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (image, image_meta): (Handle<Expression>, SourceMetadata),
+        (coordinate, coord_meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<(Handle<Expression>, Option<Handle<Expression>>), ErrorKind> {
+        let components = match *self.resolve_type(ctx, image, image_meta)? {
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class: ImageClass::Depth,
+            } => {
+                (match dim {
+                    ImageDimension::D1 => 1,
+                    ImageDimension::D2 => 2,
+                    ImageDimension::D3 | ImageDimension::Cube => 3,
+                }) + arrayed as usize
+            }
+            _ => return Ok((coordinate, None)),
+        };
+
+        // Cube arrays take the reference as an extra argument instead
+        if components > 3 {
+            return Err(ErrorKind::NotImplemented(
+                coord_meta,
+                "depth comparisons of cube arrays",
+            ));
+        }
 
-                        ctx.emit_flush(body);
+        let index = components.max(2);
+        match *self.resolve_type(ctx, coordinate, coord_meta)? {
+            TypeInner::Vector { size, .. } if size as usize == index + 1 => {}
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    coord_meta,
+                    format!("Shadow coordinates must have {} components", index + 1).into(),
+                ))
+            }
+        }
 
-                        let result = if !is_void {
-                            Some(ctx.add_expression(Expression::Call(function), body))
-                        } else {
-                            None
-                        };
+        let depth_ref = ctx.add_expression(
+            Expression::AccessIndex {
+                base: coordinate,
+                index: index as u32,
+            },
+            body,
+        );
+        let coordinate = match components {
+            1 => ctx.add_expression(
+                Expression::AccessIndex {
+                    base: coordinate,
+                    index: 0,
+                },
+                body,
+            ),
+            _ => ctx.add_expression(
+                Expression::Swizzle {
+                    size: if components == 2 {
+                        VectorSize::Bi
+                    } else {
+                        VectorSize::Tri
+                    },
+                    vector: coordinate,
+                    pattern: SwizzleComponent::XYZW,
+                },
+                body,
+            ),
+        };
 
-                        body.push(crate::Statement::Call {
-                            function,
-                            arguments,
-                            result,
-                        });
+        Ok((coordinate, Some(depth_ref)))
+    }
 
-                        ctx.emit_start();
-                        for (tgt, pointer) in proxy_writes {
-                            let temp_ref = ctx.hir_exprs.append(HirExpr {
-                                kind: HirExprKind::Variable(VariableReference {
-                                    expr: pointer,
-                                    load: true,
-                                    mutable: true,
-                                    entry_arg: None,
-                                }),
-                                meta,
-                            });
-                            let assign = ctx.hir_exprs.append(HirExpr {
-                                kind: HirExprKind::Assign {
-                                    tgt,
-                                    value: temp_ref,
-                                },
-                                meta,
-                            });
+    /// Splits the coordinate passed to an image function into the coordinate
+    /// proper and the array layer if the image is arrayed, erroring if the
+    /// coordinate doesn't have the number of components the image requires
+    ///
+    /// The layer is always the last component, for cube arrays this means
+    /// the first three components are kept as the direction.
+    fn image_coordinate(
+        &mut self,
+        ctx: &mut Context,
+        body: &mut Block,
+        (image, image_meta): (Handle<Expression>, SourceMetadata),
+        (coordinate, coord_meta): (Handle<Expression>, SourceMetadata),
+    ) -> Result<(Handle<Expression>, Option<Handle<Expression>>), ErrorKind> {
+        let (dim, arrayed) = match *self.resolve_type(ctx, image, image_meta)? {
+            TypeInner::Image { dim, arrayed, .. } => (dim, arrayed),
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    image_meta,
+                    "Expected an image".into(),
+                ))
+            }
+        };
+
+        let expected = match dim {
+            ImageDimension::D1 => 1,
+            ImageDimension::D2 => 2,
+            ImageDimension::D3 | ImageDimension::Cube => 3,
+        } + arrayed as usize;
+        let components = match *self.resolve_type(ctx, coordinate, coord_meta)? {
+            TypeInner::Scalar { .. } => 1,
+            TypeInner::Vector { size, .. } => size as usize,
+            _ => 0,
+        };
+
+        if components != expected {
+            return Err(ErrorKind::SemanticError(
+                coord_meta,
+                format!(
+                    "Image coordinate must have {} components, found {}",
+                    expected, components
+                )
+                .into(),
+            ));
+        }
 
-                            let _ = ctx.lower_expect(self, assign, false, body)?;
-                        }
-                        ctx.emit_flush(body);
-                        ctx.emit_start();
+        if !arrayed {
+            return Ok((coordinate, None));
+        }
 
-                        Ok(result)
-                    }
-                }
-            }
+        let (size, layer) = match dim {
+            ImageDimension::D1 => (None, 1),
+            ImageDimension::D2 => (Some(VectorSize::Bi), 2),
+            ImageDimension::D3 | ImageDimension::Cube => (Some(VectorSize::Tri), 3),
+        };
+
+        let coordinate_proper = match size {
+            Some(size) => ctx.add_expression(
+                Expression::Swizzle {
+                    size,
+                    vector: coordinate,
+                    pattern: SwizzleComponent::XYZW,
+                },
+                body,
+            ),
+            None => ctx.add_expression(
+                Expression::AccessIndex {
+                    base: coordinate,
+                    index: 0,
+                },
+                body,
+            ),
+        };
+
+        let mut array_index = ctx.add_expression(
+            Expression::AccessIndex {
+                base: coordinate,
+                index: layer,
+            },
+            body,
+        );
+
+        // Sampling functions take the layer as part of a float coordinate
+        // but the IR expects it to be a signed integer
+        if ctx.expr_scalar_kind(self, array_index, coord_meta)? != Some(ScalarKind::Sint) {
+            array_index = ctx.add_expression(
+                Expression::As {
+                    expr: array_index,
+                    kind: ScalarKind::Sint,
+                    convert: Some(4),
+                },
+                body,
+            );
         }
+
+        Ok((coordinate_proper, Some(array_index)))
     }
 
     pub fn parse_relational_fun(
@@ -805,72 +2822,156 @@ impl Program<'_> {
         Ok(())
     }
 
+    /// Merges the entry argument uses and `barrier` calls of the functions
+    /// called in `stmt` into the ones of `caller`, returning whether any new
+    /// use was added
     fn check_call_global(
         &self,
         caller: Handle<Function>,
         function_arg_use: &mut [Vec<EntryArgUse>],
-        stmt: &Statement,
-    ) {
-        match *stmt {
-            Statement::Block(ref block) => {
-                for stmt in block {
-                    self.check_call_global(caller, function_arg_use, stmt)
+        function_barriers: &mut FastHashMap<Handle<Function>, SourceMetadata>,
+        block: &Block,
+    ) -> bool {
+        let mut changed = false;
+        let mut stack = vec![block.iter()];
+
+        while let Some(stmts) = stack.last_mut() {
+            let stmt = match stmts.next() {
+                Some(stmt) => stmt,
+                None => {
+                    stack.pop();
+                    continue;
                 }
-            }
-            Statement::If {
-                ref accept,
-                ref reject,
-                ..
-            } => {
-                for stmt in accept.iter().chain(reject.iter()) {
-                    self.check_call_global(caller, function_arg_use, stmt)
+            };
+
+            match *stmt {
+                Statement::Block(ref block) => stack.push(block.iter()),
+                Statement::If {
+                    ref accept,
+                    ref reject,
+                    ..
+                } => {
+                    stack.push(accept.iter());
+                    stack.push(reject.iter());
                 }
-            }
-            Statement::Switch {
-                ref cases,
-                ref default,
-                ..
-            } => {
-                for stmt in cases
-                    .iter()
-                    .flat_map(|c| c.body.iter())
-                    .chain(default.iter())
-                {
-                    self.check_call_global(caller, function_arg_use, stmt)
+                Statement::Switch {
+                    ref cases,
+                    ref default,
+                    ..
+                } => {
+                    stack.extend(cases.iter().map(|case| case.body.iter()));
+                    stack.push(default.iter());
                 }
-            }
-            Statement::Loop {
-                ref body,
-                ref continuing,
-            } => {
-                for stmt in body.iter().chain(continuing.iter()) {
-                    self.check_call_global(caller, function_arg_use, stmt)
+                Statement::Loop {
+                    ref body,
+                    ref continuing,
+                } => {
+                    stack.push(body.iter());
+                    stack.push(continuing.iter());
                 }
-            }
-            Statement::Call { function, .. } => {
-                let callee_len = function_arg_use[function.index()].len();
-                let caller_len = function_arg_use[caller.index()].len();
-                function_arg_use[caller.index()].extend(
-                    std::iter::repeat(EntryArgUse::empty())
-                        .take(callee_len.saturating_sub(caller_len)),
-                );
-
-                for i in 0..callee_len.min(caller_len) {
-                    let callee_use = function_arg_use[function.index()][i];
-                    function_arg_use[caller.index()][i] |= callee_use
+                Statement::Call { function, .. } => {
+                    let callee_len = function_arg_use[function.index()].len();
+                    let caller_len = function_arg_use[caller.index()].len();
+                    function_arg_use[caller.index()].extend(
+                        std::iter::repeat(EntryArgUse::empty())
+                            .take(callee_len.saturating_sub(caller_len)),
+                    );
+
+                    for i in 0..callee_len {
+                        let callee_use = function_arg_use[function.index()][i];
+                        let caller_use = &mut function_arg_use[caller.index()][i];
+                        changed |= !caller_use.contains(callee_use);
+                        *caller_use |= callee_use
+                    }
+
+                    if !function_barriers.contains_key(&caller) {
+                        if let Some(&meta) = function_barriers.get(&function) {
+                            function_barriers.insert(caller, meta);
+                            changed = true;
+                        }
+                    }
                 }
+                _ => {}
             }
-            _ => {}
         }
+
+        changed
     }
 
-    pub fn add_entry_points(&mut self) {
+    pub fn add_entry_points(&mut self) -> Result<(), ErrorKind> {
         let mut function_arg_use = Vec::new();
         std::mem::swap(&mut self.function_arg_use, &mut function_arg_use);
+        let mut function_barriers = std::mem::take(&mut self.function_barriers);
+
+        // Functions can call functions that come later in the arena so the
+        // uses must be propagated until nothing changes
+        loop {
+            let mut changed = false;
+
+            for (handle, function) in self.module.functions.iter() {
+                changed |= self.check_call_global(
+                    handle,
+                    &mut function_arg_use,
+                    &mut function_barriers,
+                    &function.body,
+                )
+            }
+
+            if !changed {
+                break;
+            }
+        }
+
+        // `barrier` is also allowed in tessellation control shaders but the
+        // IR doesn't have that stage
+        for &(_, stage, function) in self.entries.iter() {
+            match function_barriers.get(&function) {
+                Some(&meta) if stage != crate::ShaderStage::Compute => {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        "\"barrier\" is only available in compute shaders".into(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+
+        for &(_, stage, function) in self.entries.iter() {
+            for &(index, ref name, input_stage, meta) in self.stage_only_inputs.iter() {
+                if input_stage != stage
+                    && matches!(
+                        function_arg_use[function.index()].get(index),
+                        Some(u) if u.contains(EntryArgUse::READ)
+                    )
+                {
+                    let stage_name = match input_stage {
+                        crate::ShaderStage::Vertex => "vertex",
+                        crate::ShaderStage::Fragment => "fragment",
+                        crate::ShaderStage::Compute => "compute",
+                    };
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!("\"{}\" is only available in {} shaders", name, stage_name).into(),
+                    ));
+                }
+            }
+        }
 
-        for (handle, function) in self.module.functions.iter() {
-            for stmt in function.body.iter() {
-                self.check_call_global(handle, &mut function_arg_use, stmt)
+        for &(_, stage, function) in self.entries.iter() {
+            if stage != crate::ShaderStage::Fragment {
+                continue;
+            }
+
+            for &(index, meta) in self.non_flat_inputs.iter() {
+                if matches!(
+                    function_arg_use[function.index()].get(index),
+                    Some(u) if u.contains(EntryArgUse::READ)
+                ) {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        "Integer and double fragment inputs must be qualified as flat".into(),
+                    ));
+                }
             }
         }
 
@@ -880,26 +2981,48 @@ impl Program<'_> {
             let mut body = Vec::new();
 
             for (i, arg) in self.entry_args.iter().enumerate() {
-                if function_arg_use[function.index()]
-                    .get(i)
-                    .map_or(true, |u| !u.contains(EntryArgUse::READ))
+                let keep =
+                    self.keep_unused_entry_args && matches!(arg.binding, Binding::Location { .. });
+
+                if !keep
+                    && function_arg_use[function.index()]
+                        .get(i)
+                        .map_or(true, |u| !u.contains(EntryArgUse::READ))
                     || !arg.prologue.contains(stage.into())
                 {
                     continue;
                 }
 
-                let ty = self.module.global_variables[arg.handle].ty;
+                let mut ty = self.module.global_variables[arg.handle].ty;
                 let idx = arguments.len() as u32;
 
+                let mut pointer = expressions.append(Expression::GlobalVariable(arg.handle));
+                let mut value = expressions.append(Expression::FunctionArgument(idx));
+
+                // The sample mask is an array of signed integers in GLSL and a
+                // single unsigned integer in the IR
+                if arg.binding == Binding::BuiltIn(BuiltIn::SampleMask) {
+                    ty = sample_mask_ty(&mut self.module.types);
+
+                    let len = expressions.len();
+                    pointer = expressions.append(Expression::AccessIndex {
+                        base: pointer,
+                        index: 0,
+                    });
+                    value = expressions.append(Expression::As {
+                        expr: value,
+                        kind: ScalarKind::Sint,
+                        convert: None,
+                    });
+                    body.push(Statement::Emit(expressions.range_from(len)));
+                }
+
                 arguments.push(FunctionArgument {
                     name: arg.name.clone(),
                     ty,
                     binding: Some(arg.binding.clone()),
                 });
 
-                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
-                let value = expressions.append(Expression::FunctionArgument(idx));
-
                 body.push(Statement::Store { pointer, value });
             }
 
@@ -914,14 +3037,41 @@ impl Program<'_> {
             let mut components = Vec::new();
 
             for (i, arg) in self.entry_args.iter().enumerate() {
-                if function_arg_use[function.index()]
-                    .get(i)
-                    .map_or(true, |u| !u.contains(EntryArgUse::WRITE))
+                // Location outputs are the only entry arguments without a prologue
+                let keep = self.keep_unused_entry_args
+                    && matches!(arg.binding, Binding::Location { .. })
+                    && arg.prologue.is_empty();
+
+                if !keep
+                    && function_arg_use[function.index()]
+                        .get(i)
+                        .map_or(true, |u| !u.contains(EntryArgUse::WRITE))
                 {
                     continue;
                 }
 
-                let ty = self.module.global_variables[arg.handle].ty;
+                let mut ty = self.module.global_variables[arg.handle].ty;
+
+                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
+                let len = expressions.len();
+                let load = if arg.binding == Binding::BuiltIn(BuiltIn::SampleMask) {
+                    ty = sample_mask_ty(&mut self.module.types);
+
+                    let pointer = expressions.append(Expression::AccessIndex {
+                        base: pointer,
+                        index: 0,
+                    });
+                    let load = expressions.append(Expression::Load { pointer });
+                    expressions.append(Expression::As {
+                        expr: load,
+                        kind: ScalarKind::Uint,
+                        convert: None,
+                    })
+                } else {
+                    expressions.append(Expression::Load { pointer })
+                };
+                body.push(Statement::Emit(expressions.range_from(len)));
+                components.push(load);
 
                 members.push(StructMember {
                     name: arg.name.clone(),
@@ -931,12 +3081,6 @@ impl Program<'_> {
                 });
 
                 span += self.module.types[ty].inner.span(&self.module.constants);
-
-                let pointer = expressions.append(Expression::GlobalVariable(arg.handle));
-                let len = expressions.len();
-                let load = expressions.append(Expression::Load { pointer });
-                body.push(Statement::Emit(expressions.range_from(len)));
-                components.push(load)
             }
 
             let (ty, value) = if !components.is_empty() {
@@ -960,6 +3104,11 @@ impl Program<'_> {
 
             body.push(Statement::Return { value });
 
+            let name = self
+                .entry_point_names
+                .and_then(|names| names.get(&name).cloned())
+                .unwrap_or(name);
+
             self.module.entry_points.push(EntryPoint {
                 name,
                 stage,
@@ -979,5 +3128,99 @@ impl Program<'_> {
                 },
             });
         }
+
+        Ok(())
+    }
+}
+
+/// The callee, arguments and result of a call statement
+type CallMut<'a> = (
+    &'a mut Handle<Function>,
+    &'a [Handle<Expression>],
+    Option<Handle<Expression>>,
+);
+
+/// Returns the calls in `block`, the statements are walked with an explicit
+/// stack instead of recursing so that deeply nested bodies can't overflow the
+/// stack
+fn calls_mut(block: &mut Block) -> Vec<CallMut<'_>> {
+    let mut calls = Vec::new();
+    let mut stack = vec![block.iter_mut()];
+
+    while let Some(stmts) = stack.last_mut() {
+        let stmt = match stmts.next() {
+            Some(stmt) => stmt,
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+
+        match *stmt {
+            Statement::Block(ref mut block) => stack.push(block.iter_mut()),
+            Statement::If {
+                ref mut accept,
+                ref mut reject,
+                ..
+            } => {
+                stack.push(accept.iter_mut());
+                stack.push(reject.iter_mut());
+            }
+            Statement::Switch {
+                ref mut cases,
+                ref mut default,
+                ..
+            } => {
+                stack.extend(cases.iter_mut().map(|case| case.body.iter_mut()));
+                stack.push(default.iter_mut());
+            }
+            Statement::Loop {
+                ref mut body,
+                ref mut continuing,
+            } => {
+                stack.push(body.iter_mut());
+                stack.push(continuing.iter_mut());
+            }
+            Statement::Call {
+                ref mut function,
+                ref arguments,
+                result,
+            } => calls.push((function, &arguments[..], result)),
+            _ => {}
+        }
+    }
+
+    calls
+}
+
+/// Copies `function`, the arenas are copied element by element since they
+/// can't be cloned
+fn copy_function(function: &Function) -> Function {
+    let mut copy = Function {
+        name: function.name.clone(),
+        arguments: function.arguments.clone(),
+        result: function.result.clone(),
+        local_variables: Arena::new(),
+        expressions: Arena::new(),
+        named_expressions: function.named_expressions.clone(),
+        body: function.body.clone(),
+    };
+    for (_, local) in function.local_variables.iter() {
+        copy.local_variables.append(local.clone());
+    }
+    for (_, expr) in function.expressions.iter() {
+        copy.expressions.append(expr.clone());
     }
+    copy
+}
+
+/// Type of the sample mask builtin in the IR
+fn sample_mask_ty(types: &mut Arena<Type>) -> Handle<Type> {
+    types.fetch_or_append(Type {
+        name: None,
+        inner: TypeInner::Scalar {
+            kind: ScalarKind::Uint,
+            width: 4,
+        },
+    })
 }
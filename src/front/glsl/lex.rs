@@ -6,7 +6,7 @@ use super::{
 use crate::FastHashMap;
 use pp_rs::{
     pp::Preprocessor,
-    token::{Punct, Token as PPToken, TokenValue as PPTokenValue},
+    token::{Integer, Punct, Token as PPToken, TokenValue as PPTokenValue},
 };
 use std::collections::VecDeque;
 
@@ -18,6 +18,8 @@ pub struct Lexer<'a> {
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str, defines: &'a FastHashMap<String, String>) -> Self {
         let mut pp = Preprocessor::new(input);
+        // Only a single source string is ever processed
+        pp.add_define("__FILE__", "0").unwrap();
         for (define, value) in defines {
             pp.add_define(define, value).unwrap(); //TODO: handle error
         }
@@ -65,6 +67,7 @@ impl<'a> Iterator for Lexer<'a> {
                     "out" => TokenValue::Out,
                     "uniform" => TokenValue::Uniform,
                     "buffer" => TokenValue::Buffer,
+                    "shared" => TokenValue::Shared,
                     "flat" => TokenValue::Interpolation(crate::Interpolation::Flat),
                     "noperspective" => TokenValue::Interpolation(crate::Interpolation::Linear),
                     "smooth" => TokenValue::Interpolation(crate::Interpolation::Perspective),
@@ -165,6 +168,14 @@ impl<'a> Iterator for Lexer<'a> {
                 TokenValue::Pragma
             }
             PPTokenValue::Version(version) => {
+                if let Some(&PPToken {
+                    value: PPTokenValue::Integer(Integer { value, .. }),
+                    ..
+                }) = version.tokens.first()
+                {
+                    let _ = self.pp.add_define("__VERSION__", &value.to_string());
+                }
+
                 for t in version.tokens {
                     self.tokens.push_back(t);
                 }
@@ -1,7 +1,8 @@
 pub use error::ErrorKind;
+pub use functions::IntrinsicContext;
 pub use token::{SourceMetadata, Token};
 
-use crate::{FastHashMap, Module, ShaderStage};
+use crate::{Constant, Expression, FastHashMap, GlobalVariable, Handle, Module, ShaderStage};
 
 mod lex;
 
@@ -9,7 +10,7 @@ mod ast;
 use ast::Program;
 
 mod error;
-pub use error::ParseError;
+pub use error::{ParseError, Warning};
 mod constants;
 mod functions;
 mod parser;
@@ -19,18 +20,134 @@ mod token;
 mod types;
 mod variables;
 
+/// Lowers a call to a custom intrinsic function given its already lowered
+/// arguments, returning the expression holding the result if there's one
+pub type IntrinsicFn = dyn Fn(
+        &mut IntrinsicContext,
+        &[Handle<Expression>],
+    ) -> Result<Option<Handle<Expression>>, ErrorKind>
+    + Send
+    + Sync;
+
+/// Custom intrinsic functions by name, calls to them are lowered by their
+/// callback instead of calling a function declared in the shader
 #[derive(Default)]
+pub struct Intrinsics(FastHashMap<String, Box<IntrinsicFn>>);
+
+impl Intrinsics {
+    /// Registers the intrinsic `name`, replacing any previous one
+    pub fn add<F>(&mut self, name: impl Into<String>, lower: F) -> &mut Self
+    where
+        F: Fn(
+                &mut IntrinsicContext,
+                &[Handle<Expression>],
+            ) -> Result<Option<Handle<Expression>>, ErrorKind>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.0.insert(name.into(), Box::new(lower));
+        self
+    }
+
+    fn get(&self, name: &str) -> Option<&IntrinsicFn> {
+        self.0.get(name).map(|lower| lower.as_ref())
+    }
+}
+
+impl std::fmt::Debug for Intrinsics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
 pub struct Options {
     pub entry_points: FastHashMap<String, ShaderStage>,
     pub defines: FastHashMap<String, String>,
+    /// Keep all declared `in`/`out` variables in the entry point interface,
+    /// even if the shader never reads or writes them
+    pub keep_unused_entry_args: bool,
+    /// Replace `switch` statements with a constant selector by the body of
+    /// the selected case
+    pub fold_constant_switches: bool,
+    /// Engine specific functions that are lowered by a callback, they are
+    /// only used for calls that no declared function matches
+    pub intrinsics: Intrinsics,
+    /// How deep expressions and statements can be nested before the source
+    /// is rejected, this bounds the recursion of the parser and the lowering
+    pub max_nesting_depth: usize,
+    /// Keep the source names of builtins in the entry point interface and
+    /// name the temporaries of `out` arguments after their parameters
+    pub preserve_names: bool,
+    /// Names of the generated entry points by the name of their function,
+    /// entry points whose function isn't in the map keep its name
+    pub entry_point_names: FastHashMap<String, String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            entry_points: FastHashMap::default(),
+            defines: FastHashMap::default(),
+            keep_unused_entry_args: false,
+            fold_constant_switches: false,
+            intrinsics: Intrinsics::default(),
+            max_nesting_depth: 256,
+            preserve_names: false,
+            entry_point_names: FastHashMap::default(),
+        }
+    }
 }
 
 pub fn parse_str(source: &str, options: &Options) -> Result<Module, ParseError> {
+    parse_str_with_output(source, options).map(|output| output.module)
+}
+
+/// The module parsed from a source along with what was found about the
+/// source that isn't part of the module
+#[derive(Debug)]
+pub struct ParseOutput {
+    pub module: Module,
+    /// Non-fatal issues found in the source, in the order they were found
+    pub warnings: Vec<Warning>,
+    pub reflection: Reflection,
+}
+
+/// Like [`parse_str`] but also returns the warnings and the [`Reflection`]
+/// of the source
+pub fn parse_str_with_output(source: &str, options: &Options) -> Result<ParseOutput, ParseError> {
+    let program = parse_program(source, options)?;
+    Ok(ParseOutput {
+        module: program.module,
+        warnings: program.warnings,
+        reflection: Reflection {
+            uniform_defaults: program.uniform_defaults,
+        },
+    })
+}
+
+fn parse_program<'a>(source: &str, options: &'a Options) -> Result<Program<'a>, ParseError> {
     let mut program = Program::new(&options.entry_points);
+    program.keep_unused_entry_args = options.keep_unused_entry_args;
+    program.fold_constant_switches = options.fold_constant_switches;
+    program.intrinsics = Some(&options.intrinsics);
+    program.max_nesting_depth = options.max_nesting_depth;
+    program.preserve_names = options.preserve_names;
+    program.entry_point_names = Some(&options.entry_point_names);
 
     let lex = lex::Lexer::new(source, &options.defines);
     let mut parser = parser::Parser::new(&mut program, lex);
-    parser.parse()?;
+    parser
+        .parse()
+        .map_err(|kind| ParseError::with_source(kind, source))?;
+
+    Ok(program)
+}
 
-    Ok(program.module)
+/// Information about the source that isn't part of the module
+#[derive(Debug, Default)]
+pub struct Reflection {
+    /// Default values of the uniforms declared with an initializer, which
+    /// the host can use to set them
+    pub uniform_defaults: FastHashMap<Handle<GlobalVariable>, Handle<Constant>>,
 }
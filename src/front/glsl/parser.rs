@@ -5,7 +5,8 @@ use super::{
     },
     error::ErrorKind,
     lex::Lexer,
-    token::{SourceMetadata, Token, TokenValue},
+    token::{PreprocessorError, SourceMetadata, Token, TokenValue},
+    types::parse_storage_format,
     variables::{GlobalOrConstant, VarDeclaration},
     Program,
 };
@@ -13,8 +14,8 @@ use crate::{
     arena::Handle,
     front::glsl::{ast::Precision, error::ExpectedToken},
     Arena, ArraySize, BinaryOperator, Block, Constant, ConstantInner, Expression, Function,
-    FunctionResult, ResourceBinding, ScalarKind, ScalarValue, Statement, StorageClass,
-    StructMember, SwitchCase, Type, TypeInner, UnaryOperator,
+    FunctionResult, ImageClass, ImageDimension, ResourceBinding, ScalarKind, ScalarValue,
+    Statement, StorageClass, StructMember, SwitchCase, Type, TypeInner, UnaryOperator,
 };
 use core::convert::TryFrom;
 use std::{iter::Peekable, mem};
@@ -24,6 +25,8 @@ type Result<T> = std::result::Result<T, ErrorKind>;
 pub struct Parser<'source, 'program, 'options> {
     program: &'program mut Program<'options>,
     lexer: Peekable<Lexer<'source>>,
+    /// Current nesting depth of the expressions and statements being parsed
+    depth: usize,
 }
 
 impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
@@ -31,6 +34,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         Parser {
             program,
             lexer: lexer.peekable(),
+            depth: 0,
         }
     }
 
@@ -57,7 +61,22 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
     }
 
     fn bump(&mut self) -> Result<Token> {
-        self.lexer.next().ok_or(ErrorKind::EndOfFile)
+        let token = self.lexer.next().ok_or(ErrorKind::EndOfFile)?;
+
+        // Float macros are commonly (but wrongly) used in `#if` expressions,
+        // directives only take integers so explain that instead of reporting
+        // the bare token
+        if let TokenValue::Unknown(PreprocessorError::UnexpectedToken(
+            pp_rs::token::TokenValue::Float(_),
+        )) = token.value
+        {
+            return Err(ErrorKind::SemanticError(
+                token.meta,
+                "Preprocessor directives only take integers, not floats".into(),
+            ));
+        }
+
+        Ok(token)
     }
 
     /// Returns None on the end of the file rather than an error like other methods
@@ -73,14 +92,75 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         self.lexer.peek().ok_or(ErrorKind::EndOfFile)
     }
 
+    /// Enters a nested expression or statement, must be paired with a call to
+    /// `leave` when the parsing succeeds (errors stop the parser so they don't)
+    fn enter(&mut self) -> Result<()> {
+        if self.depth >= self.program.max_nesting_depth {
+            return Err(ErrorKind::SemanticError(
+                self.expect_peek()?.meta,
+                "Maximum nesting depth exceeded".into(),
+            ));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
     pub fn parse(&mut self) -> Result<()> {
         self.parse_version()?;
 
-        while self.lexer.peek().is_some() {
-            self.parse_external_declaration()?;
+        while let Some(token) = self.lexer.peek() {
+            if token.value == TokenValue::Extension {
+                self.bump()?;
+                self.parse_extension()?;
+            } else {
+                self.parse_external_declaration()?;
+            }
         }
 
-        self.program.add_entry_points();
+        self.program.specialize_functions();
+        self.program.add_entry_points()?;
+
+        Ok(())
+    }
+
+    /// Parses the `name : behavior` following an `#extension` directive and
+    /// records whether the extension is enabled
+    fn parse_extension(&mut self) -> Result<()> {
+        let (name, _) = self.expect_ident()?;
+        self.expect(TokenValue::Colon)?;
+        let (behavior, behavior_meta) = self.expect_ident()?;
+
+        match behavior.as_str() {
+            "require" | "enable" if name == "all" => {
+                return Err(ErrorKind::SemanticError(
+                    behavior_meta,
+                    "The \"all\" extension can only be warned on or disabled".into(),
+                ))
+            }
+            "require" | "enable" | "warn" => {
+                if name != "all" {
+                    self.program.extensions.insert(name);
+                }
+            }
+            "disable" => {
+                if name == "all" {
+                    self.program.extensions.clear();
+                } else {
+                    self.program.extensions.remove(&name);
+                }
+            }
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    behavior_meta,
+                    format!("Unknown extension behavior \"{}\"", behavior).into(),
+                ))
+            }
+        }
 
         Ok(())
     }
@@ -89,9 +169,16 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         self.expect(TokenValue::Version)?;
 
         let version = self.bump()?;
-        match version.value {
+        let es = match version.value {
             TokenValue::IntConstant(i) => match i.value {
-                440 | 450 | 460 => self.program.version = i.value as u16,
+                300 | 310 | 320 => {
+                    self.program.version = i.value as u16;
+                    true
+                }
+                440 | 450 | 460 => {
+                    self.program.version = i.value as u16;
+                    false
+                }
                 _ => return Err(ErrorKind::InvalidVersion(version.meta, i.value)),
             },
             _ => {
@@ -100,7 +187,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     vec![ExpectedToken::IntLiteral],
                 ))
             }
-        }
+        };
 
         let profile = self.lexer.peek();
         self.program.profile = match profile {
@@ -111,30 +198,49 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 let (name, meta) = self.expect_ident()?;
 
                 match name.as_str() {
-                    "core" => Profile::Core,
+                    "core" if !es => Profile::Core,
+                    "compatibility" if !es => Profile::Compatibility,
+                    "es" if es => Profile::Es,
                     _ => return Err(ErrorKind::InvalidProfile(meta, name)),
                 }
             }
+            // The es versions must always specify the profile
+            _ if es => {
+                return Err(ErrorKind::InvalidVersion(
+                    version.meta,
+                    self.program.version.into(),
+                ))
+            }
             _ => Profile::Core,
         };
 
         Ok(())
     }
 
-    /// Parses an optional array_specifier returning `Ok(None)` if there is no
-    /// LeftBracket
-    fn parse_array_specifier(&mut self) -> Result<Option<ArraySize>> {
-        if self.bump_if(TokenValue::LeftBracket).is_some() {
-            if self.bump_if(TokenValue::RightBracket).is_some() {
-                return Ok(Some(ArraySize::Dynamic));
+    /// Parses the sizes of every dimension of an array specifier, outermost
+    /// first, or none if there isn't an array specifier
+    fn parse_array_specifier(&mut self) -> Result<Vec<ArraySize>> {
+        let mut sizes = Vec::new();
+
+        while let Some(Token { meta, .. }) = self.bump_if(TokenValue::LeftBracket) {
+            if let Some(Token { meta: end_meta, .. }) = self.bump_if(TokenValue::RightBracket) {
+                if !sizes.is_empty() {
+                    return Err(ErrorKind::SemanticError(
+                        meta.union(&end_meta),
+                        "Only the outermost array dimension can be unsized".into(),
+                    ));
+                }
+
+                sizes.push(ArraySize::Dynamic);
+                continue;
             }
 
             let (constant, _) = self.parse_constant_expression()?;
             self.expect(TokenValue::RightBracket)?;
-            Ok(Some(ArraySize::Constant(constant)))
-        } else {
-            Ok(None)
+            sizes.push(ArraySize::Constant(constant));
         }
+
+        Ok(sizes)
     }
 
     fn parse_type(&mut self) -> Result<(Option<Handle<Type>>, SourceMetadata)> {
@@ -146,8 +252,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 let ty_name = self.expect_ident()?.0;
                 self.expect(TokenValue::LeftBrace)?;
                 let mut members = Vec::new();
-                let span = self.parse_struct_declaration_list(&mut members)?;
+                let mut member_qualifiers = Vec::new();
+                let span = self.parse_struct_declaration_list(
+                    &mut members,
+                    &mut member_qualifiers,
+                    &ty_name,
+                )?;
                 self.expect(TokenValue::RightBrace)?;
+                Self::forbid_member_qualifiers(&member_qualifiers)?;
 
                 let ty = self.program.module.types.append(Type {
                     name: Some(ty_name.clone()),
@@ -188,8 +300,10 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         Ok((ty, meta))
     }
 
-    fn maybe_array(&mut self, base: Handle<Type>, size: Option<ArraySize>) -> Handle<Type> {
-        size.map(|size| {
+    /// Wraps `base` in an array for each of the `sizes`, the last size is the
+    /// innermost dimension
+    fn maybe_array(&mut self, base: Handle<Type>, sizes: Vec<ArraySize>) -> Handle<Type> {
+        sizes.into_iter().rev().fold(base, |base, size| {
             self.program.module.types.fetch_or_append(Type {
                 name: None,
                 inner: TypeInner::Array {
@@ -201,7 +315,6 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 },
             })
         })
-        .unwrap_or(base)
     }
 
     fn peek_type_qualifier(&mut self) -> bool {
@@ -214,6 +327,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             | TokenValue::Out
             | TokenValue::Uniform
             | TokenValue::Buffer
+            | TokenValue::Shared
             | TokenValue::Layout => true,
             _ => false,
         })
@@ -243,6 +357,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     TokenValue::Buffer => TypeQualifier::StorageQualifier(
                         StorageQualifier::StorageClass(StorageClass::Storage),
                     ),
+                    TokenValue::Shared => TypeQualifier::StorageQualifier(
+                        StorageQualifier::StorageClass(StorageClass::WorkGroup),
+                    ),
                     TokenValue::Sampling(s) => TypeQualifier::Sampling(s),
                     TokenValue::PrecisionQualifier(p) => TypeQualifier::Precision(p),
                     _ => unreachable!(),
@@ -352,6 +469,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                             "local_size_x" => TypeQualifier::WorkGroupSize(0, value),
                             "local_size_y" => TypeQualifier::WorkGroupSize(1, value),
                             "local_size_z" => TypeQualifier::WorkGroupSize(2, value),
+                            // Input attachments are bound as regular images
+                            // so the index isn't needed
+                            "input_attachment_index" => return Ok(()),
                             _ => return Err(ErrorKind::UnknownLayoutQualifier(token.meta, name)),
                         },
                         token.meta,
@@ -375,7 +495,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                         "early_fragment_tests" => {
                             qualifiers.push((TypeQualifier::EarlyFragmentTests, token.meta))
                         }
-                        _ => return Err(ErrorKind::UnknownLayoutQualifier(token.meta, name)),
+                        _ => match parse_storage_format(&name) {
+                            Some(format) => {
+                                qualifiers.push((TypeQualifier::StorageFormat(format), token.meta))
+                            }
+                            None => {
+                                return Err(ErrorKind::UnknownLayoutQualifier(token.meta, name))
+                            }
+                        },
                     }
                 };
 
@@ -452,23 +579,55 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
     fn peek_parameter_qualifier(&mut self) -> bool {
         self.lexer.peek().map_or(false, |t| match t.value {
-            TokenValue::In | TokenValue::Out | TokenValue::InOut | TokenValue::Const => true,
+            TokenValue::In
+            | TokenValue::Out
+            | TokenValue::InOut
+            | TokenValue::Const
+            | TokenValue::PrecisionQualifier(_) => true,
             _ => false,
         })
     }
 
     /// Returns the parsed `ParameterQualifier` or `ParameterQualifier::In`
-    fn parse_parameter_qualifier(&mut self) -> ParameterQualifier {
-        if self.peek_parameter_qualifier() {
-            match self.bump().unwrap().value {
+    ///
+    /// Precision qualifiers are accepted but ignored since they don't have a
+    /// representation in the IR
+    fn parse_parameter_qualifier(&mut self) -> Result<ParameterQualifier> {
+        let mut direction = None;
+        let mut constant = None;
+
+        while self.peek_parameter_qualifier() {
+            let Token { value, meta } = self.bump()?;
+
+            let qualifier = match value {
                 TokenValue::In => ParameterQualifier::In,
                 TokenValue::Out => ParameterQualifier::Out,
                 TokenValue::InOut => ParameterQualifier::InOut,
-                TokenValue::Const => ParameterQualifier::Const,
+                TokenValue::Const => {
+                    constant = Some(meta);
+                    continue;
+                }
+                TokenValue::PrecisionQualifier(_) => continue,
                 _ => unreachable!(),
+            };
+
+            if direction.is_some() {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    "Parameters can only have one of in, out or inout".into(),
+                ));
             }
-        } else {
-            ParameterQualifier::In
+            direction = Some(qualifier);
+        }
+
+        match (constant, direction) {
+            (Some(meta), Some(direction)) if direction.is_lhs() => Err(ErrorKind::SemanticError(
+                meta,
+                "const parameters can't be out or inout".into(),
+            )),
+            // `const in` is the same as `const`
+            (Some(_), _) => Ok(ParameterQualifier::Const),
+            (None, direction) => Ok(direction.unwrap_or(ParameterQualifier::In)),
         }
     }
 
@@ -487,11 +646,72 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         //     initializer
         //     initializer_list COMMA initializer
         if let Some(Token { mut meta, .. }) = self.bump_if(TokenValue::LeftBrace) {
+            // The type of each element of the list and how many there must be
+            let (component_tys, count): (Vec<_>, _) = match self.program.module.types[ty].inner {
+                TypeInner::Vector { size, kind, width } => {
+                    let component_ty = self.program.module.types.fetch_or_append(Type {
+                        name: None,
+                        inner: TypeInner::Scalar { kind, width },
+                    });
+                    (vec![component_ty], Some(size as usize))
+                }
+                TypeInner::Matrix {
+                    columns,
+                    rows,
+                    width,
+                } => {
+                    let component_ty = self.program.module.types.fetch_or_append(Type {
+                        name: None,
+                        inner: TypeInner::Vector {
+                            size: rows,
+                            kind: ScalarKind::Float,
+                            width,
+                        },
+                    });
+                    (vec![component_ty], Some(columns as usize))
+                }
+                TypeInner::Array { base, size, .. } => {
+                    let count = match size {
+                        ArraySize::Constant(constant) => self.program.module.constants[constant]
+                            .to_array_length()
+                            .map(|len| len as usize),
+                        ArraySize::Dynamic => None,
+                    };
+                    (vec![base], count)
+                }
+                TypeInner::Struct { ref members, .. } => (
+                    members.iter().map(|member| member.ty).collect(),
+                    Some(members.len()),
+                ),
+                _ => {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        "Initializer lists can only be used with aggregate types".into(),
+                    ))
+                }
+            };
+
+            // Lists have at least one element but empty structs have no type
+            // to parse it as
+            if component_tys.is_empty() {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    "Initializer lists can't be used with empty structs".into(),
+                ));
+            }
+
             // initializer_list
             let mut components = Vec::new();
             loop {
-                // TODO: Change type
-                components.push(self.parse_initializer(ty, ctx, body)?.0);
+                let component_ty = component_tys[components.len().min(component_tys.len() - 1)];
+                let (mut component, component_meta) =
+                    self.parse_initializer(component_ty, ctx, body)?;
+
+                if let Some(kind) = self.program.module.types[component_ty].inner.scalar_kind() {
+                    ctx.implicit_conversion(self.program, &mut component, component_meta, kind)?;
+                }
+
+                components.push(component);
 
                 let token = self.bump()?;
                 match token.value {
@@ -516,6 +736,30 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 }
             }
 
+            if let Some(count) = count {
+                if count != components.len() {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!(
+                            "Initializer list expects {} elements but got {}",
+                            count,
+                            components.len()
+                        )
+                        .into(),
+                    ));
+                }
+            }
+
+            // Unsized arrays are sized by their initializer list
+            let ty = match self.program.module.types[ty].inner {
+                TypeInner::Array {
+                    base,
+                    size: ArraySize::Dynamic,
+                    ..
+                } => self.program.sized_array(base, components.len()),
+                _ => ty,
+            };
+
             Ok((
                 ctx.add_expression(Expression::Compose { ty, components }, body),
                 meta,
@@ -582,15 +826,39 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
             // parse an array specifier if it exists
             // NOTE: unlike other parse methods this one doesn't expect an array specifier and
-            // returns Ok(None) rather than an error if there is not one
+            // returns no sizes rather than an error if there is not one
             let array_specifier = self.parse_array_specifier()?;
-            let ty = self.maybe_array(ty, array_specifier);
+            let mut ty = self.maybe_array(ty, array_specifier);
 
             let init = self
                 .bump_if(TokenValue::Assign)
                 .map::<Result<_>, _>(|_| {
                     let (mut expr, init_meta) = self.parse_initializer(ty, ctx.ctx, ctx.body)?;
 
+                    // Unsized arrays take the size of their initializer
+                    if let TypeInner::Array {
+                        base,
+                        size: ArraySize::Dynamic,
+                        ..
+                    } = self.program.module.types[ty].inner
+                    {
+                        let len = match *self.program.resolve_type(ctx.ctx, expr, init_meta)? {
+                            TypeInner::Array {
+                                size: ArraySize::Constant(constant),
+                                ..
+                            } => self.program.module.constants[constant].to_array_length(),
+                            _ => None,
+                        };
+                        let len = len.ok_or_else(|| {
+                            ErrorKind::SemanticError(
+                                init_meta,
+                                "Array size can't be inferred from this initializer".into(),
+                            )
+                        })?;
+
+                        ty = self.program.sized_array(base, len as usize);
+                    }
+
                     if let Some(kind) = self.program.module.types[ty].inner.scalar_kind() {
                         ctx.ctx
                             .implicit_conversion(self.program, &mut expr, init_meta, kind)?;
@@ -605,8 +873,24 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             // TODO: Should we try to make constants here?
             // This is mostly a hack because we don't yet support adding
             // bodies to entry points for variable initialization
-            let maybe_constant =
-                init.and_then(|(root, meta)| self.program.solve_constant(ctx.ctx, root, meta).ok());
+            let maybe_constant = match init {
+                Some((root, meta)) => {
+                    // Global constants can't fall back to a runtime initializer
+                    let global_const = ctx.external
+                        && ctx.qualifiers.iter().any(|qualifier| {
+                            matches!(
+                                qualifier.0,
+                                TypeQualifier::StorageQualifier(StorageQualifier::Const)
+                            )
+                        });
+                    if global_const {
+                        Some(self.program.solve_constant(ctx.ctx, root, meta)?)
+                    } else {
+                        self.program.try_solve_constant(ctx.ctx, root, meta)
+                    }
+                }
+                None => None,
+            };
 
             let pointer = ctx.add_var(self.program, ty, name, maybe_constant, meta)?;
 
@@ -681,6 +965,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                                 &mut local_variables,
                                 &mut arguments,
                             );
+                            context.result = ty;
 
                             self.parse_function_args(
                                 &mut context,
@@ -718,8 +1003,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
                                     // parse the body
                                     self.parse_compound_statement(&mut context, &mut body)?;
+                                    context.warn_unused_locals(self.program);
 
-                                    let Context { arg_use, .. } = context;
+                                    let Context {
+                                        arg_use,
+                                        barrier,
+                                        arg_locals,
+                                        ..
+                                    } = context;
                                     let handle = self.program.add_function(
                                         Function {
                                             name: Some(name.clone()),
@@ -737,6 +1028,12 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                                     )?;
 
                                     self.program.function_arg_use[handle.index()] = arg_use;
+                                    if let Some(meta) = barrier {
+                                        self.program.function_barriers.insert(handle, meta);
+                                    }
+                                    if !arg_locals.is_empty() {
+                                        self.program.function_arg_locals.insert(handle, arg_locals);
+                                    }
 
                                     Ok(true)
                                 }
@@ -804,6 +1101,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                         for &(ref qualifier, meta) in qualifiers.iter() {
                             match *qualifier {
                                 TypeQualifier::WorkGroupSize(i, value) => {
+                                    if value == 0 {
+                                        return Err(ErrorKind::SemanticError(
+                                            meta,
+                                            "Workgroup size dimensions must be greater than zero"
+                                                .into(),
+                                        ));
+                                    }
+
                                     self.program.workgroup_size[i] = value
                                 }
                                 TypeQualifier::EarlyFragmentTests => {
@@ -862,13 +1167,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                         | TypeInner::Scalar {
                             kind: ScalarKind::Sint,
                             ..
-                        } => {}
-                        _ => {
-                            return Err(ErrorKind::SemanticError(
-                                meta,
-                                "Precision statement can only work on floats and ints".into(),
-                            ))
                         }
+                        | TypeInner::Image { .. }
+                        | TypeInner::Sampler { .. } => {}
+                        _ => return Err(ErrorKind::SemanticError(
+                            meta,
+                            "Precision statement can only work on floats, ints and opaque types"
+                                .into(),
+                        )),
                     }
 
                     self.expect(TokenValue::Semicolon)?;
@@ -887,38 +1193,19 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         mut meta: SourceMetadata,
     ) -> Result<bool> {
         let mut members = Vec::new();
-        let span = self.parse_struct_declaration_list(&mut members)?;
+        let mut member_qualifiers = Vec::new();
+        let span =
+            self.parse_struct_declaration_list(&mut members, &mut member_qualifiers, &ty_name)?;
         self.expect(TokenValue::RightBrace)?;
 
-        let mut ty = self.program.module.types.append(Type {
-            name: Some(ty_name),
-            inner: TypeInner::Struct {
-                top_level: true,
-                members: members.clone(),
-                span,
-            },
-        });
-
         let token = self.bump()?;
-        let name = match token.value {
-            TokenValue::Semicolon => None,
+        let (name, array_size) = match token.value {
+            TokenValue::Semicolon => (None, Vec::new()),
             TokenValue::Identifier(name) => {
-                if let Some(size) = self.parse_array_specifier()? {
-                    ty = self.program.module.types.fetch_or_append(Type {
-                        name: None,
-                        inner: TypeInner::Array {
-                            base: ty,
-                            size,
-                            stride: self.program.module.types[ty]
-                                .inner
-                                .span(&self.program.module.constants),
-                        },
-                    });
-                }
-
+                let size = self.parse_array_specifier()?;
                 self.expect(TokenValue::Semicolon)?;
 
-                Some(name)
+                (Some(name), size)
             }
             _ => {
                 return Err(ErrorKind::InvalidToken(
@@ -929,6 +1216,44 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         };
         meta = meta.union(&token.meta);
 
+        // `in` and `out` blocks only group varyings so each member
+        // becomes its own entry point argument or result
+        let interface = qualifiers.iter().any(|qualifier| {
+            matches!(
+                qualifier.0,
+                TypeQualifier::StorageQualifier(StorageQualifier::Input)
+                    | TypeQualifier::StorageQualifier(StorageQualifier::Output)
+            )
+        });
+        if interface {
+            if !array_size.is_empty() {
+                return Err(ErrorKind::NotImplemented(
+                    meta,
+                    "arrays of interface blocks",
+                ));
+            }
+
+            self.program.add_interface_block(
+                qualifiers,
+                members.into_iter().zip(member_qualifiers).collect(),
+                name,
+                meta,
+            )?;
+            return Ok(true);
+        }
+
+        Self::forbid_member_qualifiers(&member_qualifiers)?;
+
+        let ty = self.program.module.types.append(Type {
+            name: Some(ty_name),
+            inner: TypeInner::Struct {
+                top_level: true,
+                members: members.clone(),
+                span,
+            },
+        });
+        let ty = self.maybe_array(ty, array_size);
+
         let global = self.program.add_global_var(VarDeclaration {
             qualifiers,
             ty,
@@ -960,14 +1285,63 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         Ok(true)
     }
 
+    /// Errors if a member of a struct or of a block that isn't an `in` or
+    /// `out` block has interpolation or sampling qualifiers
+    fn forbid_member_qualifiers(
+        member_qualifiers: &[Vec<(TypeQualifier, SourceMetadata)>],
+    ) -> Result<()> {
+        match member_qualifiers.iter().flatten().next() {
+            Some(&(_, meta)) => Err(ErrorKind::SemanticError(
+                meta,
+                "Interpolation and sampling qualifiers can only be used on members of in/out blocks"
+                    .into(),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// `name` is the name of the struct or block being declared, it's only
+    /// used to produce better errors for self referencing members, the
+    /// interpolation and sampling qualifiers of each member are pushed to
+    /// `member_qualifiers`
     // TODO: Accept layout arguments
-    fn parse_struct_declaration_list(&mut self, members: &mut Vec<StructMember>) -> Result<u32> {
+    fn parse_struct_declaration_list(
+        &mut self,
+        members: &mut Vec<StructMember>,
+        member_qualifiers: &mut Vec<Vec<(TypeQualifier, SourceMetadata)>>,
+        name: &str,
+    ) -> Result<u32> {
         let mut span = 0;
 
         loop {
-            // TODO: type_qualifier
+            // TODO: Other type qualifiers
+            let mut qualifiers = Vec::new();
+            while let TokenValue::Interpolation(_) | TokenValue::Sampling(_) =
+                self.expect_peek()?.value
+            {
+                let token = self.bump()?;
+                qualifiers.push((
+                    match token.value {
+                        TokenValue::Interpolation(i) => TypeQualifier::Interpolation(i),
+                        TokenValue::Sampling(s) => TypeQualifier::Sampling(s),
+                        _ => unreachable!(),
+                    },
+                    token.meta,
+                ));
+            }
+            member_qualifiers.push(qualifiers);
 
-            let ty = self.parse_type_non_void()?.0;
+            // The struct is only registered after all of its members are
+            // parsed so a member of its own type shows up as an unknown type
+            let ty = match self.parse_type_non_void() {
+                Err(ErrorKind::UnknownType(meta, ref ty_name)) if ty_name == name => {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!("Struct \"{}\" can't have a member of its own type", name).into(),
+                    ))
+                }
+                res => res?.0,
+            };
             let name = self.expect_ident()?.0;
 
             let array_specifier = self.parse_array_specifier()?;
@@ -1082,21 +1456,43 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Identifier(_) => {
                 let (name, mut meta) = self.expect_ident()?;
 
-                let expr = if self.bump_if(TokenValue::LeftParen).is_some() {
+                // A type name followed by an array specifier is an array constructor
+                let array_ty = match self.program.lookup_type.get(&name).copied() {
+                    Some(ty) if self.expect_peek()?.value == TokenValue::LeftBracket => {
+                        let size = self.parse_array_specifier()?;
+                        self.expect(TokenValue::LeftParen)?;
+                        Some(self.maybe_array(ty, size))
+                    }
+                    _ => None,
+                };
+
+                let expr = if array_ty.is_some() || self.bump_if(TokenValue::LeftParen).is_some() {
                     let args = self.parse_function_call_args(ctx, body, &mut meta)?;
 
-                    let kind = match self.program.lookup_type.get(&name) {
-                        Some(ty) => FunctionCallKind::TypeConstructor(*ty),
-                        None => FunctionCallKind::Function(name),
-                    };
+                    let kind =
+                        match array_ty.or_else(|| self.program.lookup_type.get(&name).copied()) {
+                            Some(ty) => FunctionCallKind::TypeConstructor(ty),
+                            None => FunctionCallKind::Function(name),
+                        };
 
                     HirExpr {
                         kind: HirExprKind::Call(FunctionCall { kind, args }),
                         meta,
                     }
                 } else {
-                    let var = match self.program.lookup_variable(ctx, body, &name)? {
+                    let var = match self.program.lookup_variable(ctx, body, &name, meta)? {
                         Some(var) => var,
+                        // Members of named interface blocks are declared as `instance.member`
+                        None if self.bump_if(TokenValue::Dot).is_some() => {
+                            let (field, field_meta) = self.expect_ident()?;
+                            let qualified = format!("{}.{}", name, field);
+                            meta = meta.union(&field_meta);
+
+                            match self.program.lookup_variable(ctx, body, &qualified, meta)? {
+                                Some(var) => var,
+                                None => return Err(ErrorKind::UnknownVariable(meta, qualified)),
+                            }
+                        }
                         None => return Err(ErrorKind::UnknownVariable(meta, name)),
                     };
 
@@ -1111,20 +1507,41 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::TypeName(_) => {
                 let Token { value, mut meta } = self.bump()?;
 
-                let handle = if let TokenValue::TypeName(ty) = value {
-                    self.program.module.types.fetch_or_append(ty)
+                let ty = if let TokenValue::TypeName(ty) = value {
+                    ty
                 } else {
                     unreachable!()
                 };
 
+                // Images can't be constructed so the legacy sampling functions
+                // which share their names with the image types are calls instead
+                let kind = match ty.inner {
+                    TypeInner::Image {
+                        dim,
+                        arrayed: false,
+                        class:
+                            ImageClass::Sampled {
+                                kind: ScalarKind::Float,
+                                multi: false,
+                            },
+                    } if dim == ImageDimension::D2 || dim == ImageDimension::Cube => {
+                        FunctionCallKind::Function(match dim {
+                            ImageDimension::D2 => "texture2D".into(),
+                            _ => "textureCube".into(),
+                        })
+                    }
+                    _ => {
+                        let base = self.program.module.types.fetch_or_append(ty);
+                        let size = self.parse_array_specifier()?;
+                        FunctionCallKind::TypeConstructor(self.maybe_array(base, size))
+                    }
+                };
+
                 self.expect(TokenValue::LeftParen)?;
                 let args = self.parse_function_call_args(ctx, body, &mut meta)?;
 
                 ctx.hir_exprs.append(HirExpr {
-                    kind: HirExprKind::Call(FunctionCall {
-                        kind: FunctionCallKind::TypeConstructor(handle),
-                        args,
-                    }),
+                    kind: HirExprKind::Call(FunctionCall { kind, args }),
                     meta,
                 })
             }
@@ -1189,7 +1606,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Plus | TokenValue::Dash | TokenValue::Bang | TokenValue::Tilde => {
                 let Token { value, meta } = self.bump()?;
 
+                self.enter()?;
                 let expr = self.parse_unary(ctx, body)?;
+                self.leave();
                 let end_meta = ctx.hir_exprs[expr].meta;
 
                 let kind = match value {
@@ -1212,7 +1631,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Increment | TokenValue::Decrement => {
                 let Token { value, meta } = self.bump()?;
 
+                self.enter()?;
                 let expr = self.parse_unary(ctx, body)?;
+                self.leave();
 
                 ctx.hir_exprs.append(HirExpr {
                     kind: HirExprKind::IncDec {
@@ -1316,10 +1737,11 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
     }
 
     fn parse_assignment(&mut self, ctx: &mut Context, body: &mut Block) -> Result<Handle<HirExpr>> {
+        self.enter()?;
         let tgt = self.parse_unary(ctx, body)?;
         let start_meta = ctx.hir_exprs[tgt].meta;
 
-        Ok(match self.expect_peek()?.value {
+        let expr = match self.expect_peek()?.value {
             TokenValue::Assign => {
                 self.bump()?;
                 let value = self.parse_assignment(ctx, body)?;
@@ -1371,7 +1793,10 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 })
             }
             _ => self.parse_conditional(ctx, body, Some(tgt))?,
-        })
+        };
+
+        self.leave();
+        Ok(expr)
     }
 
     fn parse_expression(&mut self, ctx: &mut Context, body: &mut Block) -> Result<Handle<HirExpr>> {
@@ -1379,13 +1804,25 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
 
         while let TokenValue::Comma = self.expect_peek()?.value {
             self.bump()?;
-            expr = self.parse_assignment(ctx, body)?;
+            let right = self.parse_assignment(ctx, body)?;
+            let meta = ctx.hir_exprs[expr].meta.union(&ctx.hir_exprs[right].meta);
+
+            expr = ctx.hir_exprs.append(HirExpr {
+                kind: HirExprKind::Sequence { left: expr, right },
+                meta,
+            });
         }
 
         Ok(expr)
     }
 
     fn parse_statement(&mut self, ctx: &mut Context, body: &mut Block) -> Result<()> {
+        // Emit what the previous statement left pending (like expression
+        // statements without side effects) so no range spans more than one
+        // statement, keeping the live ranges of the expressions short
+        ctx.emit_flush(body);
+        ctx.emit_start();
+
         // TODO: This prevents snippets like the following from working
         // ```glsl
         // vec4(1.0);
@@ -1393,11 +1830,16 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
         // But this would require us to add lookahead to also support
         // declarations and since this statement is very unlikely and most
         // likely an error, for now we don't support it
-        if self.peek_type_name() || self.peek_type_qualifier() {
+        if self.peek_type_name()
+            || self.peek_type_qualifier()
+            || self.lexer.peek().map(|t| &t.value) == Some(&TokenValue::Precision)
+        {
             self.parse_declaration(ctx, body, false)?;
             return Ok(());
         }
 
+        self.enter()?;
+
         match self.expect_peek()?.value {
             TokenValue::Continue => {
                 self.bump()?;
@@ -1410,16 +1852,32 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 self.expect(TokenValue::Semicolon)?;
             }
             TokenValue::Return => {
-                self.bump()?;
+                let mut meta = self.bump()?.meta;
                 let value = match self.expect_peek()?.value {
                     TokenValue::Semicolon => {
-                        self.bump()?;
+                        meta = meta.union(&self.bump()?.meta);
+
+                        if ctx.result.is_some() {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                "Non-void function must return a value".into(),
+                            ));
+                        }
+
                         None
                     }
                     _ => {
                         // TODO: Implicit conversions
                         let expr = self.parse_expression(ctx, body)?;
-                        self.expect(TokenValue::Semicolon)?;
+                        meta = meta.union(&self.expect(TokenValue::Semicolon)?.meta);
+
+                        if ctx.result.is_none() {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                "Void function cannot return a value".into(),
+                            ));
+                        }
+
                         Some(ctx.lower_expect(self.program, expr, false, body)?.0)
                     }
                 };
@@ -1465,10 +1923,35 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 self.bump()?;
 
                 self.expect(TokenValue::LeftParen)?;
-                // TODO: Implicit conversions
                 let selector = {
                     let expr = self.parse_expression(ctx, body)?;
-                    ctx.lower_expect(self.program, expr, false, body)?.0
+                    let (selector, meta) = ctx.lower_expect(self.program, expr, false, body)?;
+
+                    match *self.program.resolve_type(ctx, selector, meta)? {
+                        TypeInner::Scalar {
+                            kind: ScalarKind::Sint,
+                            ..
+                        } => selector,
+                        // The IR only has signed selectors, the case values are
+                        // already reinterpreted the same way
+                        TypeInner::Scalar {
+                            kind: ScalarKind::Uint,
+                            ..
+                        } => ctx.add_expression(
+                            Expression::As {
+                                expr: selector,
+                                kind: ScalarKind::Sint,
+                                convert: None,
+                            },
+                            body,
+                        ),
+                        _ => {
+                            return Err(ErrorKind::SemanticError(
+                                meta,
+                                "Switch selector must be an integer".into(),
+                            ))
+                        }
+                    }
                 };
                 self.expect(TokenValue::RightParen)?;
 
@@ -1565,6 +2048,14 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     }
                 }
 
+                if self.program.fold_constant_switches {
+                    if let Some(block) = self.fold_switch(ctx, selector, &mut cases, &mut default) {
+                        body.push(Statement::Block(block));
+                        self.leave();
+                        return Ok(());
+                    }
+                }
+
                 body.push(Statement::Switch {
                     selector,
                     cases,
@@ -1574,6 +2065,9 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::While => {
                 self.bump()?;
 
+                ctx.emit_flush(body);
+                ctx.emit_start();
+
                 let mut loop_body = Block::new();
 
                 self.expect(TokenValue::LeftParen)?;
@@ -1610,6 +2104,11 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Do => {
                 self.bump()?;
 
+                ctx.emit_flush(body);
+                ctx.emit_start();
+
+                // The body must run once before the condition is ever tested,
+                // so the break check is placed after it
                 let mut loop_body = Block::new();
                 self.parse_statement(ctx, &mut loop_body)?;
 
@@ -1653,7 +2152,8 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                     if self.peek_type_name() || self.peek_type_qualifier() {
                         self.parse_declaration(ctx, body, false)?;
                     } else {
-                        self.parse_expression(ctx, body)?;
+                        let expr = self.parse_expression(ctx, body)?;
+                        ctx.lower(self.program, expr, false, body)?;
                         self.expect(TokenValue::Semicolon)?;
                     }
                 }
@@ -1717,8 +2217,13 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
                 match self.expect_peek()?.value {
                     TokenValue::RightParen => {}
                     _ => {
+                        // The update clause lives in the `continuing` block so
+                        // that it still runs when the body executes `continue`
                         let rest = self.parse_expression(ctx, &mut continuing)?;
                         ctx.lower(self.program, rest, false, &mut continuing)?;
+
+                        ctx.emit_flush(&mut continuing);
+                        ctx.emit_start();
                     }
                 }
 
@@ -1748,6 +2253,8 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             | TokenValue::Dash
             | TokenValue::Bang
             | TokenValue::Tilde
+            | TokenValue::Increment
+            | TokenValue::Decrement
             | TokenValue::LeftParen
             | TokenValue::Identifier(_)
             | TokenValue::TypeName(_)
@@ -1761,12 +2268,97 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
             TokenValue::Semicolon => {
                 self.bump()?;
             }
-            _ => {}
+            // Consume the token, otherwise the enclosing block would keep
+            // trying to parse a statement from it
+            _ => {
+                let token = self.bump()?;
+                return Err(ErrorKind::InvalidToken(
+                    token,
+                    vec![TokenValue::Semicolon.into(), TokenValue::RightBrace.into()],
+                ));
+            }
         }
 
+        self.leave();
         Ok(())
     }
 
+    /// Returns the statements a switch with a constant selector reduces to or
+    /// `None` if it can't be folded
+    ///
+    /// Cases are only folded if their only `break` is the last statement,
+    /// otherwise they either fall through or the `break` would end up
+    /// applying to an enclosing loop.
+    ///
+    /// The expressions of the other cases were already added to the function
+    /// so their emits are kept at the start of the block.
+    fn fold_switch(
+        &self,
+        ctx: &Context,
+        selector: Handle<Expression>,
+        cases: &mut Vec<SwitchCase>,
+        default: &mut Block,
+    ) -> Option<Block> {
+        let mut selector = ctx.get_expression(selector);
+        // Unsigned selectors are cast to signed integers
+        if let Expression::As {
+            expr,
+            convert: None,
+            ..
+        } = *selector
+        {
+            selector = ctx.get_expression(expr);
+        }
+
+        let value = match *selector {
+            Expression::Constant(constant) => match self.program.module.constants[constant].inner {
+                ConstantInner::Scalar {
+                    value: ScalarValue::Sint(value),
+                    ..
+                } => value as i32,
+                ConstantInner::Scalar {
+                    value: ScalarValue::Uint(value),
+                    ..
+                } => value as i32,
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let (index, body) = match cases.iter().position(|case| case.value == value) {
+            Some(index) => (Some(index), &cases[index].body),
+            None => (None, &*default),
+        };
+
+        let len = match body.last() {
+            Some(&Statement::Break) => body.len() - 1,
+            _ if index.is_none() => body.len(),
+            _ => return None,
+        };
+
+        if contains_break(&body[..len]) {
+            return None;
+        }
+
+        let mut emits = Block::new();
+        for (case_index, case) in cases.iter().enumerate() {
+            if Some(case_index) != index && !collect_emits(&case.body, &mut emits) {
+                return None;
+            }
+        }
+        if index.is_some() && !collect_emits(default, &mut emits) {
+            return None;
+        }
+
+        let mut block = match index {
+            Some(index) => cases.swap_remove(index).body,
+            None => mem::take(default),
+        };
+        block.truncate(len);
+        emits.extend(block);
+        Some(emits)
+    }
+
     fn parse_compound_statement(&mut self, ctx: &mut Context, body: &mut Block) -> Result<()> {
         loop {
             if self.bump_if(TokenValue::RightBrace).is_some() {
@@ -1788,7 +2380,7 @@ impl<'source, 'program, 'options> Parser<'source, 'program, 'options> {
     ) -> Result<()> {
         loop {
             if self.peek_type_name() || self.peek_parameter_qualifier() {
-                let qualifier = self.parse_parameter_qualifier();
+                let qualifier = self.parse_parameter_qualifier()?;
                 qualifiers.push(qualifier);
                 let ty = self.parse_type_non_void()?.0;
 
@@ -1881,6 +2473,53 @@ impl<'ctx, 'fun> DeclarationContext<'ctx, 'fun> {
     }
 }
 
+/// Checks if any statement breaks out of the enclosing switch or loop
+fn contains_break(block: &[Statement]) -> bool {
+    block.iter().any(|stmt| match *stmt {
+        Statement::Break => true,
+        Statement::Block(ref block) => contains_break(block),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => contains_break(accept) || contains_break(reject),
+        _ => false,
+    })
+}
+
+/// Collects the emits of a block that is dropped, returns `false` if it has
+/// call results since those can only be produced by the call itself
+fn collect_emits(block: &[Statement], emits: &mut Block) -> bool {
+    block.iter().all(|stmt| match *stmt {
+        Statement::Emit(ref range) => {
+            emits.push(Statement::Emit(range.clone()));
+            true
+        }
+        Statement::Call {
+            result: Some(_), ..
+        } => false,
+        Statement::Block(ref block) => collect_emits(block, emits),
+        Statement::If {
+            ref accept,
+            ref reject,
+            ..
+        } => collect_emits(accept, emits) && collect_emits(reject, emits),
+        Statement::Switch {
+            ref cases,
+            ref default,
+            ..
+        } => {
+            cases.iter().all(|case| collect_emits(&case.body, emits))
+                && collect_emits(default, emits)
+        }
+        Statement::Loop {
+            ref body,
+            ref continuing,
+        } => collect_emits(body, emits) && collect_emits(continuing, emits),
+        _ => true,
+    })
+}
+
 fn binding_power(value: &TokenValue) -> Option<(u8, u8)> {
     Some(match *value {
         TokenValue::LogicalOr => (1, 2),
@@ -2,12 +2,15 @@ use pp_rs::token::PreprocessorError;
 
 use super::lex::Lexer;
 use super::parser;
-use super::{ast::Profile, error::ErrorKind};
+use super::{
+    ast::Profile,
+    error::{ErrorKind, Warning},
+};
 use super::{ast::Program, SourceMetadata};
 use crate::front::glsl::error::ExpectedToken;
 use crate::{
     front::glsl::{token::TokenValue, Token},
-    ShaderStage,
+    ScalarKind, ShaderStage,
 };
 
 fn parse_program<'a>(
@@ -47,6 +50,20 @@ fn version() {
         ErrorKind::InvalidProfile(SourceMetadata { start: 13, end: 18 }, "smart".into())
     );
 
+    assert_eq!(
+        parse_program("#version 450 es", &entry_points)
+            .err()
+            .unwrap(),
+        ErrorKind::InvalidProfile(SourceMetadata { start: 13, end: 15 }, "es".into())
+    );
+
+    assert_eq!(
+        parse_program("#version 300\nvoid main() {}", &entry_points)
+            .err()
+            .unwrap(),
+        ErrorKind::InvalidVersion(SourceMetadata { start: 9, end: 12 }, 300)
+    );
+
     assert_eq!(
         parse_program("#version 450\nvoid f(){} #version 450", &entry_points)
             .err()
@@ -69,6 +86,142 @@ fn version() {
 
     let program = parse_program("#version 450 core\nvoid main() {}", &entry_points).unwrap();
     assert_eq!((program.version, program.profile), (450, Profile::Core));
+
+    let program =
+        parse_program("#version 460 compatibility\nvoid main() {}", &entry_points).unwrap();
+    assert_eq!(
+        (program.version, program.profile),
+        (460, Profile::Compatibility)
+    );
+
+    let program = parse_program("#version 310 es\nvoid main() {}", &entry_points).unwrap();
+    assert_eq!((program.version, program.profile), (310, Profile::Es));
+}
+
+#[test]
+fn error_reporting() {
+    let mut options = super::Options::default();
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Vertex);
+
+    let source = "#version 450\nvoid main() {\n    float a = b;\n}\n";
+    let error = super::parse_str(source, &options).err().unwrap();
+
+    assert_eq!(error.to_string(), "3:15: Unknown variable: b");
+    assert_eq!(error.span(), Some(41..42));
+    assert_eq!(error.location(source), Some((3, 15)));
+    assert!(error.emit_to_string(source).contains("float a = b;"));
+}
+
+#[test]
+fn line_continuations_and_comments() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        #define SCALE 2.0 * \
+            3.0
+        /* a block comment
+        #define SCALE 1.0
+        spanning a directive */
+        #define OFFSET /* inline */ 1.0
+        void main() {
+            float a = SCALE + OFFSET; // a line comment \
+            that continues on the next line
+            flo\
+at b = a;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+}
+
+#[test]
+fn function_like_macros() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        #define SQR(x) ((x) * (x))
+        #define SUM(a, b) (a + b)
+        #define NESTED(x) SUM(SQR(x), x)
+        void main() {
+            float value = 2.0;
+        #define value value + 1.0
+            float a = SQR(value);
+            float b = NESTED(SUM(1.0, 2.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            #define SQR(x) ((x) * (x))
+            #undef SQR
+            void main() {
+                float a = SQR(2.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 140,
+                end: 148
+            },
+            "Unknown function 'SQR'".into()
+        )
+    );
+}
+
+#[test]
+fn conditional_compilation() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        #define VERSION 3
+        #define FEATURE
+        #if VERSION >= 3 && defined(FEATURE)
+            #ifdef MISSING
+                this is not parsed
+            #elif 2 * VERSION == 6
+                #ifndef FEATURE
+                    nor is this
+                #else
+                    float value() { return 1.0; }
+                #endif
+            #else
+                nor this
+            #endif
+        #elif defined FEATURE
+            nor this
+        #else
+            nor this
+        #endif
+        #if !defined(MISSING) || VERSION < 2
+        void main() {
+            float a = value();
+        }
+        #endif
+        "#,
+        &entry_points,
+    )
+    .unwrap();
 }
 
 #[test]
@@ -81,9 +234,9 @@ fn control_flow() {
         #  version 450
         void main() {
             if (true) {
-                return 1;
+                return;
             } else {
-                return 2;
+                return;
             }
         }
         "#,
@@ -96,7 +249,7 @@ fn control_flow() {
         #  version 450
         void main() {
             if (true) {
-                return 1;
+                return;
             }
         }
         "#,
@@ -151,7 +304,7 @@ fn control_flow() {
                 x = x + 2;
             }
             for(;;);
-            return x;
+            return;
         }
         "#,
         &entry_points,
@@ -159,6 +312,92 @@ fn control_flow() {
     .unwrap();
 }
 
+#[test]
+fn switch_selectors() {
+    use crate::Statement;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let source = |selector: &str| {
+        format!(
+            r#"
+            #version 450
+            void main() {{
+                int a = 0;
+                for (int i = 0; i < 4; i++) {{
+                    switch ({}) {{
+                        case 0:
+                            a = 1;
+                            break;
+                        case 1:
+                            if (a == 1) {{
+                                break;
+                            }}
+                            a = 2;
+                            break;
+                        default:
+                            a = 3;
+                    }}
+                }}
+            }}
+            "#,
+            selector
+        )
+    };
+
+    parse_program(&source("1u"), &entry_points).unwrap();
+
+    assert_eq!(
+        parse_program(&source("1.0"), &entry_points).err().unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 153,
+                end: 156
+            },
+            "Switch selector must be an integer".into()
+        )
+    );
+
+    fn has_switch(block: &[Statement]) -> bool {
+        block.iter().any(|stmt| match *stmt {
+            Statement::Switch { .. } => true,
+            Statement::Block(ref block)
+            | Statement::Loop {
+                body: ref block, ..
+            } => has_switch(block),
+            _ => false,
+        })
+    }
+
+    // Checks if the switch is still there after folding
+    let folded = |selector: &str| {
+        let mut program = Program::new(&entry_points);
+        program.fold_constant_switches = true;
+        let defines = crate::FastHashMap::default();
+        let source = source(selector);
+        let lex = Lexer::new(&source, &defines);
+        parser::Parser::new(&mut program, lex).parse().unwrap();
+
+        crate::valid::Validator::new(
+            crate::valid::ValidationFlags::all(),
+            crate::valid::Capabilities::all(),
+        )
+        .validate(&program.module)
+        .unwrap();
+
+        let main = &program.module.functions.iter().next().unwrap().1;
+        !has_switch(&main.body)
+    };
+
+    assert!(folded("0"));
+    assert!(folded("5"));
+    assert!(folded("0u"));
+    assert!(!folded("a"));
+    // The nested break can't be removed
+    assert!(!folded("1"));
+}
+
 #[test]
 fn declarations() {
     let mut entry_points = crate::FastHashMap::default();
@@ -255,6 +494,81 @@ fn declarations() {
         &entry_points,
     )
     .unwrap();
+
+    let _program = parse_program(
+        r#"
+        #version 300 es
+        precision mediump float;
+        precision lowp int;
+        precision highp texture2D;
+        precision highp sampler;
+        void main() {
+            precision highp float;
+            float a = 1.0;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            precision highp vec4;
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 54, end: 58 },
+            "Precision statement can only work on floats, ints and opaque types".into()
+        )
+    );
+}
+
+#[test]
+fn precision_qualifiers() {
+    use crate::TypeInner;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 300 es
+        highp float f(const in highp float a, out mediump vec2 b, lowp int c) {
+            b = vec2(a);
+            return a;
+        }
+        void main() {
+            mediump vec2 b;
+            highp float a = f(1.0, b, 1);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, function) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("f"))
+        .unwrap();
+    let pointers: Vec<_> = function
+        .arguments
+        .iter()
+        .map(|arg| {
+            matches!(
+                program.module.types[arg.ty].inner,
+                TypeInner::Pointer { .. }
+            )
+        })
+        .collect();
+    // Only the `out` parameter is passed by reference
+    assert_eq!(pointers, [false, true, false]);
 }
 
 #[test]
@@ -280,396 +594,5874 @@ fn textures() {
 }
 
 #[test]
-fn functions() {
+fn texture_offset() {
+    use crate::{ConstantInner, Expression, ScalarValue};
+
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Vertex);
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
 
-    parse_program(
+    let program = parse_program(
         r#"
-        #  version 450
-        void test1(float);
-        void test1(float) {}
-
-        void main() {}
+        #version 450
+        layout(location = 0) in vec2 v_uv;
+        layout(location = 0) out vec4 o_color;
+        layout(set = 1, binding = 1) uniform texture2D tex;
+        layout(set = 1, binding = 2) uniform sampler tex_sampler;
+        void main() {
+            o_color = textureOffset(sampler2D(tex, tex_sampler), v_uv, ivec2(1, -1));
+        }
         "#,
         &entry_points,
     )
     .unwrap();
 
-    parse_program(
-        r#"
-        #  version 450
-        void test2(float a) {}
-        void test3(float a, float b) {}
-        void test4(float, float) {}
+    let (_, function) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+    let offset = function
+        .expressions
+        .iter()
+        .find_map(|(_, e)| match *e {
+            Expression::ImageSample { offset, .. } => offset,
+            _ => None,
+        })
+        .unwrap();
 
-        void main() {}
-        "#,
-        &entry_points,
-    )
-    .unwrap();
+    let components: Vec<_> = match program.module.constants[offset].inner {
+        ConstantInner::Composite { ref components, .. } => components
+            .iter()
+            .map(|&c| program.module.constants[c].inner.clone())
+            .collect(),
+        _ => Vec::new(),
+    };
+    assert_eq!(
+        components,
+        [
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Sint(1)
+            },
+            ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Sint(-1)
+            },
+        ]
+    );
 
-    parse_program(
-        r#"
-        #  version 450
-        float test(float a) { return a; }
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 o_color;
+            layout(set = 1, binding = 1) uniform texture2D tex;
+            layout(set = 1, binding = 2) uniform sampler tex_sampler;
+            void main() {
+                ivec2 offset = ivec2(1, -1);
+                o_color = textureOffset(sampler2D(tex, tex_sampler), v_uv, offset);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 404,
+                end: 410
+            },
+            "offset must be a constant expression".into()
+        )
+    );
+}
 
-        void main() {}
-        "#,
-        &entry_points,
-    )
-    .unwrap();
+#[test]
+fn subpass_load() {
+    use crate::{Binding, BuiltIn, Expression};
 
-    parse_program(
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
         r#"
-        #  version 450
-        float test(vec4 p) {
-            return p.x;
+        #version 450
+        layout(location = 0) out vec4 o_color;
+        layout(input_attachment_index = 0, set = 0, binding = 0) uniform subpassInput albedo;
+        layout(input_attachment_index = 1, set = 0, binding = 1) uniform subpassInputMS normal;
+        void main() {
+            o_color = subpassLoad(albedo) + subpassLoad(normal, 0);
         }
         "#,
         &entry_points,
     )
     .unwrap();
 
-    // Function overloading
-    parse_program(
-        r#"
-        #  version 450
-        float test(vec2 p);
-        float test(vec3 p);
-        float test(vec4 p);
+    let (_, function) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+    let loads = function
+        .expressions
+        .iter()
+        .filter(|&(_, e)| matches!(*e, Expression::ImageLoad { .. }))
+        .count();
+    assert_eq!(loads, 2);
 
-        float test(vec2 p) {
-            return p.x;
-        }
+    // The fragment position is only read once
+    let bindings: Vec<_> = program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect();
+    assert_eq!(bindings, [Some(Binding::BuiltIn(BuiltIn::Position))]);
+}
 
-        float test(vec3 p) {
-            return p.x;
-        }
+#[test]
+fn integer_min_max_clamp() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
 
-        float test(vec4 p) {
-            return p.x;
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            int a = max(1, 2);
+            uint b = min(1u, 2u);
+            uint c = max(1, 2u);
+            ivec3 d = clamp(ivec3(1), 0, 2);
+            uvec2 e = min(uvec2(1u), 2);
         }
         "#,
         &entry_points,
     )
     .unwrap();
 
+    let function = &program.module.functions.iter().next().unwrap().1;
+    let conversions: Vec<_> = function
+        .expressions
+        .iter()
+        .filter_map(|(_, expr)| match *expr {
+            crate::Expression::As { kind, .. } => Some(kind),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(conversions, [ScalarKind::Uint, ScalarKind::Uint]);
+
     assert_eq!(
         parse_program(
             r#"
-                #  version 450
-                int test(vec4 p) {
-                    return p.x;
-                }
-
-                float test(vec4 p) {
-                    return p.x;
-                }
-                "#,
-            &entry_points
+            #version 450
+            void main() {
+                int a = max(1, true);
+            }
+            "#,
+            &entry_points,
         )
         .err()
         .unwrap(),
         ErrorKind::SemanticError(
-            SourceMetadata {
-                start: 134,
-                end: 152
-            },
-            "Function already defined".into()
+            SourceMetadata { start: 83, end: 87 },
+            "\"max\" expects numeric arguments".into()
         )
     );
+}
 
-    println!();
+#[test]
+fn inout_structs() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
 
-    let _program = parse_program(
+    let program = parse_program(
         r#"
-        #  version 450
-        float callee(uint q) {
-            return float(q);
+        #version 450
+        struct Light {
+            vec3 color;
+            float intensity;
+        };
+        struct Scene {
+            Light light;
+        };
+        Scene global_scene;
+        void dim(inout Light light) {
+            light.intensity *= 0.5;
         }
-
-        float caller() {
-            callee(1u);
+        void main() {
+            Light light = Light(vec3(1.0), 1.0);
+            Scene scene = Scene(light);
+            dim(light);
+            dim(scene.light);
+            dim(global_scene.light);
         }
         "#,
         &entry_points,
     )
     .unwrap();
 
-    // Nested function call
-    let _program = parse_program(
-        r#"
-            #  version 450
-            layout(set = 0, binding = 1) uniform texture2D t_noise;
-            layout(set = 0, binding = 2) uniform sampler s_noise;
+    let main = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, function)| function.name.as_deref() == Some("main"))
+        .unwrap()
+        .1;
 
-            void main() {
-                textureLod(sampler2D(t_noise, s_noise), vec2(1.0), 0);
-            }
-        "#,
-        &entry_points,
-    )
-    .unwrap();
+    // The members are copied to temporaries and written back after the calls
+    let write_backs = main
+        .body
+        .iter()
+        .filter(|stmt| match **stmt {
+            crate::Statement::Store { pointer, .. } => matches!(
+                main.expressions[pointer],
+                crate::Expression::AccessIndex { .. }
+            ),
+            _ => false,
+        })
+        .count();
+    assert_eq!(write_backs, 2);
+}
 
-    parse_program(
+#[test]
+fn array_parameters_by_value() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
         r#"
-        #  version 450
-        void fun(vec2 in_parameter, out float out_parameter) {
-            ivec2 _ = ivec2(in_parameter);
+        #version 450
+        float mutate(float values[2]) {
+            values[0] = 2.0;
+            return values[0];
         }
-
         void main() {
-            float a;
-            fun(vec2(1.0), a);
+            float values[2];
+            values[0] = 1.0;
+            float a = mutate(values);
+            float b = values[0];
         }
         "#,
         &entry_points,
     )
     .unwrap();
+
+    let mut functions = program.module.functions.iter().map(|(_, f)| f);
+    let mutate = functions.next().unwrap();
+    let main = functions.next().unwrap();
+
+    // The caller passes a loaded copy of its array
+    let argument = main
+        .body
+        .iter()
+        .find_map(|stmt| match *stmt {
+            crate::Statement::Call { ref arguments, .. } => Some(arguments[0]),
+            _ => None,
+        })
+        .unwrap();
+    assert!(matches!(
+        main.expressions[argument],
+        crate::Expression::Load { .. }
+    ));
+
+    // And the callee copies it into a local before mutating it
+    assert_eq!(mutate.local_variables.len(), 1);
+    match mutate.body[0] {
+        crate::Statement::Store { pointer, value } => {
+            assert!(matches!(
+                mutate.expressions[pointer],
+                crate::Expression::LocalVariable(_)
+            ));
+            assert!(matches!(
+                mutate.expressions[value],
+                crate::Expression::FunctionArgument(0)
+            ));
+        }
+        _ => unreachable!(),
+    }
 }
 
 #[test]
-fn constants() {
-    use crate::{Constant, ConstantInner, ScalarValue};
-
+fn entry_arg_use_through_pointers() {
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Vertex);
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
 
     let program = parse_program(
         r#"
-        #  version 450
-        const float a = 1.0;
-        float global = a;
-        const float b = a;
+        #version 450
+        layout(location = 0) out vec4 o_color;
+        layout(location = 1) out vec4 o_normal;
+        layout(location = 2) out float o_depth;
+        void write(out vec4 color) {
+            color = vec4(1.0);
+        }
+        void scale(inout vec4 normal) {
+            normal *= 2.0;
+        }
+        layout(location = 3) out float o_alpha;
+        void forward(out float depth);
+        void first();
+        void second();
+        void third();
+        void main() {
+            write(o_color);
+            scale(o_normal);
+            forward(o_depth);
+            first();
+        }
+        void forward(out float depth) {
+            depth = 0.5;
+        }
+        void first() { second(); }
+        void second() { third(); }
+        void third() { o_alpha = 1.0; }
         "#,
         &entry_points,
     )
     .unwrap();
 
-    let mut constants = program.module.constants.iter();
-
-    assert_eq!(
-        constants.next().unwrap().1,
-        &Constant {
-            name: None,
-            specialization: None,
-            inner: ConstantInner::Scalar {
-                width: 4,
-                value: ScalarValue::Float(1.0)
-            }
-        }
-    );
-
-    assert!(constants.next().is_none());
+    let entry = &program.module.entry_points[0].function;
+    let outputs = entry
+        .result
+        .as_ref()
+        .map_or(0, |r| match program.module.types[r.ty].inner {
+            crate::TypeInner::Struct { ref members, .. } => members.len(),
+            _ => 0,
+        });
+    assert_eq!(outputs, 4);
 }
 
 #[test]
-fn function_overloading() {
+fn keep_unused_entry_args() {
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Vertex);
-
-    parse_program(
-        r#"
-        #  version 450
-
-        float saturate(float v) { return clamp(v, 0.0, 1.0); }
-        vec2 saturate(vec2 v) { return clamp(v, vec2(0.0), vec2(1.0)); }
-        vec3 saturate(vec3 v) { return clamp(v, vec3(0.0), vec3(1.0)); }
-        vec4 saturate(vec4 v) { return clamp(v, vec4(0.0), vec4(1.0)); }
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
 
+    let source = r#"
+        #version 450
+        layout(location = 0) in vec2 v_uv;
+        layout(location = 1) in vec4 v_color;
+        layout(location = 0) out vec4 o_color;
+        layout(location = 1) out vec4 o_normal;
         void main() {
-            float v1 = saturate(1.5);
-            vec2 v2 = saturate(vec2(0.5, 1.5));
-            vec3 v3 = saturate(vec3(0.5, 1.5, 2.5));
-            vec3 v4 = saturate(vec4(0.5, 1.5, 2.5, 3.5));
+            o_color = vec4(v_uv, 0.0, 1.0);
         }
-        "#,
-        &entry_points,
-    )
-    .unwrap();
+        "#;
+
+    let interface = |program: &Program| {
+        let entry = &program.module.entry_points[0].function;
+        let outputs = entry
+            .result
+            .as_ref()
+            .map_or(0, |r| match program.module.types[r.ty].inner {
+                crate::TypeInner::Struct { ref members, .. } => members.len(),
+                _ => 0,
+            });
+        (entry.arguments.len(), outputs)
+    };
+
+    let program = parse_program(source, &entry_points).unwrap();
+    assert_eq!(interface(&program), (1, 1));
+
+    let mut program = Program::new(&entry_points);
+    program.keep_unused_entry_args = true;
+    let defines = crate::FastHashMap::default();
+    let lex = Lexer::new(source, &defines);
+    parser::Parser::new(&mut program, lex).parse().unwrap();
+    assert_eq!(interface(&program), (2, 2));
 }
 
 #[test]
-fn implicit_conversions() {
+fn vector_constructor_component_count() {
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Vertex);
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
 
-    parse_program(
+    let program = parse_program(
         r#"
-        #  version 450
+        #version 450
         void main() {
-            mat4 a = mat4(1);
-            float b = 1u;
-            float c = 1 + 2.0;
+            vec4 a = vec4(1.0, vec2(2.0), 3.0);
+            vec3 b = vec3(vec2(1.0), 2.0);
+            vec2 c = vec2(1.0);
+            // The last argument is truncated
+            vec3 d = vec3(vec2(1.0), vec2(2.0));
+            vec2 e = vec2(1.0, vec3(2.0));
+            vec3 f = vec3(1.0, mat2(2.0));
         }
         "#,
         &entry_points,
     )
     .unwrap();
 
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
     assert_eq!(
         parse_program(
             r#"
-                #  version 450
-                void test(int a) {}
-                void test(uint a) {}
-
-                void main() {
-                    test(1.0);
-                }
-                "#,
-            &entry_points
+            #version 450
+            void main() {
+                vec3 a = vec3(1.0, 2.0);
+            }
+            "#,
+            &entry_points,
         )
         .err()
         .unwrap(),
         ErrorKind::SemanticError(
-            SourceMetadata {
-                start: 156,
-                end: 165
-            },
-            "Unknown function \'test\'".into()
+            SourceMetadata { start: 77, end: 91 },
+            "Vector constructor expects 3 components but got 2".into()
         )
     );
 
     assert_eq!(
         parse_program(
             r#"
-                #  version 450
-                void test(float a) {}
-                void test(uint a) {}
-
-                void main() {
-                    test(1);
-                }
-                "#,
-            &entry_points
+            #version 450
+            void main() {
+                vec2 a = vec2(1.0, 2.0, 3.0);
+            }
+            "#,
+            &entry_points,
         )
         .err()
         .unwrap(),
         ErrorKind::SemanticError(
-            SourceMetadata {
-                start: 158,
-                end: 165
-            },
-            "Ambiguous best function for \'test\'".into()
+            SourceMetadata { start: 77, end: 96 },
+            "Vector constructor expects 2 components but got 3".into()
         )
     );
 }
 
 #[test]
-fn structs() {
+fn bool_conversions() {
+    use crate::{BinaryOperator, Expression};
+
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Fragment);
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
 
-    parse_program(
+    let program = parse_program(
         r#"
-        #  version 450
-        Test {
-            vec4 pos;
-          } xx;
+        #version 450
+        void main() {
+            bool b = true;
+            int a = int(b);
+            float c = float(b);
+            bool d = bool(2);
+            bvec2 e = bvec2(vec2(1.0));
+            vec3 f = vec3(bvec3(b));
+            uvec2 g = uvec2(true);
+        }
         "#,
         &entry_points,
     )
-    .unwrap_err();
+    .unwrap();
 
-    parse_program(
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let count = |f: &dyn Fn(&Expression) -> bool| {
+        main.expressions.iter().filter(|&(_, expr)| f(expr)).count()
+    };
+    assert_eq!(count(&|expr| matches!(*expr, Expression::As { .. })), 0);
+    assert_eq!(count(&|expr| matches!(*expr, Expression::Select { .. })), 4);
+    assert_eq!(
+        count(&|expr| matches!(
+            *expr,
+            Expression::Binary {
+                op: BinaryOperator::NotEqual,
+                ..
+            }
+        )),
+        2
+    );
+
+    // Folding the comparison registers the boolean vector type
+    let program = parse_program(
         r#"
-        #  version 450
-        struct Test {
-            vec4 pos;
-        };
+        #version 450
+        const bvec2 b = bvec2(ivec2(2, 0));
+        void main() {}
         "#,
         &entry_points,
     )
     .unwrap();
 
-    parse_program(
-        r#"
-        #  version 450
-        const int NUM_VECS = 42;
-        struct Test {
-            vec4 vecs[NUM_VECS];
-        };
-        "#,
-        &entry_points,
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
     )
+    .validate(&program.module)
     .unwrap();
 
-    parse_program(
+    let components: Vec<_> = program
+        .module
+        .constants
+        .iter()
+        .find_map(|(_, c)| match c.inner {
+            crate::ConstantInner::Composite { ty, ref components }
+                if program.module.types[ty].inner.scalar_kind() == Some(ScalarKind::Bool) =>
+            {
+                Some(
+                    components
+                        .iter()
+                        .map(|&c| program.module.constants[c].inner.clone())
+                        .collect(),
+                )
+            }
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(
+        components,
+        [
+            crate::ConstantInner::Scalar {
+                width: crate::BOOL_WIDTH,
+                value: crate::ScalarValue::Bool(true)
+            },
+            crate::ConstantInner::Scalar {
+                width: crate::BOOL_WIDTH,
+                value: crate::ScalarValue::Bool(false)
+            },
+        ]
+    );
+
+    // The selects of conversions from booleans are folded too
+    let program = parse_program(
         r#"
-        #  version 450
-        struct Hello {
-            vec4 test;
-        } test() {
-            return Hello( vec4(1.0) );
-        }
+        #version 450
+        const float f = float(true);
+        const vec2 v = vec2(bvec2(true, false));
+        void main() {}
         "#,
         &entry_points,
     )
     .unwrap();
 
-    parse_program(
-        r#"
-        #  version 450
-        struct Test {};
-        "#,
-        &entry_points,
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
     )
-    .unwrap_err();
+    .validate(&program.module)
+    .unwrap();
 
-    parse_program(
-        r#"
-        #  version 450
-        inout struct Test {
-            vec4 x;
-        };
-        "#,
-        &entry_points,
-    )
-    .unwrap_err();
+    let constant = |name: &str| {
+        let handle = program
+            .global_variables
+            .iter()
+            .find_map(|global| match global.1.kind {
+                super::ast::GlobalLookupKind::Constant(handle) if global.0 == name => Some(handle),
+                _ => None,
+            })
+            .unwrap();
+        &program.module.constants[handle].inner
+    };
+    let float = |value| crate::ConstantInner::Scalar {
+        width: 4,
+        value: crate::ScalarValue::Float(value),
+    };
+    assert_eq!(*constant("f"), float(1.0));
+    let components = match *constant("v") {
+        crate::ConstantInner::Composite { ref components, .. } => components
+            .iter()
+            .map(|&c| program.module.constants[c].inner.clone())
+            .collect(),
+        crate::ConstantInner::Scalar { .. } => Vec::new(),
+    };
+    assert_eq!(components, [float(1.0), float(0.0)]);
 }
 
 #[test]
-fn swizzles() {
+fn vector_from_matrix() {
     let mut entry_points = crate::FastHashMap::default();
-    entry_points.insert("".to_string(), ShaderStage::Fragment);
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
 
     parse_program(
         r#"
-        #  version 450
+        #version 450
         void main() {
-            vec4 v = vec4(1);
-            v.xyz = vec3(2);
-            v.x = 5.0;
-            v.xyz.zxy.yx.xy = vec2(5.0, 1.0);
+            mat2 m = mat2(1.0);
+            mat2x3 n = mat2x3(1.0);
+            vec4 a = vec4(m);
+            vec3 b = vec3(n);
+            ivec2 c = ivec2(m);
+            vec4 d = vec4(1.0, 2.0, vec2(3.0));
+            vec4 e = vec4(m) + vec4(mat2(2.0));
         }
         "#,
         &entry_points,
     )
     .unwrap();
 
-    parse_program(
-        r#"
-        #  version 450
-        void main() {
-            vec4 v = vec4(1);
-            v.xx = vec2(5.0);
-        }
-        "#,
-        &entry_points,
-    )
-    .unwrap_err();
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                mat2 m = mat2(1.0);
+                vec4 a = vec4(m, 1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 113,
+                end: 125
+            },
+            "Vector constructor expects 4 components but got 5".into()
+        )
+    );
+}
 
-    parse_program(
-        r#"
-        #  version 450
-        void main() {
-            vec3 v = vec3(1);
-            v.w = 2.0;
-        }
-        "#,
-        &entry_points,
-    )
-    .unwrap_err();
+#[test]
+fn texture_gather_offsets() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let source = |call: &str| {
+        format!(
+            r#"
+            #version 450
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 o_color;
+            layout(set = 1, binding = 1) uniform texture2D tex;
+            layout(set = 1, binding = 2) uniform sampler tex_sampler;
+            void main() {{
+                o_color = {};
+            }}
+            "#,
+            call
+        )
+    };
+
+    assert_eq!(
+        parse_program(
+            &source("textureGatherOffsets(sampler2D(tex, tex_sampler), v_uv, ivec2(0))"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 366,
+                end: 374
+            },
+            "textureGatherOffsets expects an array of four offsets".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            &source("textureGatherOffset(sampler2D(tex, tex_sampler), v_uv, ivec2(1))"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 310,
+                end: 374
+            },
+            "texture gathering"
+        )
+    );
+}
+
+#[test]
+fn texture_coordinate_mismatch() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Fragment);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 1, binding = 1) uniform texture2D tex;
+            layout(set = 1, binding = 2) uniform sampler tex_sampler;
+            void main() {
+                vec4 color = texture(sampler2D(tex, tex_sampler), vec3(0.0));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 252,
+                end: 261
+            },
+            "Image coordinate must have 2 components, found 3".into()
+        )
+    );
+
+    parse_program(
+        r#"
+        #version 450
+        layout(set = 1, binding = 1) uniform texture2DArray tex;
+        layout(set = 1, binding = 2) uniform sampler tex_sampler;
+        void main() {
+            vec4 color = texture(sampler2DArray(tex, tex_sampler), vec2(0.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn cube_textures() {
+    use crate::{Expression, VectorSize};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in vec4 v_dir;
+        layout(location = 0) out vec4 o_color;
+        layout(set = 1, binding = 0) uniform textureCube tex_cube;
+        layout(set = 1, binding = 1) uniform textureCubeArray tex_cube_array;
+        layout(set = 1, binding = 2) uniform sampler tex_sampler;
+        void main() {
+            o_color = texture(samplerCube(tex_cube, tex_sampler), v_dir.xyz);
+            o_color += texture(samplerCubeArray(tex_cube_array, tex_sampler), v_dir, 1.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, function) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+
+    let samples: Vec<_> = function
+        .expressions
+        .iter()
+        .filter_map(|(_, e)| match *e {
+            Expression::ImageSample {
+                coordinate,
+                array_index,
+                ..
+            } => Some((coordinate, array_index)),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(samples.len(), 2);
+
+    // Cube maps use the whole coordinate as the direction
+    let (coordinate, array_index) = samples[0];
+    assert!(array_index.is_none());
+    assert!(matches!(
+        function.expressions[coordinate],
+        Expression::Swizzle {
+            size: VectorSize::Tri,
+            ..
+        }
+    ));
+
+    // Cube arrays keep the first three components and use the fourth as the layer
+    let (coordinate, array_index) = samples[1];
+    assert!(matches!(
+        function.expressions[coordinate],
+        Expression::Swizzle {
+            size: VectorSize::Tri,
+            ..
+        }
+    ));
+    let layer = match function.expressions[array_index.unwrap()] {
+        Expression::As {
+            expr,
+            kind: ScalarKind::Sint,
+            ..
+        } => Some(expr),
+        _ => None,
+    };
+    assert!(matches!(
+        function.expressions[layer.unwrap()],
+        Expression::AccessIndex { index: 3, .. }
+    ));
+}
+
+#[test]
+fn functions() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #  version 450
+        void test1(float);
+        void test1(float) {}
+
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        void test2(float a) {}
+        void test3(float a, float b) {}
+        void test4(float, float) {}
+
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        float test(float a) { return a; }
+
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        float test(vec4 p) {
+            return p.x;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    // Function overloading
+    parse_program(
+        r#"
+        #  version 450
+        float test(vec2 p);
+        float test(vec3 p);
+        float test(vec4 p);
+
+        float test(vec2 p) {
+            return p.x;
+        }
+
+        float test(vec3 p) {
+            return p.x;
+        }
+
+        float test(vec4 p) {
+            return p.x;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+                #  version 450
+                int test(vec4 p) {
+                    return p.x;
+                }
+
+                float test(vec4 p) {
+                    return p.x;
+                }
+                "#,
+            &entry_points
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 134,
+                end: 152
+            },
+            "Function already defined".into()
+        )
+    );
+
+    println!();
+
+    let _program = parse_program(
+        r#"
+        #  version 450
+        float callee(uint q) {
+            return float(q);
+        }
+
+        float caller() {
+            callee(1u);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    // Nested function call
+    let _program = parse_program(
+        r#"
+            #  version 450
+            layout(set = 0, binding = 1) uniform texture2D t_noise;
+            layout(set = 0, binding = 2) uniform sampler s_noise;
+
+            void main() {
+                textureLod(sampler2D(t_noise, s_noise), vec2(1.0), 0);
+            }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        void fun(vec2 in_parameter, out float out_parameter) {
+            ivec2 _ = ivec2(in_parameter);
+        }
+
+        void main() {
+            float a;
+            fun(vec2(1.0), a);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+}
+
+#[test]
+fn matrix_multiplication() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            mat4x3 a = mat4x3(1.0);
+            mat2x4 b = mat2x4(1.0);
+            vec3 v = a * vec4(1.0);
+            vec4 w = vec3(1.0) * a;
+            mat2x3 c = a * b;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                vec3 v = mat3(1.0) * vec4(1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 79,
+                end: 100
+            },
+            "Cannot multiply operands with inner dimensions 3 and 4".into()
+        )
+    );
+}
+
+#[test]
+fn non_square_transpose() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        void main() {
+            mat2x3 m = mat2x3(1.0);
+            mat3x2 t = transpose(m);
+            vec2 a = t * vec3(1.0);
+            vec3 b = vec2(1.0) * t;
+            mat3 c = m * transpose(m);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                mat2x3 m = mat2x3(1.0);
+                vec2 a = transpose(m) * vec2(1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 117,
+                end: 141
+            },
+            "Cannot multiply operands with inner dimensions 3 and 2".into()
+        )
+    );
+}
+
+#[test]
+fn non_square_outer_product() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        void main() {
+            mat2x3 m = outerProduct(vec3(1.0), vec2(2.0));
+            vec3 a = outerProduct(vec3(1.0), vec2(2.0)) * vec2(1.0);
+            vec2 b = vec3(1.0) * outerProduct(vec3(1.0), vec2(2.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec3 a = outerProduct(vec3(1.0), vec2(2.0)) * vec3(1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 77,
+                end: 123
+            },
+            "Cannot multiply operands with inner dimensions 2 and 3".into()
+        )
+    );
+}
+
+#[test]
+fn matrix_scalar_operations() {
+    use crate::{BinaryOperator, Expression};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            mat3 a = 2.0 * mat3(1.0);
+            mat3 b = a / 2.0;
+            mat2x4 c = 1.0 - mat2x4(0.5);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, main) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+    let ops: Vec<_> = main
+        .expressions
+        .iter()
+        .filter_map(|(_, e)| match *e {
+            Expression::Binary { op, .. } => Some(op),
+            _ => None,
+        })
+        .collect();
+
+    // The division and subtraction are done per column
+    assert_eq!(
+        ops,
+        [
+            BinaryOperator::Multiply,
+            BinaryOperator::Divide,
+            BinaryOperator::Divide,
+            BinaryOperator::Divide,
+            BinaryOperator::Subtract,
+            BinaryOperator::Subtract,
+        ]
+    );
+}
+
+#[test]
+fn vertex_builtins() {
+    use crate::{Binding, BuiltIn};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) out uint o_id;
+        void main() {
+            o_id = gl_VertexID + gl_InstanceIndex;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let bindings: Vec<_> = program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect();
+
+    assert_eq!(
+        bindings,
+        [
+            Some(Binding::BuiltIn(BuiltIn::VertexIndex)),
+            Some(Binding::BuiltIn(BuiltIn::InstanceIndex)),
+        ]
+    );
+
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) out vec4 o_color;
+            void main() {
+                o_color = vec4(gl_VertexIndex);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 134,
+                end: 148
+            },
+            "\"gl_VertexIndex\" is only available in vertex shaders".into()
+        )
+    );
+
+    // Having a vertex entry point doesn't allow the other entry points to
+    // read the vertex only builtins
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("vert_main".to_string(), ShaderStage::Vertex);
+    entry_points.insert("frag_main".to_string(), ShaderStage::Fragment);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) out vec4 o_color;
+            float index() { return float(gl_InstanceIndex); }
+            void vert_main() {}
+            void frag_main() {
+                o_color = vec4(index());
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 118,
+                end: 134
+            },
+            "\"gl_InstanceIndex\" is only available in vertex shaders".into()
+        )
+    );
+}
+
+#[test]
+fn layered_rendering() {
+    use crate::{Binding, BuiltIn, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            gl_Position = vec4(0.0);
+            gl_Layer = 2;
+            gl_ViewportIndex = 1;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let result = program.module.entry_points[0]
+        .function
+        .result
+        .as_ref()
+        .unwrap();
+    let bindings: Vec<_> = match program.module.types[result.ty].inner {
+        TypeInner::Struct { ref members, .. } => {
+            members.iter().map(|m| m.binding.clone()).collect()
+        }
+        _ => Vec::new(),
+    };
+
+    assert_eq!(
+        bindings,
+        [
+            Some(Binding::BuiltIn(BuiltIn::Position)),
+            Some(Binding::BuiltIn(BuiltIn::Layer)),
+            Some(Binding::BuiltIn(BuiltIn::ViewportIndex)),
+        ]
+    );
+}
+
+#[test]
+fn compute_builtins() {
+    use crate::{Binding, BuiltIn};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Compute);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) buffer Data {
+            uint data[];
+        };
+        void main() {
+            uvec3 id = gl_GlobalInvocationID + gl_LocalInvocationID;
+            id += gl_WorkGroupID * gl_NumWorkGroups;
+            data[id.x] = gl_LocalInvocationIndex;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let bindings: Vec<_> = program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect();
+
+    assert_eq!(
+        bindings,
+        [
+            BuiltIn::GlobalInvocationId,
+            BuiltIn::LocalInvocationId,
+            BuiltIn::WorkGroupId,
+            BuiltIn::NumWorkGroups,
+            BuiltIn::LocalInvocationIndex,
+        ]
+        .iter()
+        .map(|&b| Some(Binding::BuiltIn(b)))
+        .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn legacy_texture_functions() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let source = r#"
+        layout(location = 0) in vec4 v_dir;
+        layout(location = 0) out vec4 o_color;
+        layout(set = 1, binding = 0) uniform texture2D tex;
+        layout(set = 1, binding = 1) uniform textureCube tex_cube;
+        layout(set = 1, binding = 2) uniform sampler tex_sampler;
+        void main() {
+            o_color = texture2D(sampler2D(tex, tex_sampler), v_dir.xy);
+            o_color += textureCube(samplerCube(tex_cube, tex_sampler), v_dir.xyz);
+        }
+        "#;
+
+    parse_program(
+        &format!("#version 450 compatibility\n{}", source),
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(&format!("#version 450\n{}", source), &entry_points)
+            .err()
+            .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 342,
+                end: 390
+            },
+            "\"texture2D\" is only available in the compatibility profile".into()
+        )
+    );
+}
+
+#[test]
+fn interpolation_qualifiers() {
+    use crate::{Binding, Interpolation, Sampling};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) sample in vec4 v_color;
+        layout(location = 1) noperspective centroid in vec2 v_uv;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = v_color + vec4(v_uv, 0.0, 0.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let bindings: Vec<_> = program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect();
+
+    assert_eq!(
+        bindings,
+        [
+            Some(Binding::Location {
+                location: 0,
+                interpolation: Some(Interpolation::Perspective),
+                sampling: Some(Sampling::Sample),
+            }),
+            Some(Binding::Location {
+                location: 1,
+                interpolation: Some(Interpolation::Linear),
+                sampling: Some(Sampling::Centroid),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn mix_scalar_interpolant() {
+    use crate::{Expression, MathFunction};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            vec4 a = mix(vec4(0.0), vec4(1.0), 0.5);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, main) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+    let selector = main
+        .expressions
+        .iter()
+        .find_map(|(_, e)| match *e {
+            Expression::Math {
+                fun: MathFunction::Mix,
+                arg2,
+                ..
+            } => arg2,
+            _ => None,
+        })
+        .unwrap();
+
+    assert!(matches!(
+        main.expressions[selector],
+        Expression::Splat { .. }
+    ));
+}
+
+#[test]
+fn swizzle_write_duplicates() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                vec4 v = vec4(1.0);
+                v.xx = vec2(2.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 107,
+                end: 110
+            },
+            "swizzle cannot have duplicate components in left-hand-side expression for \"xx\""
+                .into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void f(out vec2 a) {
+                a = vec2(1.0);
+            }
+            void main() {
+                vec4 v = vec4(1.0);
+                f(v.yzy.xy);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 187,
+                end: 191
+            },
+            "swizzle cannot have duplicate components in left-hand-side expression for \"yzy\""
+                .into()
+        )
+    );
+}
+
+#[test]
+fn extended_multiplication() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                uint msb, lsb;
+                umulExtended(1u, 2u, msb, lsb);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 101,
+                end: 131
+            },
+            "extended multiplication (requires 64-bit integers)"
+        )
+    );
+}
+
+#[test]
+fn double_packing() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                double d = packDouble2x32(uvec2(1u, 2u));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 81,
+                end: 110
+            },
+            "double packing"
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                uvec2 v = unpackDouble2x32(1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 97,
+                end: 100
+            },
+            "\"unpackDouble2x32\" expects a double".into()
+        )
+    );
+}
+
+#[test]
+fn constants() {
+    use crate::{Constant, ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        const float a = 1.0;
+        float global = a;
+        const float b = a;
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let mut constants = program.module.constants.iter();
+
+    assert_eq!(
+        constants.next().unwrap().1,
+        &Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(1.0)
+            }
+        }
+    );
+
+    assert!(constants.next().is_none());
+}
+
+#[test]
+fn predefined_macros() {
+    use crate::{ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        #if __VERSION__ != 450
+        #error wrong version
+        #endif
+        const int version = __VERSION__;
+        const int file = __FILE__;
+        const uint line = __LINE__;
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let values: Vec<_> = program
+        .module
+        .constants
+        .iter()
+        .filter_map(|(_, constant)| match constant.inner {
+            ConstantInner::Scalar {
+                value: ScalarValue::Sint(value),
+                ..
+            } => Some(value),
+            // `__LINE__` is expanded to an unsigned integer
+            ConstantInner::Scalar {
+                value: ScalarValue::Uint(value),
+                ..
+            } => Some(value as i64),
+            _ => None,
+        })
+        .collect();
+
+    assert_eq!(values, [450, 0, 8]);
+}
+
+#[test]
+fn function_overloading() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #  version 450
+
+        float saturate(float v) { return clamp(v, 0.0, 1.0); }
+        vec2 saturate(vec2 v) { return clamp(v, vec2(0.0), vec2(1.0)); }
+        vec3 saturate(vec3 v) { return clamp(v, vec3(0.0), vec3(1.0)); }
+        vec4 saturate(vec4 v) { return clamp(v, vec4(0.0), vec4(1.0)); }
+
+        void main() {
+            float v1 = saturate(1.5);
+            vec2 v2 = saturate(vec2(0.5, 1.5));
+            vec3 v3 = saturate(vec3(0.5, 1.5, 2.5));
+            vec3 v4 = saturate(vec4(0.5, 1.5, 2.5, 3.5));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+}
+
+#[test]
+fn implicit_conversions() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            mat4 a = mat4(1);
+            float b = 1u;
+            float c = 1 + 2.0;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+                #  version 450
+                void test(int a) {}
+                void test(uint a) {}
+
+                void main() {
+                    test(1.0);
+                }
+                "#,
+            &entry_points
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 156,
+                end: 165
+            },
+            "Unknown function \'test\'".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+                #  version 450
+                void test(float a) {}
+                void test(uint a) {}
+
+                void main() {
+                    test(1);
+                }
+                "#,
+            &entry_points
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 158,
+                end: 165
+            },
+            "Ambiguous best function for \'test\'".into()
+        )
+    );
+}
+
+#[test]
+fn structs() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Fragment);
+
+    parse_program(
+        r#"
+        #  version 450
+        Test {
+            vec4 pos;
+          } xx;
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+
+    parse_program(
+        r#"
+        #  version 450
+        struct Test {
+            vec4 pos;
+        };
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        const int NUM_VECS = 42;
+        struct Test {
+            vec4 vecs[NUM_VECS];
+        };
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        struct Hello {
+            vec4 test;
+        } test() {
+            return Hello( vec4(1.0) );
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        struct Inner {
+            vec4 pos;
+        };
+        struct Outer {
+            Inner inner;
+            Inner inners[2];
+        };
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        struct Outer {
+            Inner inner;
+        };
+        struct Inner {
+            vec4 pos;
+        };
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            struct Test {
+                Test test;
+            };
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 70, end: 74 },
+            "Struct \"Test\" can't have a member of its own type".into()
+        )
+    );
+
+    parse_program(
+        r#"
+        #  version 450
+        struct Test {};
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+
+    parse_program(
+        r#"
+        #  version 450
+        inout struct Test {
+            vec4 x;
+        };
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn swizzles() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Fragment);
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            vec4 v = vec4(1);
+            v.xyz = vec3(2);
+            v.x = 5.0;
+            v.xyz.zxy.yx.xy = vec2(5.0, 1.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            vec4 v = vec4(1);
+            v.xx = vec2(5.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+
+    parse_program(
+        r#"
+        #  version 450
+        void main() {
+            vec3 v = vec3(1);
+            v.w = 2.0;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap_err();
+}
+
+#[test]
+fn loop_condition_placement() {
+    use crate::{Block, Statement};
+
+    fn main_body<'a>(program: &'a Program) -> &'a Block {
+        &program
+            .module
+            .functions
+            .iter()
+            .find(|&(_, f)| f.name.as_deref() == Some("main"))
+            .unwrap()
+            .1
+            .body
+    }
+
+    fn loop_body(body: &Block) -> &Block {
+        body.iter()
+            .find_map(|s| match *s {
+                Statement::Loop { ref body, .. } => Some(body),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn is_break_check(stmt: &Statement) -> bool {
+        match *stmt {
+            Statement::If { ref accept, .. } => matches!(accept[..], [Statement::Break]),
+            _ => false,
+        }
+    }
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    // `while` tests the condition before running the body
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            int x = 0;
+            while(false) {
+                x = x + 1;
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let body = loop_body(main_body(&program));
+    let check = body.iter().position(is_break_check).unwrap();
+    let block = body
+        .iter()
+        .position(|s| matches!(*s, Statement::Block(_)))
+        .unwrap();
+    assert!(check < block);
+
+    // `do-while` runs the body once before testing the condition
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            int x = 0;
+            do {
+                x = x + 1;
+            } while(false);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let body = loop_body(main_body(&program));
+    let check = body.iter().position(is_break_check).unwrap();
+    let block = body
+        .iter()
+        .position(|s| matches!(*s, Statement::Block(_)))
+        .unwrap();
+    assert!(block < check);
+    assert_eq!(check, body.len() - 1);
+}
+
+#[test]
+fn loop_continue() {
+    use crate::Statement;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            int x = 0;
+            for(int i = 0; i < 10; i++) {
+                if (i == 5) continue;
+                x = x + i;
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, function) = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, f)| f.name.as_deref() == Some("main"))
+        .unwrap();
+
+    let (body, continuing) = function
+        .body
+        .iter()
+        .find_map(|s| match *s {
+            Statement::Loop {
+                ref body,
+                ref continuing,
+            } => Some((body, continuing)),
+            _ => None,
+        })
+        .unwrap();
+
+    // `continue` jumps to the continuing block, which must hold the update
+    assert!(body.iter().any(|s| match *s {
+        Statement::Block(ref block) => block.iter().any(|s| match *s {
+            Statement::If { ref accept, .. } => matches!(accept[..], [Statement::Continue]),
+            _ => false,
+        }),
+        _ => false,
+    }));
+    assert!(continuing
+        .iter()
+        .any(|s| matches!(*s, Statement::Store { .. })));
+}
+
+#[test]
+fn return_type_mismatch() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                return 1;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 70, end: 79 },
+            "Void function cannot return a value".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            float test() {
+                return;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 71, end: 78 },
+            "Non-void function must return a value".into()
+        )
+    );
+}
+
+#[test]
+fn initializer_lists() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        struct Light {
+            vec3 color;
+            float intensity;
+        };
+        void main() {
+            vec3 v = {1, 2, 3};
+            mat2 m = {{1.0, 0.0}, {0.0, 1.0}};
+            float a[2] = {1.0, 2.0};
+            Light l = {{1.0, 1.0, 1.0}, 2};
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec3 v = {1.0, 2.0};
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 77, end: 87 },
+            "Initializer list expects 3 elements but got 2".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float f = {1.0};
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 78, end: 79 },
+            "Initializer lists can only be used with aggregate types".into()
+        )
+    );
+}
+
+#[test]
+fn scalar_swizzles() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        void main() {
+            float f = 1.0;
+            float a = f.x;
+            f.r = 2.0;
+            vec3 b = f.sss;
+            float c = (f * 2.0).r + b.x.x;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float f = 1.0;
+                vec2 v = f.xy;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 109,
+                end: 112
+            },
+            "Invalid swizzle for scalar \"xy\"".into()
+        )
+    );
+}
+
+#[test]
+fn clip_distances() {
+    use crate::{Binding, BuiltIn, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let distances = |program: &Program| {
+        let function = &program.module.entry_points[0].function;
+        let ty = function.result.as_ref().unwrap().ty;
+        match program.module.types[ty].inner {
+            TypeInner::Struct { ref members, .. } => members
+                .iter()
+                .filter_map(|member| match member.binding {
+                    Some(Binding::BuiltIn(builtin @ BuiltIn::ClipDistance))
+                    | Some(Binding::BuiltIn(builtin @ BuiltIn::CullDistance)) => {
+                        match program.module.types[member.ty].inner {
+                            TypeInner::Array {
+                                size: crate::ArraySize::Constant(size),
+                                ..
+                            } => Some((
+                                builtin,
+                                program.module.constants[size].to_array_length().unwrap(),
+                            )),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        }
+    };
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            gl_Position = vec4(1.0);
+            gl_ClipDistance[0] = 1.0;
+            gl_CullDistance[1] = -1.0;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+    assert_eq!(
+        distances(&program),
+        [(BuiltIn::ClipDistance, 8), (BuiltIn::CullDistance, 8)]
+    );
+
+    let program = parse_program(
+        r#"
+        #version 450
+        out float gl_ClipDistance[2];
+        void main() {
+            gl_Position = vec4(1.0);
+            for (int i = 0; i < 2; i++) {
+                gl_ClipDistance[i] = 1.0;
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+    assert_eq!(distances(&program), [(BuiltIn::ClipDistance, 2)]);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            out vec2 gl_ClipDistance;
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 47, end: 62 },
+            "\"gl_ClipDistance\" must be redeclared as a sized float array".into()
+        )
+    );
+}
+
+#[test]
+fn block_selects_emitted() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(push_constant) uniform PushConstants {
+            float example;
+        } c;
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+}
+
+#[test]
+fn atomic_counters() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                uint counter;
+                atomicCounterIncrement(counter);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 100,
+                end: 131
+            },
+            "atomic counters (requires atomic operations)"
+        )
+    );
+}
+
+#[test]
+fn mix_integer_select() {
+    use crate::Expression;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #  version 450
+        void main() {
+            ivec3 x = ivec3(1);
+            ivec3 y = ivec3(2);
+            ivec3 a = mix(x, y, bvec3(true, false, true));
+            uvec2 b = mix(uvec2(1u), uvec2(2u), bvec2(false));
+            int c = mix(1, 2, true);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let selects: Vec<_> = main
+        .expressions
+        .iter()
+        .filter_map(|(_, e)| match *e {
+            Expression::Select { accept, reject, .. } => Some((accept, reject)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(selects.len(), 3);
+
+    let local_name = |expr| match main.expressions[expr] {
+        Expression::Load { pointer } => match main.expressions[pointer] {
+            Expression::LocalVariable(var) => main.local_variables[var].name.as_deref(),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    // `y` is picked where the selector is true
+    let (accept, reject) = selects[0];
+    assert_eq!(local_name(accept), Some("y"));
+    assert_eq!(local_name(reject), Some("x"));
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #  version 450
+            void main() {
+                ivec3 a = mix(ivec3(1), ivec3(2), 0.5);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 80,
+                end: 108
+            },
+            "\"mix\" with non float operands requires a boolean selector".into()
+        )
+    );
+}
+
+#[test]
+fn aggregate_equality() {
+    use crate::{BinaryOperator, Expression};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        struct Inner {
+            vec2 a;
+            float b[2];
+        };
+        struct Outer {
+            Inner inner;
+            mat2 m;
+            int c;
+        };
+        void main() {
+            Outer x;
+            Outer y;
+            bool same = x == y;
+            bool different = x.inner.b != y.inner.b;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let count = |f: &dyn Fn(&Expression) -> bool| {
+        main.expressions.iter().filter(|&(_, expr)| f(expr)).count()
+    };
+    // Each aggregate is reduced to a single boolean
+    assert_eq!(
+        count(&|expr| matches!(
+            *expr,
+            Expression::Binary {
+                op: BinaryOperator::LogicalAnd,
+                ..
+            }
+        )),
+        5
+    );
+    assert_eq!(
+        count(&|expr| matches!(
+            *expr,
+            Expression::Binary {
+                op: BinaryOperator::LogicalOr,
+                ..
+            }
+        )),
+        1
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(std430, set = 0, binding = 0) buffer Data {
+                float values[];
+            };
+            void main() {
+                bool same = values == values;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 190,
+                end: 206
+            },
+            "Cannot compare runtime sized arrays".into()
+        )
+    );
+}
+
+#[test]
+fn integer_sign() {
+    use crate::{Expression, MathFunction};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            int a = sign(-2);
+            ivec3 b = sign(ivec3(1, 0, -1));
+            vec2 c = sign(vec2(-1.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    assert_eq!(
+        main.expressions
+            .iter()
+            .filter(|&(_, expr)| matches!(
+                *expr,
+                Expression::Math {
+                    fun: MathFunction::Sign,
+                    ..
+                }
+            ))
+            .count(),
+        3
+    );
+    // The integer operands aren't converted to floats
+    assert!(!main
+        .expressions
+        .iter()
+        .any(|(_, expr)| matches!(*expr, Expression::As { .. })));
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                uint a = sign(2u);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 82, end: 84 },
+            "\"sign\" expects a signed integer or float argument".into()
+        )
+    );
+}
+
+#[test]
+fn custom_intrinsics() {
+    use super::{parse_str, Options};
+    use crate::{BinaryOperator, Expression, TypeInner};
+
+    // The options can be shared with other threads
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Options>();
+
+    let mut options = Options::default();
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Vertex);
+    options.intrinsics.add("square", |ctx, args| {
+        let arg = match *args {
+            [arg] => arg,
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    ctx.meta(),
+                    "\"square\" expects one argument".into(),
+                ))
+            }
+        };
+        match *ctx.resolve_type(arg)? {
+            TypeInner::Scalar { .. } | TypeInner::Vector { .. } => {}
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    ctx.meta(),
+                    "\"square\" expects a scalar or vector".into(),
+                ))
+            }
+        }
+
+        Ok(Some(ctx.add_expression(Expression::Binary {
+            op: BinaryOperator::Multiply,
+            left: arg,
+            right: arg,
+        })))
+    });
+
+    let module = parse_str(
+        r#"
+        #version 450
+        void main() {
+            vec3 a = square(vec3(2.0));
+        }
+        "#,
+        &options,
+    )
+    .unwrap();
+
+    let main = &module.functions.iter().next().unwrap().1;
+    assert!(main.expressions.iter().any(|(_, expr)| matches!(
+        *expr,
+        Expression::Binary {
+            op: BinaryOperator::Multiply,
+            left,
+            right
+        } if left == right
+    )));
+
+    let error = parse_str(
+        r#"
+        #version 450
+        void main() {
+            float a = square(1.0, 2.0);
+        }
+        "#,
+        &options,
+    )
+    .err()
+    .unwrap();
+    assert_eq!(
+        error.kind,
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 66, end: 82 },
+            "\"square\" expects one argument".into()
+        )
+    );
+
+    // Declared functions take precedence over intrinsics of the same name
+    let module = parse_str(
+        r#"
+        #version 450
+        float square(float x) {
+            return x + x;
+        }
+        void main() {
+            float a = square(2.0);
+            vec3 b = square(vec3(2.0));
+        }
+        "#,
+        &options,
+    )
+    .unwrap();
+
+    let main = &module.functions.iter().nth(1).unwrap().1;
+    let calls = main
+        .body
+        .iter()
+        .filter(|stmt| matches!(**stmt, crate::Statement::Call { .. }))
+        .count();
+    assert_eq!(calls, 1);
+    assert!(main.expressions.iter().any(|(_, expr)| matches!(
+        *expr,
+        Expression::Binary {
+            op: BinaryOperator::Multiply,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn scalar_length() {
+    use crate::{Expression, MathFunction};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float a = length(-2.0);
+            float b = length(vec2(1.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let funs: Vec<_> = main
+        .expressions
+        .iter()
+        .filter_map(|(_, expr)| match *expr {
+            Expression::Math { fun, .. } => Some(fun),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(funs, [MathFunction::Abs, MathFunction::Length]);
+}
+
+#[test]
+fn array_size_inference() {
+    use crate::{ArraySize, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float a[] = float[](1.0, 2.0, 3.0);
+            float b[] = {1.0, 2.0};
+            int c[2] = int[2](1, 2);
+            float d[] = float[3](1.0, 2.0, 3.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let lengths: Vec<_> = main
+        .local_variables
+        .iter()
+        .map(|(_, var)| match program.module.types[var.ty].inner {
+            TypeInner::Array {
+                size: ArraySize::Constant(constant),
+                ..
+            } => program.module.constants[constant].to_array_length(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lengths, [Some(3), Some(2), Some(2), Some(3)]);
+
+    assert_eq!(
+        parse_program(
+            r#"
+        #version 450
+        void main() {
+            float a[] = float[2](1.0, 2.0, 3.0);
+        }
+        "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 68, end: 91 },
+            "Array constructor expects 2 elements but got 3".into()
+        )
+    );
+}
+
+#[test]
+fn interface_blocks() {
+    use crate::{Binding, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in VertexIn {
+            vec3 position;
+            mat2 transform;
+        };
+        layout(location = 1) out VertexData {
+            vec3 normal;
+            int id;
+        } vOut;
+        void main() {
+            vOut.normal = position;
+            vOut.id = 1;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let entry_point = &program.module.entry_points[0];
+    let arguments: Vec<_> = entry_point
+        .function
+        .arguments
+        .iter()
+        .map(|arg| (arg.name.as_deref(), arg.binding.clone()))
+        .collect();
+    assert_eq!(arguments.len(), 1);
+    assert!(matches!(
+        arguments[0],
+        (
+            Some("position"),
+            Some(Binding::Location { location: 0, .. })
+        )
+    ));
+
+    let result_ty = entry_point.function.result.as_ref().unwrap().ty;
+    let members = match program.module.types[result_ty].inner {
+        TypeInner::Struct { ref members, .. } => members,
+        _ => unreachable!(),
+    };
+    let locations: Vec<_> = members
+        .iter()
+        .map(|member| match member.binding {
+            Some(Binding::Location { location, .. }) => (member.name.as_deref(), location),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(locations, [(Some("vOut.normal"), 1), (Some("vOut.id"), 2)]);
+}
+
+#[test]
+fn zero_workgroup_size() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Compute);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(local_size_x = 64) in;
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+    assert_eq!(program.module.entry_points[0].workgroup_size, [64, 1, 1]);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(local_size_x = 8, local_size_y = 0) in;
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 63, end: 79 },
+            "Workgroup size dimensions must be greater than zero".into()
+        )
+    );
+}
+
+#[test]
+fn texture_lod_clamp() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            #extension GL_ARB_sparse_texture_clamp : require
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                vec4 c = textureClampARB(sampler2D(tex, samp), vec2(0.5), 1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 265,
+                end: 318
+            },
+            "level of detail clamping"
+        )
+    );
+}
+
+#[test]
+fn constant_splat() {
+    use crate::{ConstantInner, Expression};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float x = 1.0;
+            vec3 a = vec3(2.0);
+            vec3 b = vec3(x);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let splats = main
+        .expressions
+        .iter()
+        .filter(|&(_, expr)| matches!(*expr, Expression::Splat { .. }))
+        .count();
+    assert_eq!(splats, 1);
+
+    let init = main.local_variables.iter().nth(1).unwrap().1.init.unwrap();
+    match program.module.constants[init].inner {
+        ConstantInner::Composite { ref components, .. } => assert_eq!(components.len(), 3),
+        ConstantInner::Scalar { .. } => unreachable!(),
+    }
+}
+
+#[test]
+fn storage_images() {
+    use crate::{ImageClass, Statement, StorageFormat, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Compute);
+
+    let source = |value: &str| {
+        format!(
+            r#"
+            #version 450
+            layout(rgba8, set = 0, binding = 0) uniform image2D img;
+            layout(r32i, set = 0, binding = 1) uniform iimage2DArray layers;
+            void main() {{
+                ivec4 texel = imageLoad(layers, ivec3(0, 0, 1));
+                imageStore(img, ivec2(0), {});
+            }}
+            "#,
+            value
+        )
+    };
+
+    let program = parse_program(&source("vec4(texel)"), &entry_points).unwrap();
+
+    let formats: Vec<_> = program
+        .module
+        .global_variables
+        .iter()
+        .map(|(_, var)| match program.module.types[var.ty].inner {
+            TypeInner::Image {
+                class: ImageClass::Storage(format),
+                ..
+            } => format,
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(formats, [StorageFormat::Rgba8Unorm, StorageFormat::R32Sint]);
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    assert!(main
+        .body
+        .iter()
+        .any(|stmt| matches!(*stmt, Statement::ImageStore { .. })));
+
+    assert_eq!(
+        parse_program(&source("vec3(1.0)"), &entry_points)
+            .err()
+            .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 305,
+                end: 314
+            },
+            "imageStore value doesn't match the image format".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(rgba8, set = 0, binding = 0) uniform iimage2D img;
+            void main() {}
+            "#,
+            &entry_points
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 91, end: 94 },
+            "Format qualifier doesn't match the image type".into()
+        )
+    );
+}
+
+#[test]
+fn out_parameter_write_back() {
+    use crate::Statement;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        float g;
+        void f(out float x) { x = 1.0; }
+        void k(out int x) { x = 1; }
+        void main() {
+            float a;
+            f(a);
+            f(g);
+            float arr[2];
+            f(arr[1]);
+            float b;
+            k(b);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, function)| function.name.as_deref() == Some("main"))
+        .unwrap()
+        .1;
+
+    // Only the global, the array element and the converted value need a
+    // temporary, `out` temporaries aren't initialized but are written back
+    let temporaries = main
+        .local_variables
+        .iter()
+        .filter(|&(_, var)| var.name.is_none())
+        .count();
+    assert_eq!(temporaries, 3);
+
+    let stores = main
+        .body
+        .iter()
+        .filter(|stmt| matches!(**stmt, Statement::Store { .. }))
+        .count();
+    assert_eq!(stores, 3);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void f(inout float x) { x += 1.0; }
+            void main() {
+                int i;
+                f(i);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 139,
+                end: 143
+            },
+            "Unknown function 'f'".into()
+        )
+    );
+}
+
+#[test]
+fn shared_variables() {
+    use crate::{Barrier, Expression, Statement, StorageClass};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Compute);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(local_size_x = 256) in;
+        shared float tile[256];
+        void main() {
+            uint i = gl_LocalInvocationIndex;
+            tile[i] = float(i);
+            barrier();
+            float sum = tile[i] + tile[255 - i];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (tile, var) = program
+        .module
+        .global_variables
+        .iter()
+        .find(|&(_, var)| var.name.as_deref() == Some("tile"))
+        .unwrap();
+    assert_eq!(var.class, StorageClass::WorkGroup);
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    assert!(main.expressions.iter().any(|(_, expr)| match *expr {
+        Expression::Access { base, .. } =>
+            main.expressions[base] == Expression::GlobalVariable(tile),
+        _ => false,
+    }));
+    assert!(main
+        .body
+        .iter()
+        .any(|stmt| matches!(*stmt, Statement::Barrier(Barrier::WORK_GROUP))));
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            shared float value = 1.0;
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 51, end: 62 },
+            "shared variables can't have an initializer".into()
+        )
+    );
+}
+
+#[test]
+fn nesting_depth() {
+    use super::{parse_str, Options};
+
+    let mut options = Options {
+        max_nesting_depth: 16,
+        ..Default::default()
+    };
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Vertex);
+
+    let parens = |depth: usize| {
+        format!(
+            "#version 450\nvoid main() {{ float x = {}1.0{}; }}",
+            "(".repeat(depth),
+            ")".repeat(depth)
+        )
+    };
+
+    parse_str(&parens(8), &options).unwrap();
+    assert_eq!(
+        parse_str(&parens(1024), &options).err().unwrap().kind,
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 53, end: 54 },
+            "Maximum nesting depth exceeded".into()
+        )
+    );
+
+    // Flat chains of operators aren't nested
+    let chain = format!(
+        "#version 450\nvoid main() {{ float x = 1.0{}; }}",
+        " + 1.0".repeat(300)
+    );
+    parse_str(&chain, &options).unwrap();
+
+    let blocks = format!(
+        "#version 450\nvoid main() {{ {}{}}}",
+        "{ ".repeat(1024),
+        "} ".repeat(1024)
+    );
+    assert_eq!(
+        parse_str(&blocks, &options).err().unwrap().kind,
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 59, end: 60 },
+            "Maximum nesting depth exceeded".into()
+        )
+    );
+}
+
+#[test]
+fn deeply_nested_calls() {
+    use super::ast::{EntryArg, EntryArgUse, PrologueStage};
+    use crate::{
+        Arena, Binding, Expression, Function, GlobalVariable, ScalarValue, Statement,
+        StorageAccess, StorageClass, Type, TypeInner,
+    };
+
+    let entry_points = crate::FastHashMap::default();
+    let mut program = Program::new(&entry_points);
+
+    let ty = program.module.types.append(Type {
+        name: None,
+        inner: TypeInner::Scalar {
+            kind: ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let handle = program.module.global_variables.append(GlobalVariable {
+        name: Some("color".into()),
+        class: StorageClass::Private,
+        binding: None,
+        ty,
+        init: None,
+        storage_access: StorageAccess::empty(),
+    });
+    program.entry_args.push(EntryArg {
+        name: Some("color".into()),
+        binding: Binding::Location {
+            location: 0,
+            interpolation: None,
+            sampling: None,
+        },
+        handle,
+        prologue: PrologueStage::empty(),
+    });
+
+    // The callee writes the output and is only called from the bottom of
+    // thousands of nested ifs in the entry point
+    let callee = program.module.functions.append(Function::default());
+    let mut expressions = Arena::new();
+    let condition = expressions.append(Expression::Constant(
+        program.module.constants.fetch_or_append(crate::Constant {
+            name: None,
+            specialization: None,
+            inner: crate::ConstantInner::Scalar {
+                width: 1,
+                value: ScalarValue::Bool(true),
+            },
+        }),
+    ));
+    let mut body = vec![Statement::Call {
+        function: callee,
+        arguments: Vec::new(),
+        result: None,
+    }];
+    for _ in 0..10_000 {
+        body = vec![Statement::If {
+            condition,
+            accept: body,
+            reject: Vec::new(),
+        }];
+    }
+    let caller = program.module.functions.append(Function {
+        expressions,
+        body,
+        ..Default::default()
+    });
+
+    program.function_arg_use = vec![vec![EntryArgUse::WRITE], Vec::new()];
+    program
+        .entries
+        .push(("main".into(), ShaderStage::Fragment, caller));
+    program.add_entry_points().unwrap();
+
+    let entry = &program.module.entry_points[0];
+    assert!(entry.function.result.is_some());
+}
+
+#[test]
+fn integer_samplers() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform itexture2D offsets;
+        layout(set = 0, binding = 1) uniform sampler samp;
+        layout(location = 0) out int result;
+        void main() {
+            ivec4 offset = texelFetch(isampler2D(offsets, samp), ivec2(0), 0);
+            result = offset.y + 1;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform utexture2D ids;
+        layout(set = 0, binding = 1) uniform sampler samp;
+        layout(location = 0) out uint result;
+        void main() {
+            uvec4 id = texture(usampler2D(ids, samp), vec2(0.5));
+            result = id.x & 0xFFu;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D colors;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                uvec4 id = texture(usampler2D(colors, samp), vec2(0.5));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 228,
+                end: 234
+            },
+            "\"usampler2D\" doesn't match the sampled type of the texture".into()
+        )
+    );
+}
+
+#[test]
+fn assignment_chaining() {
+    use crate::{ConstantInner, Expression, ScalarValue, Statement};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float a;
+            float b;
+            a = b = 1.0;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let stores: Vec<_> = main
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Statement::Store { pointer, value } => Some((pointer, value)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stores.len(), 2);
+
+    let locals: Vec<_> = stores
+        .iter()
+        .map(|&(pointer, _)| match main.expressions[pointer] {
+            Expression::LocalVariable(local) => main.local_variables[local].name.clone(),
+            _ => None,
+        })
+        .collect();
+    assert!(stores.iter().all(|&(_, value)| matches!(
+        main.expressions[value],
+        Expression::Constant(constant) if program.module.constants[constant].inner
+            == ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(1.0)
+            }
+    )));
+    assert_eq!(locals, [Some("b".into()), Some("a".into())]);
+}
+
+#[test]
+fn const_parameters() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        float scale(const in float x, in const highp float y) {
+            return x * y;
+        }
+        void main() {
+            float a = scale(1.0, 2.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void scale(const in float x) {
+                x = 2.0;
+            }
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 85, end: 86 },
+            "Variable cannot be used in LHS position".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void scale(const out float x) {}
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 49, end: 54 },
+            "const parameters can't be out or inout".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void scale(in out float x) {}
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 52, end: 55 },
+            "Parameters can only have one of in, out or inout".into()
+        )
+    );
+}
+
+#[test]
+fn texel_fetch_offset() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform texture2D tex;
+        layout(set = 0, binding = 1) uniform sampler samp;
+        layout(location = 0) out vec4 color;
+        void main() {
+            color = texelFetchOffset(sampler2D(tex, samp), ivec2(4), 0, ivec2(1, -1));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                vec4 color = texelFetchOffset(sampler2D(tex, samp), ivec2(4), 0, ivec3(1));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 260,
+                end: 268
+            },
+            "Texel offset doesn't match the image dimension".into()
+        )
+    );
+}
+
+#[test]
+fn struct_return() {
+    use crate::Expression;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        struct Data {
+            vec3 color;
+            float alpha;
+        };
+        Data getData() {
+            return Data(vec3(1.0), 0.5);
+        }
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = vec4(getData().color, getData().alpha);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    let main = &program.module.functions.iter().nth(1).unwrap().1;
+    let accesses = main
+        .expressions
+        .iter()
+        .filter(|&(_, expr)| match *expr {
+            Expression::AccessIndex { base, .. } => {
+                matches!(main.expressions[base], Expression::Call(_))
+            }
+            _ => false,
+        })
+        .count();
+    assert_eq!(accesses, 2);
+}
+
+#[test]
+fn overload_shapes() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        float f(vec2 v) { return v.x; }
+        float f(vec3 v) { return v.y; }
+        void main() {
+            float a = f(ivec2(1));
+            float b = f(uvec3(1u));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            float f(vec3 v) { return v.x; }
+            void main() {
+                float a = f(vec2(1.0));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 122,
+                end: 134
+            },
+            "Unknown function 'f'".into()
+        )
+    );
+}
+
+#[test]
+fn extensions() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        #extension GL_ARB_sparse_texture_clamp : enable
+        #extension GL_GOOGLE_include_directive : require
+        #extension GL_EXT_samplerless_texture_functions : enable
+        #extension GL_EXT_samplerless_texture_functions : disable
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let mut extensions: Vec<_> = program.extensions.iter().map(String::as_str).collect();
+    extensions.sort_unstable();
+    assert_eq!(
+        extensions,
+        ["GL_ARB_sparse_texture_clamp", "GL_GOOGLE_include_directive"]
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            #extension GL_ARB_sparse_texture_clamp : enable
+            #extension all : disable
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                vec4 c = textureClampARB(sampler2D(tex, samp), vec2(0.5), 1.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 301,
+                end: 354
+            },
+            "\"textureClampARB\" requires the GL_ARB_sparse_texture_clamp extension".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            #extension GL_ARB_sparse_texture_clamp : maybe
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 79, end: 84 },
+            "Unknown extension behavior \"maybe\"".into()
+        )
+    );
+}
+
+#[test]
+fn texture_query_lod() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform texture2DArray tex;
+        layout(set = 0, binding = 1) uniform sampler samp;
+        layout(location = 0) in vec2 uv;
+        layout(location = 0) out vec2 lod;
+        void main() {
+            lod = textureQueryLod(sampler2DArray(tex, samp), uv);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                vec2 lod = textureQueryLod(sampler2D(tex, samp), vec3(0.5));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 244,
+                end: 253
+            },
+            "Level of detail queries need a float coordinate with 2 components".into()
+        )
+    );
+}
+
+#[test]
+fn preserve_names() {
+    use super::{parse_str, Options};
+
+    let mut options = Options {
+        preserve_names: true,
+        ..Default::default()
+    };
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Vertex);
+
+    let module = parse_str(
+        r#"
+        #version 450
+        void get(out int value) {
+            value = 1;
+        }
+        void main() {
+            float f;
+            get(f);
+            gl_Position = vec4(f);
+        }
+        "#,
+        &options,
+    )
+    .unwrap();
+
+    let main = &module.functions.iter().nth(1).unwrap().1;
+    assert!(main
+        .local_variables
+        .iter()
+        .any(|(_, local)| local.name.as_deref() == Some("value")));
+
+    let result = module.entry_points[0].function.result.as_ref().unwrap();
+    assert!(matches!(
+        module.types[result.ty].inner,
+        crate::TypeInner::Struct { ref members, .. }
+            if members[0].name.as_deref() == Some("gl_Position")
+    ));
+}
+
+#[test]
+fn local_const_arrays() {
+    use crate::Statement;
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int index;
+        layout(location = 0) out vec2 color;
+        void main() {
+            const vec2 kernel[3] = vec2[3](vec2(1.0), vec2(2.0, 0.5), vec2(-1.0));
+            const float weights[] = { 0.25, 0.5, 0.25 };
+            color = kernel[index] * weights[index];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // The arrays are initialized by constants instead of per element stores
+    let main = &program.module.functions.iter().next().unwrap().1;
+    assert!(main
+        .local_variables
+        .iter()
+        .all(|(_, var)| var.init.is_some()));
+    assert_eq!(
+        main.body
+            .iter()
+            .filter(|stmt| matches!(**stmt, Statement::Store { .. }))
+            .count(),
+        1
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                const float weights[2] = float[2](0.5, 0.5);
+                weights[1] = 1.0;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 129,
+                end: 136
+            },
+            "Variable cannot be used in LHS position".into()
+        )
+    );
+}
+
+#[test]
+fn integer_indices() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int signed_index;
+        layout(location = 1) flat in uint unsigned_index;
+        layout(location = 0) out vec4 color;
+        void main() {
+            float values[4] = float[4](0.0, 1.0, 2.0, 3.0);
+            vec4 v = vec4(1.0);
+            color = vec4(
+                values[signed_index],
+                values[unsigned_index],
+                v[unsigned_index],
+                v[signed_index + 1u]
+            );
+            v[unsigned_index] = values[2u];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float values[2] = float[2](0.0, 1.0);
+                float x = values[0.5];
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 139,
+                end: 142
+            },
+            "Array index must be an integer".into()
+        )
+    );
+}
+
+#[test]
+fn modulo_operands() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    parse_program(
+        r#"
+        #version 450
+        void main() {
+            int a = 7 % 3;
+            uvec2 b = uvec2(7u) % 2u;
+            a %= 2;
+            float c = mod(7.0, 3);
+            vec3 d = mod(vec3(7.0), 2.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    // The scalar divisor is splatted to the dividend's size
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in vec3 value;
+        layout(location = 0) out vec3 result;
+        void main() {
+            result = mod(value, 2.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float a = 7.0;
+                float b = a % 3.0;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 109,
+                end: 116
+            },
+            "The % operator only works on integers, use mod for floats".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                int a = mod(7, 3);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 76, end: 85 },
+            "mod only works on floats, use the % operator for integers".into()
+        )
+    );
+}
+
+#[test]
+fn integer_sample_coordinates() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform texture2D tex;
+        layout(set = 0, binding = 1) uniform texture2DArray tex_array;
+        layout(set = 0, binding = 2) uniform sampler samp;
+        layout(location = 0) flat in ivec2 coord;
+        layout(location = 0) out vec4 color;
+        void main() {
+            color = texture(sampler2D(tex, samp), coord)
+                + textureLod(sampler2DArray(tex_array, samp), ivec3(coord, 1), 0.0)
+                + texelFetch(sampler2D(tex, samp), coord, 0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // Both sampled coordinates and the level of detail are converted, the
+    // fetched coordinate stays an integer
+    let main = &program.module.functions.iter().next().unwrap().1;
+    assert_eq!(
+        main.expressions
+            .iter()
+            .filter(|&(_, expr)| matches!(
+                *expr,
+                crate::Expression::As {
+                    kind: ScalarKind::Float,
+                    convert: Some(4),
+                    ..
+                }
+            ))
+            .count(),
+        3
+    );
+    assert!(main.expressions.iter().any(|(_, expr)| matches!(
+        *expr,
+        crate::Expression::ImageLoad { coordinate, .. }
+            if matches!(main.expressions[coordinate], crate::Expression::Load { .. })
+    )));
+}
+
+#[test]
+fn sample_builtins() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) out vec4 color;
+        void main() {
+            color = vec4(float(gl_SampleID));
+            gl_SampleMask[0] = gl_SampleMaskIn[0] & 0x5;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    let function = &program.module.entry_points[0].function;
+    let sample_mask = crate::Binding::BuiltIn(crate::BuiltIn::SampleMask);
+    assert!(function
+        .arguments
+        .iter()
+        .any(|arg| arg.binding == Some(crate::Binding::BuiltIn(crate::BuiltIn::SampleIndex))));
+    assert!(function
+        .arguments
+        .iter()
+        .any(|arg| arg.binding.as_ref() == Some(&sample_mask)));
+    let result = function.result.as_ref().unwrap().ty;
+    assert!(matches!(
+        program.module.types[result].inner,
+        crate::TypeInner::Struct { ref members, .. }
+            if members.iter().any(|member| member.binding.as_ref() == Some(&sample_mask))
+    ));
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) out uint primitive;
+        void main() {
+            primitive = gl_PrimitiveID;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert!(program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .any(|arg| arg.binding == Some(crate::Binding::BuiltIn(crate::BuiltIn::PrimitiveIndex))));
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) flat out uint primitive;
+            void main() {
+                primitive = gl_PrimitiveID;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 138,
+                end: 152
+            },
+            "\"gl_PrimitiveID\" is only available in fragment shaders".into()
+        )
+    );
+}
+
+#[test]
+fn constant_texture_sampling() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            void main() {
+                float values[int(texture(sampler2D(tex, samp), vec2(0.5)).x)];
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 208,
+                end: 255
+            },
+            "Texture sampling is not a constant expression".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler samp;
+            const vec4 color = texture(sampler2D(tex, samp), vec2(0.5));
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 184,
+                end: 224
+            },
+            "Texture sampling is not a constant expression".into()
+        )
+    );
+}
+
+#[test]
+fn arrays_of_arrays() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int row;
+        layout(location = 0) out float color;
+        float weights[2][3] = float[2][3](float[3](1.0, 2.0, 3.0), float[3](4.0, 5.0, 6.0));
+        void main() {
+            float[3] grid[2] = weights;
+            float flat_grid[][2] = { { 1.0, 2.0 }, { 3.0, 4.0 } };
+            grid[1][2] = flat_grid[1][0];
+            color = grid[row][1] + weights[0][row];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // The outer array has two elements which are arrays of three floats
+    let global = program
+        .module
+        .global_variables
+        .iter()
+        .find(|&(_, var)| var.name.as_deref() == Some("weights"))
+        .unwrap()
+        .1;
+    let (base, size) = match program.module.types[global.ty].inner {
+        crate::TypeInner::Array {
+            base,
+            size: crate::ArraySize::Constant(size),
+            ..
+        } => (base, size),
+        _ => unreachable!(),
+    };
+    assert_eq!(program.module.constants[size].to_array_length(), Some(2));
+    assert!(matches!(
+        program.module.types[base].inner,
+        crate::TypeInner::Array { size: crate::ArraySize::Constant(size), .. }
+            if program.module.constants[size].to_array_length() == Some(3)
+    ));
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float grid[2][] = float[2][3](float[3](1.0, 2.0, 3.0), float[3](4.0, 5.0, 6.0));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 81, end: 83 },
+            "Only the outermost array dimension can be unsized".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float grid[2][3] = float[2][3](float[3](1.0, 2.0, 3.0), float[2](4.0, 5.0));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 124,
+                end: 142
+            },
+            "Array constructor element doesn't match the element type".into()
+        )
+    );
+}
+
+#[test]
+fn chained_swizzles() {
+    use crate::{Expression, Statement, SwizzleComponent, VectorSize};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let parse_module = |source| {
+        let program = parse_program(source, &entry_points).unwrap();
+        crate::valid::Validator::new(
+            crate::valid::ValidationFlags::all(),
+            crate::valid::Capabilities::all(),
+        )
+        .validate(&program.module)
+        .unwrap();
+
+        program.module
+    };
+
+    // The second swizzle indexes into the result of the first one
+    let module = parse_module(
+        r#"
+        #version 450
+        layout(location = 0) in vec4 v;
+        layout(location = 0) out vec2 color;
+        void main() {
+            color = v.wzyx.xy;
+        }
+        "#,
+    );
+    let function = module.functions.iter().next().unwrap().1;
+    let (vector, pattern) = function
+        .expressions
+        .iter()
+        .find_map(|(_, expr)| match *expr {
+            Expression::Swizzle {
+                size: VectorSize::Bi,
+                vector,
+                pattern,
+            } => Some((vector, pattern)),
+            _ => None,
+        })
+        .unwrap();
+    assert_eq!(pattern[..2], [SwizzleComponent::W, SwizzleComponent::Z]);
+    assert!(matches!(
+        function.expressions[vector],
+        Expression::Load { .. }
+    ));
+
+    // A single component selects the matching component of the original vector
+    let module = parse_module(
+        r#"
+        #version 450
+        layout(location = 0) in vec4 v;
+        layout(location = 0) out float color;
+        void main() {
+            color = v.wzyx.yx.y;
+        }
+        "#,
+    );
+    let function = module.functions.iter().next().unwrap().1;
+    assert!(function.expressions.iter().any(|(_, expr)| matches!(
+        *expr,
+        Expression::AccessIndex { base, index: 3 }
+            if matches!(function.expressions[base], Expression::Load { .. })
+    )));
+
+    // Stores through chained swizzles write the original components
+    let module = parse_module(
+        r#"
+        #version 450
+        layout(location = 0) out vec4 color;
+        void main() {
+            vec4 v = vec4(0.0);
+            v.wzyx.xy = vec2(1.0, 2.0);
+            color = v;
+        }
+        "#,
+    );
+    let function = module.functions.iter().next().unwrap().1;
+    let stored: Vec<_> = function
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Statement::Store { pointer, .. } => match function.expressions[pointer] {
+                Expression::AccessIndex { index, .. } => Some(index),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stored, [3, 2]);
+}
+
+#[test]
+fn matrix_column_swizzle_writes() {
+    use crate::{Expression, Statement};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int column;
+        layout(location = 0) out vec4 color;
+        void add(inout vec2 v) {
+            v += vec2(1.0);
+        }
+        void main() {
+            mat4 m = mat4(1.0);
+            m[1].yz = vec2(2.0, 3.0);
+            m[column].x = 4.0;
+            add(m[2].wx);
+            color = m[1];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // `m[1].yz` is stored one component at a time into the second column
+    let main = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, function)| function.name.as_deref() == Some("main"))
+        .unwrap()
+        .1;
+    let stored: Vec<_> = main
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Statement::Store { pointer, .. } => match main.expressions[pointer] {
+                Expression::AccessIndex { base, index } => match main.expressions[base] {
+                    Expression::AccessIndex { index: column, .. } => Some((column, index)),
+                    _ => None,
+                },
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    assert_eq!(stored[..2], [(1, 1), (1, 2)]);
+}
+
+#[test]
+fn reflect_refract_operands() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in vec3 incident;
+        layout(location = 1) in vec3 normal;
+        layout(location = 0) out vec3 color;
+        void main() {
+            color = reflect(incident, normal) + refract(incident, normal, 0.75);
+            color += refract(incident, normal, 1);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec3 r = reflect(vec3(1.0), vec2(0.0, 1.0));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 96,
+                end: 110
+            },
+            "The normal must have the same type as the incident vector".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec3 r = refract(vec3(1.0), vec3(0.0, 1.0, 0.0), vec3(0.5));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 117,
+                end: 126
+            },
+            "eta must be a float scalar".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                ivec2 r = reflect(ivec2(1), ivec2(0, 1));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 86, end: 94 },
+            "reflect expects a float vector".into()
+        )
+    );
+}
+
+#[test]
+fn barrier_stages() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    // Memory barriers can still be used outside of compute shaders
+    parse_program(
+        r#"
+        #version 450
+        void main() {
+            memoryBarrier();
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                barrier();
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 68, end: 77 },
+            "\"barrier\" is only available in compute shaders".into()
+        )
+    );
+
+    // Only the entry points reaching the call are checked
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Compute);
+    entry_points.insert("frag".to_string(), ShaderStage::Fragment);
+
+    parse_program(
+        r#"
+        #version 450
+        void sync() {
+            barrier();
+        }
+        void main() {
+            sync();
+        }
+        void frag() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void sync() {
+                barrier();
+            }
+            void main() {}
+            void frag() {
+                sync();
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 68, end: 77 },
+            "\"barrier\" is only available in compute shaders".into()
+        )
+    );
+}
+
+#[test]
+fn warnings() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450 compatibility
+        layout(set = 0, binding = 0) uniform texture2D tex;
+        layout(set = 0, binding = 1) uniform sampler tex_sampler;
+        layout(location = 0) flat in int index;
+        layout(location = 0) out vec4 color;
+        void main() {
+            float unused = 1.0;
+            float scale = index;
+            color = texture2D(sampler2D(tex, tex_sampler), vec2(0.5)) * scale * 2;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(program.warnings.len(), 3);
+    assert_eq!(
+        program.warnings[0],
+        Warning::LossyConversion(
+            SourceMetadata {
+                start: 335,
+                end: 340
+            },
+            ScalarKind::Sint,
+            ScalarKind::Float
+        )
+    );
+    assert_eq!(
+        program.warnings[1],
+        Warning::Deprecated(
+            SourceMetadata {
+                start: 362,
+                end: 411
+            },
+            "texture2D".into()
+        )
+    );
+    assert_eq!(
+        program.warnings[2],
+        Warning::UnusedVariable(
+            SourceMetadata {
+                start: 295,
+                end: 307
+            },
+            "unused".into()
+        )
+    );
+}
+
+#[test]
+fn per_vertex_arrays() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                gl_Position = gl_in[0].gl_Position;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 82, end: 87 },
+            "\"gl_in\" is only available in geometry and tessellation shaders".into()
+        )
+    );
+}
+
+#[test]
+fn texture_gather_depth_compare() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let source = |call: &str| {
+        format!(
+            r#"
+            #version 450
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 o_color;
+            layout(set = 1, binding = 1) uniform texture2D tex;
+            layout(set = 1, binding = 2) uniform sampler tex_sampler;
+            layout(set = 1, binding = 3) uniform samplerShadow shadow_sampler;
+            void main() {{
+                o_color = {};
+            }}
+            "#,
+            call
+        )
+    };
+
+    assert_eq!(
+        parse_program(
+            &source("textureGather(sampler2D(tex, tex_sampler), v_uv, 0.5)"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 438,
+                end: 441
+            },
+            "The depth comparison form of textureGather needs a shadow sampler".into()
+        )
+    );
+
+    // The component select form is still parsed but gathers can't be lowered
+    assert_eq!(
+        parse_program(
+            &source("textureGather(sampler2D(tex, tex_sampler), v_uv, 1)"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::NotImplemented(
+            SourceMetadata {
+                start: 389,
+                end: 440
+            },
+            "texture gathering"
+        )
+    );
+
+    // The reference of the depth comparison form comes before the offset
+    for call in [
+        "textureGather(sampler2DShadow(tex, shadow_sampler), v_uv, 0.5)",
+        "textureGatherOffset(sampler2DShadow(tex, shadow_sampler), v_uv, 0.5, ivec2(1))",
+    ] {
+        assert!(matches!(
+            parse_program(&source(call), &entry_points).err().unwrap(),
+            ErrorKind::NotImplemented(_, "texture gathering")
+        ));
+    }
+}
+
+#[test]
+fn call_result_member_access() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        struct Data {
+            vec4 position;
+            float weights[2];
+        };
+        Data makeData() {
+            return Data(vec4(1.0), float[2](0.5, 0.5));
+        }
+        void main() {
+            float x = makeData().position.x;
+            vec2 xy = makeData().position.xy;
+            float w = makeData().weights[1];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+}
+
+#[test]
+fn clamp_operand_order() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int value;
+        layout(location = 0) out int color;
+        void main() {
+            color = clamp(value, 7, 3);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let constant = |expr: Option<crate::Handle<crate::Expression>>| match expr
+        .map(|expr| &main.expressions[expr])
+    {
+        Some(&crate::Expression::Constant(constant)) => {
+            match program.module.constants[constant].inner {
+                crate::ConstantInner::Scalar {
+                    value: crate::ScalarValue::Sint(value),
+                    ..
+                } => Some(value),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    // Even with the minimum above the maximum the operands aren't reordered
+    let clamps: Vec<_> = main
+        .expressions
+        .iter()
+        .filter_map(|(_, expr)| match *expr {
+            crate::Expression::Math {
+                fun: crate::MathFunction::Clamp,
+                arg,
+                arg1,
+                arg2,
+            } => Some((arg, constant(arg1), constant(arg2))),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(clamps.len(), 1);
+    assert!(matches!(
+        main.expressions[clamps[0].0],
+        crate::Expression::Load { .. }
+    ));
+    assert_eq!((clamps[0].1, clamps[0].2), (Some(7), Some(3)));
+}
+
+#[test]
+fn integer_abs_sign() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in ivec3 offsets;
+        layout(location = 0) out int color;
+        void main() {
+            int values[4] = int[4](1, 2, 3, 4);
+            ivec3 distance = abs(offsets);
+            ivec3 direction = sign(offsets);
+            color = values[abs(offsets.x)] + values[sign(offsets.y) + 1] + distance.z + direction.z;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                uint distance = abs(3u);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 88, end: 90 },
+            "\"abs\" expects a signed integer or float argument".into()
+        )
+    );
+}
+
+#[test]
+fn preprocessor_conditionals() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    // Only the function in the taken branch is declared
+    let functions = |condition: &str| {
+        parse_program(
+            &format!(
+                "#version 450\n#define A 2\n#define B 0\n#if {}\nvoid f() {{}}\n#endif\nvoid main() {{}}\n",
+                condition
+            ),
+            &entry_points,
+        )
+        .unwrap()
+        .module
+        .functions
+        .len()
+    };
+
+    assert_eq!(functions("A && !B"), 2);
+    assert_eq!(functions("A && B"), 1);
+    assert_eq!(functions("B || A > 1"), 2);
+    assert_eq!(functions("!A || B"), 1);
+    assert_eq!(functions("A == 2 && A != 3 && A >= 2 && A <= 2"), 2);
+    assert_eq!(functions("A < 2 || A > 2"), 1);
+
+    assert_eq!(
+        parse_program(
+            "#version 450\n#define SCALE 1.5\n#if SCALE > 1\nvoid main() {}\n#endif\n",
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 27, end: 30 },
+            "Preprocessor directives only take integers, not floats".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            "#version 450\nvoid main() {\n#if 1.0\n#endif\n}\n",
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 31, end: 34 },
+            "Preprocessor directives only take integers, not floats".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            "#version 450
+#line 1.0
+void main() {}
+",
+            &entry_points
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 19, end: 22 },
+            "Preprocessor directives only take integers, not floats".into()
+        )
+    );
+}
+
+#[test]
+fn prefix_increment_statements() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            int i = 0;
+            ++i;
+            --i;
+            for (int j = 0; j < 4; ++j) {
+                ++i;
+            }
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let mut validator = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    );
+    validator.validate(&program.module).unwrap();
+}
+
+#[test]
+fn unsized_array_parameters() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        float sum(float data[]);
+        void main() {
+            float a[3] = float[3](1.0, 2.0, 3.0);
+            float b[2] = float[2](4.0, 5.0);
+            float s = sum(a) + sum(b) + sum(a);
+        }
+        float sum(float data[]) {
+            return data[0] + data[1];
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // One version of `sum` for each array size
+    let sizes: Vec<_> = program
+        .module
+        .functions
+        .iter()
+        .filter(|&(_, function)| function.name.as_deref() == Some("sum"))
+        .map(
+            |(_, function)| match program.module.types[function.arguments[0].ty].inner {
+                crate::TypeInner::Array {
+                    size: crate::ArraySize::Constant(size),
+                    ..
+                } => program.module.constants[size].to_array_length(),
+                _ => None,
+            },
+        )
+        .collect();
+    assert_eq!(sizes, [Some(3), Some(2)]);
+
+    // Each call site calls the version of the size passed to it
+    let main = program
+        .module
+        .functions
+        .iter()
+        .find(|&(_, function)| function.name.as_deref() == Some("main"))
+        .unwrap()
+        .1;
+    let callees: Vec<_> = main
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            crate::Statement::Call { function, .. } => Some(function),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(callees.len(), 3);
+    assert_ne!(callees[0], callees[1]);
+    assert_eq!(callees[0], callees[2]);
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            float sum(float data[]) {
+                return data[0];
+            }
+            void main() {
+                int a[3] = int[3](1, 2, 3);
+                float s = sum(a);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 206,
+                end: 212
+            },
+            "Unknown function 'sum'".into()
+        )
+    );
+}
+
+#[test]
+fn entry_point_names() {
+    use super::{parse_str, Options};
+
+    let mut options = Options::default();
+    options
+        .entry_points
+        .insert("main".to_string(), ShaderStage::Vertex);
+    options
+        .entry_point_names
+        .insert("main".to_string(), "vs_main".to_string());
+
+    let module = parse_str(
+        r#"
+        #version 450
+        void main() {
+            gl_Position = vec4(1.0);
+        }
+        "#,
+        &options,
+    )
+    .unwrap();
+
+    let names: Vec<_> = module.entry_points.iter().map(|ep| &ep.name).collect();
+    assert_eq!(names, ["vs_main"]);
+
+    // Entry points that aren't renamed keep the name of their function
+    options.entry_point_names.clear();
+    let module = parse_str("#version 450\nvoid main() {}", &options).unwrap();
+    let names: Vec<_> = module.entry_points.iter().map(|ep| &ep.name).collect();
+    assert_eq!(names, ["main"]);
+}
+
+#[test]
+fn flat_integer_inputs() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    parse_program(
+        r#"
+        #version 450
+        layout(location = 0) flat in int index;
+        layout(location = 1) flat in dvec2 precise_uv;
+        layout(location = 2) in vec2 uv;
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) in int index;
+            void main() {
+                int i = index;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 66, end: 71 },
+            "Integer and double fragment inputs must be qualified as flat".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) smooth in dvec2 uv;
+            void main() {
+                dvec2 v = uv;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 75, end: 77 },
+            "Integer and double fragment inputs must be qualified as flat".into()
+        )
+    );
+
+    // Vertex inputs are never interpolated
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+    parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in ivec2 index;
+        void main() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    // With other stages inputs only belong to the fragment entry points
+    // reading them
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+    entry_points.insert("frag".to_string(), ShaderStage::Fragment);
+    parse_program(
+        r#"
+        #version 450
+        layout(location = 0) in ivec2 index;
+        void main() {
+            ivec2 i = index;
+        }
+        void frag() {}
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            layout(location = 0) in ivec2 index;
+            void main() {}
+            void frag() {
+                ivec2 i = index;
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 68, end: 73 },
+            "Integer and double fragment inputs must be qualified as flat".into()
+        )
+    );
+
+    // Even when the unused inputs are kept, as they default to flat
+    let source = r#"
+        #version 450
+        layout(location = 0) in ivec2 index;
+        void main() {
+            ivec2 i = index;
+        }
+        void frag() {}
+        "#;
+    let mut program = Program::new(&entry_points);
+    program.keep_unused_entry_args = true;
+    let defines = crate::FastHashMap::default();
+    let lex = Lexer::new(source, &defines);
+    parser::Parser::new(&mut program, lex).parse().unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+}
+
+#[test]
+fn pow_atan_operands() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            vec3 a = pow(vec3(2.0), vec3(0.5));
+            float b = atan(1.0, 2);
+            vec2 c = atan(vec2(1.0), ivec2(2));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec3 a = pow(vec3(2.0), vec2(0.5));
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 77,
+                end: 102
+            },
+            "\"pow\" expects both arguments to have the same type".into()
+        )
+    );
+
+    // Scalars aren't broadcast to the vector operand
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                vec2 a = atan(vec2(1.0), 2.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 77, end: 97 },
+            "\"atan\" expects both arguments to have the same type".into()
+        )
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            void main() {
+                float a = pow(true, 2.0);
+            }
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 82, end: 86 },
+            "\"pow\" expects float scalars or vectors".into()
+        )
+    );
+}
+
+#[test]
+fn block_member_interpolation() {
+    use crate::{Binding, Interpolation, Sampling};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) centroid in VertexData {
+            noperspective vec2 screen_uv;
+            flat int id;
+            sample vec4 color;
+        } v;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = v.color + vec4(v.screen_uv, float(v.id), 0.0);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let bindings: Vec<_> = program.module.entry_points[0]
+        .function
+        .arguments
+        .iter()
+        .map(|arg| arg.binding.clone())
+        .collect();
+
+    assert_eq!(
+        bindings,
+        [
+            Some(Binding::Location {
+                location: 0,
+                interpolation: Some(Interpolation::Linear),
+                sampling: Some(Sampling::Centroid),
+            }),
+            Some(Binding::Location {
+                location: 1,
+                interpolation: Some(Interpolation::Flat),
+                sampling: Some(Sampling::Centroid),
+            }),
+            Some(Binding::Location {
+                location: 2,
+                interpolation: Some(Interpolation::Perspective),
+                sampling: Some(Sampling::Sample),
+            }),
+        ]
+    );
+
+    assert_eq!(
+        parse_program(
+            r#"
+            #version 450
+            struct Data {
+                noperspective vec2 uv;
+            };
+            void main() {}
+            "#,
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata { start: 68, end: 81 },
+            "Interpolation and sampling qualifiers can only be used on members of in/out blocks"
+                .into()
+        )
+    );
+}
+
+#[test]
+fn sampler_comparison_mode() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let source = |call: &str| {
+        format!(
+            r#"
+            #version 450
+            layout(set = 0, binding = 0) uniform texture2D tex;
+            layout(set = 0, binding = 1) uniform sampler tex_sampler;
+            layout(set = 0, binding = 2) uniform samplerShadow shadow_sampler;
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 0) out vec4 o_color;
+            void main() {{
+                o_color = vec4({});
+            }}
+            "#,
+            call
+        )
+    };
+
+    parse_program(
+        &source("texture(sampler2D(tex, tex_sampler), v_uv)"),
+        &entry_points,
+    )
+    .unwrap();
+
+    assert_eq!(
+        parse_program(
+            &source("texture(sampler2D(tex, shadow_sampler), v_uv)"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 417,
+                end: 431
+            },
+            "\"sampler2D\" can't use a samplerShadow, it has no depth comparison form".into()
+        )
+    );
+    assert_eq!(
+        parse_program(
+            &source("texture(sampler2DShadow(tex, tex_sampler), vec3(v_uv, 0.5))"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 423,
+                end: 434
+            },
+            "\"sampler2DShadow\" needs a samplerShadow to do depth comparisons".into()
+        )
+    );
+
+    // Shadow samplers make their texture a depth texture
+    let program = parse_program(
+        &source("texture(sampler2DShadow(tex, shadow_sampler), vec3(v_uv, 0.5))"),
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    let tex = program
+        .module
+        .global_variables
+        .iter()
+        .find(|&(_, var)| var.name.as_deref() == Some("tex"))
+        .unwrap()
+        .1;
+    assert!(matches!(
+        program.module.types[tex.ty].inner,
+        crate::TypeInner::Image {
+            class: crate::ImageClass::Depth,
+            ..
+        }
+    ));
+
+    assert_eq!(
+        parse_program(
+            &source(
+                "texture(sampler2D(tex, tex_sampler), v_uv).x + \
+                 texture(sampler2DShadow(tex, shadow_sampler), vec3(v_uv, 0.5))"
+            ),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 465,
+                end: 468
+            },
+            "\"sampler2DShadow\" can't combine a texture used with a regular sampler".into()
+        )
+    );
+    assert_eq!(
+        parse_program(
+            &source(
+                "texture(sampler2DShadow(tex, shadow_sampler), vec3(v_uv, 0.5)) + \
+                 texture(sampler2D(tex, tex_sampler), v_uv).x"
+            ),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 477,
+                end: 480
+            },
+            "\"sampler2D\" can't combine a texture used with a shadow sampler".into()
+        )
+    );
+    assert_eq!(
+        parse_program(
+            &source("texture(sampler2DShadow(tex, shadow_sampler), v_uv)"),
+            &entry_points,
+        )
+        .err()
+        .unwrap(),
+        ErrorKind::SemanticError(
+            SourceMetadata {
+                start: 440,
+                end: 444
+            },
+            "Shadow coordinates must have 3 components".into()
+        )
+    );
+}
+
+#[test]
+fn uniform_defaults() {
+    use crate::{ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(set = 0, binding = 0) uniform float exposure = 1.5;
+        layout(set = 0, binding = 1) uniform float gamma;
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            o_color = vec4(pow(exposure, gamma));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (exposure, _) = program
+        .module
+        .global_variables
+        .iter()
+        .find(|&(_, var)| var.name.as_deref() == Some("exposure"))
+        .unwrap();
+    assert!(program.module.global_variables[exposure].init.is_none());
+    assert_eq!(program.uniform_defaults.len(), 1);
+    assert!(matches!(
+        program.module.constants[program.uniform_defaults[&exposure]].inner,
+        ConstantInner::Scalar {
+            value: ScalarValue::Float(value),
+            ..
+        } if value == 1.5
+    ));
+}
+
+#[test]
+fn comma_operator() {
+    use crate::{BinaryOperator, Expression, Statement};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float a;
+            vec2 v = vec2((a = 1.0, a + 1.0), 2.0);
+            int i, j;
+            for (i = 0, j = 2; i < j; i++) {}
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let function = &program.module.functions.iter().next().unwrap().1;
+    let stores: Vec<_> = function
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Statement::Store { value, .. } => Some(&function.expressions[value]),
+            _ => None,
+        })
+        .collect();
+
+    // `a = 1.0`, `v = ...`, `i = 0` and `j = 2` in that order
+    assert_eq!(stores.len(), 4);
+    assert!(matches!(*stores[0], Expression::Constant(_)));
+    assert!(matches!(
+        *stores[1],
+        Expression::Compose { ref components, .. } if components.len() == 2
+            && matches!(
+                function.expressions[components[0]],
+                Expression::Binary {
+                    op: BinaryOperator::Add,
+                    ..
+                }
+            )
+    ));
+    assert!(matches!(*stores[2], Expression::Constant(_)));
+    assert!(matches!(*stores[3], Expression::Constant(_)));
+
+    let mut validator = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    );
+    validator.validate(&program.module).unwrap();
+}
+
+#[test]
+fn builtin_component_access() {
+    use crate::{Expression, ScalarKind, TypeInner};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Fragment);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        layout(location = 0) out vec4 o_color;
+        void main() {
+            float depth = gl_FragCoord.z;
+            float inv_w = gl_FragCoord.w;
+            vec2 screen = gl_FragCoord.xy;
+            float near = 0.1;
+            float linear_depth = near / (1.0 - depth);
+            o_color = vec4(screen * inv_w, linear_depth, gl_FragCoord[2]);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let (_, function) = program.module.functions.iter().next().unwrap();
+    let accesses: Vec<_> = function
+        .expressions
+        .iter()
+        .filter(|&(_, expr)| matches!(*expr, Expression::AccessIndex { .. }))
+        .map(|(handle, _)| handle)
+        .collect();
+    // `.z`, `.w` and `[2]` are float accesses, `.xy` is a swizzle
+    assert_eq!(accesses.len(), 3);
+    assert!(function
+        .expressions
+        .iter()
+        .any(|(_, expr)| matches!(*expr, Expression::Swizzle { .. })));
+
+    let mut typifier = crate::front::Typifier::new();
+    let resolve_ctx = crate::proc::ResolveContext {
+        constants: &program.module.constants,
+        types: &program.module.types,
+        global_vars: &program.module.global_variables,
+        local_vars: &function.local_variables,
+        functions: &program.module.functions,
+        arguments: &function.arguments,
+    };
+    for &access in accesses.iter() {
+        typifier
+            .grow(access, &function.expressions, &resolve_ctx)
+            .unwrap();
+        assert!(matches!(
+            *typifier.get(access, &program.module.types),
+            TypeInner::Scalar {
+                kind: ScalarKind::Float,
+                width: 4
+            } | TypeInner::ValuePointer {
+                size: None,
+                kind: ScalarKind::Float,
+                width: 4,
+                ..
+            }
+        ));
+    }
+
+    let mut validator = crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    );
+    validator.validate(&program.module).unwrap();
+}
+
+#[test]
+fn constant_splat_folding() {
+    use crate::{ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float x = 1.0;
+            vec3 a = vec3(50.0 * 0.33);
+            vec3 b;
+            b = vec3(2.0 * 3.0 * x);
+            float c = 4.0 * 5.0 * x;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    let float_constant = |v: f64| ConstantInner::Scalar {
+        width: 4,
+        value: ScalarValue::Float(v),
+    };
+    let constants = &program.module.constants;
+
+    // Splatted values are rounded to the width of the vector
+    assert!(constants
+        .iter()
+        .any(|(_, c)| c.inner == float_constant(16.5)));
+    // Folds of non constant values don't leave any constants behind
+    assert!(!constants
+        .iter()
+        .any(|(_, c)| c.inner == float_constant(6.0) || c.inner == float_constant(20.0)));
+}
+
+#[test]
+fn forwarded_unsized_array_parameters() {
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        float first(const float data[]) {
+            return data[0];
+        }
+        float second(float data[]) {
+            return data[1];
+        }
+        float sum(float data[], out float head) {
+            head = first(data);
+            return head + second(data);
+        }
+        void main() {
+            float a[3] = float[3](1.0, 2.0, 3.0);
+            float b[2] = float[2](4.0, 5.0);
+            float head;
+            float s = first(a) + sum(a, head) + sum(b, head);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    // The functions called by `sum` have a version for each size it's called with
+    let sizes = |name| {
+        let mut sizes: Vec<_> = program
+            .module
+            .functions
+            .iter()
+            .filter(|&(_, function)| function.name.as_deref() == Some(name))
+            .map(
+                |(_, function)| match program.module.types[function.arguments[0].ty].inner {
+                    crate::TypeInner::Array {
+                        size: crate::ArraySize::Constant(size),
+                        ..
+                    } => program.module.constants[size].to_array_length(),
+                    _ => None,
+                },
+            )
+            .collect();
+        sizes.sort_unstable();
+        sizes
+    };
+    assert_eq!(sizes("first"), [Some(2), Some(3)]);
+    assert_eq!(sizes("second"), [Some(2), Some(3)]);
+    assert_eq!(sizes("sum"), [Some(2), Some(3)]);
+
+    // The versions made for the calls of `sum` come after it in the arena
+    // until the functions are sorted so that callees come first, and each
+    // version of `sum` forwards its array to the versions of the same size
+    let calls = |function: &crate::Function| -> Vec<_> {
+        function
+            .body
+            .iter()
+            .filter_map(|stmt| match *stmt {
+                crate::Statement::Call { function, .. } => Some(function),
+                _ => None,
+            })
+            .collect()
+    };
+    for (handle, function) in program.module.functions.iter() {
+        for callee in calls(function) {
+            assert!(callee.index() < handle.index());
+            if function.name.as_deref() == Some("sum") {
+                assert_eq!(
+                    program.module.functions[callee].arguments[0].ty,
+                    function.arguments[0].ty
+                );
+            }
+        }
+    }
+
+    // Recursion isn't allowed but specializing it must still end, with one
+    // version of each function for each size
+    let program = parse_program(
+        r#"
+        #version 450
+        float odd(float data[], int n);
+        float even(float data[], int n) {
+            return n == 0 ? data[0] : odd(data, n - 1);
+        }
+        float odd(float data[], int n) {
+            return n == 0 ? data[1] : even(data, n - 1);
+        }
+        void main() {
+            float a[3] = float[3](1.0, 2.0, 3.0);
+            float b[2] = float[2](4.0, 5.0);
+            float s = even(a, 2) + even(b, 1);
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    for name in ["even", "odd"].iter() {
+        let versions: Vec<_> = program
+            .module
+            .functions
+            .iter()
+            .filter(|&(_, function)| function.name.as_deref() == Some(name))
+            .map(|(_, function)| function.arguments[0].ty)
+            .collect();
+        assert_eq!(versions.len(), 2);
+        assert_ne!(versions[0], versions[1]);
+    }
+}
+
+#[test]
+fn expression_statement_emits() {
+    use crate::{BinaryOperator, Expression, Statement};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float a = 1.0;
+            float b = 2.0;
+            a + b;
+            a * b;
+            b = a - b;
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    crate::valid::Validator::new(
+        crate::valid::ValidationFlags::all(),
+        crate::valid::Capabilities::all(),
+    )
+    .validate(&program.module)
+    .unwrap();
+
+    let main = &program.module.functions.iter().next().unwrap().1;
+    let binary = |op| {
+        main.expressions
+            .iter()
+            .find(|&(_, expr)| matches!(*expr, Expression::Binary { op: expr_op, .. } if expr_op == op))
+            .unwrap()
+            .0
+    };
+    let emits: Vec<Vec<_>> = main
+        .body
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Statement::Emit(ref range) => Some(range.clone().collect()),
+            _ => None,
+        })
+        .collect();
+    let emit_of = |expr| emits.iter().position(|range| range.contains(&expr));
+
+    // Side effect free expression statements are emitted on their own, along
+    // with the loads of their operands
+    let add = binary(BinaryOperator::Add);
+    let multiply = binary(BinaryOperator::Multiply);
+    let subtract = binary(BinaryOperator::Subtract);
+    assert!(emit_of(add).is_some());
+    assert_ne!(emit_of(add), emit_of(multiply));
+    assert_ne!(emit_of(multiply), emit_of(subtract));
+    for range in emits.iter().filter(|range| range.contains(&add)) {
+        assert_eq!(range.len(), 3);
+    }
+}
+
+#[test]
+fn determinant_of_matrix_from_scalars() {
+    use crate::{ConstantInner, ScalarValue};
+
+    let mut entry_points = crate::FastHashMap::default();
+    entry_points.insert("main".to_string(), ShaderStage::Vertex);
+
+    let program = parse_program(
+        r#"
+        #version 450
+        void main() {
+            float d = determinant(mat2(1.0, 2.0, 3.0, 4.0));
+        }
+        "#,
+        &entry_points,
+    )
+    .unwrap();
+
+    assert!(program.module.constants.iter().any(|(_, constant)| {
+        constant.inner
+            == ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(-2.0),
+            }
+    }));
 }
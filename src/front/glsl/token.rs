@@ -55,6 +55,7 @@ pub enum TokenValue {
     InOut,
     Uniform,
     Buffer,
+    Shared,
     Const,
     Interpolation(Interpolation),
     Sampling(Sampling),
@@ -1,4 +1,4 @@
-use crate::{ScalarKind, Type, TypeInner, VectorSize};
+use crate::{ImageClass, ImageDimension, ScalarKind, StorageFormat, Type, TypeInner, VectorSize};
 
 pub fn parse_type(type_name: &str) -> Option<Type> {
     match type_name {
@@ -37,21 +37,14 @@ pub fn parse_type(type_name: &str) -> Option<Type> {
                 width: 4,
             },
         }),
-        "texture2D" => Some(Type {
-            name: None,
-            inner: TypeInner::Image {
-                dim: crate::ImageDimension::D2,
-                arrayed: false,
-                class: crate::ImageClass::Sampled {
-                    kind: ScalarKind::Float,
-                    multi: false,
-                },
-            },
-        }),
         "sampler" => Some(Type {
             name: None,
             inner: TypeInner::Sampler { comparison: false },
         }),
+        "samplerShadow" => Some(Type {
+            name: None,
+            inner: TypeInner::Sampler { comparison: true },
+        }),
         word => {
             fn kind_width_parse(ty: &str) -> Option<(ScalarKind, u8)> {
                 Some(match ty {
@@ -114,7 +107,152 @@ pub fn parse_type(type_name: &str) -> Option<Type> {
                 })
             };
 
-            vec_parse(word).or_else(|| mat_parse(word))
+            let texture_parse = |word: &str| {
+                let mut iter = word.split("texture");
+
+                let texture_kind = |ty| {
+                    Some(match ty {
+                        "" => ScalarKind::Float,
+                        "i" => ScalarKind::Sint,
+                        "u" => ScalarKind::Uint,
+                        _ => return None,
+                    })
+                };
+
+                let kind = iter.next()?;
+                let size = iter.next()?;
+                let kind = texture_kind(kind)?;
+
+                let sampled = |multi| ImageClass::Sampled { kind, multi };
+
+                let (dim, arrayed, class) = match size {
+                    "1D" => (ImageDimension::D1, false, sampled(false)),
+                    "1DArray" => (ImageDimension::D1, true, sampled(false)),
+                    "2D" => (ImageDimension::D2, false, sampled(false)),
+                    "2DArray" => (ImageDimension::D2, true, sampled(false)),
+                    "2DMS" => (ImageDimension::D2, false, sampled(true)),
+                    "2DMSArray" => (ImageDimension::D2, true, sampled(true)),
+                    "3D" => (ImageDimension::D3, false, sampled(false)),
+                    "Cube" => (ImageDimension::Cube, false, sampled(false)),
+                    "CubeArray" => (ImageDimension::Cube, true, sampled(false)),
+                    _ => return None,
+                };
+
+                Some(Type {
+                    name: None,
+                    inner: TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    },
+                })
+            };
+
+            // Input attachments are represented as 2D images since they are
+            // read at the fragment position
+            let subpass_parse = |word: &str| {
+                let mut iter = word.split("subpassInput");
+
+                let kind = match iter.next()? {
+                    "" => ScalarKind::Float,
+                    "i" => ScalarKind::Sint,
+                    "u" => ScalarKind::Uint,
+                    _ => return None,
+                };
+                let multi = match iter.next()? {
+                    "" => false,
+                    "MS" => true,
+                    _ => return None,
+                };
+
+                Some(Type {
+                    name: None,
+                    inner: TypeInner::Image {
+                        dim: ImageDimension::D2,
+                        arrayed: false,
+                        class: ImageClass::Sampled { kind, multi },
+                    },
+                })
+            };
+
+            // The format is a placeholder with the right scalar kind, the
+            // declaration replaces it with the one from its format qualifier
+            let image_parse = |word: &str| {
+                let mut iter = word.split("image");
+
+                let format = match iter.next()? {
+                    "" => StorageFormat::Rgba32Float,
+                    "i" => StorageFormat::Rgba32Sint,
+                    "u" => StorageFormat::Rgba32Uint,
+                    _ => return None,
+                };
+
+                let (dim, arrayed) = match iter.next()? {
+                    "1D" => (ImageDimension::D1, false),
+                    "1DArray" => (ImageDimension::D1, true),
+                    "2D" => (ImageDimension::D2, false),
+                    "2DArray" => (ImageDimension::D2, true),
+                    "3D" => (ImageDimension::D3, false),
+                    "Cube" => (ImageDimension::Cube, false),
+                    "CubeArray" => (ImageDimension::Cube, true),
+                    _ => return None,
+                };
+
+                Some(Type {
+                    name: None,
+                    inner: TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class: ImageClass::Storage(format),
+                    },
+                })
+            };
+
+            vec_parse(word)
+                .or_else(|| mat_parse(word))
+                .or_else(|| texture_parse(word))
+                .or_else(|| subpass_parse(word))
+                .or_else(|| image_parse(word))
         }
     }
 }
+
+pub fn parse_storage_format(format: &str) -> Option<StorageFormat> {
+    use StorageFormat as Sf;
+
+    Some(match format {
+        "r8" => Sf::R8Unorm,
+        "r8_snorm" => Sf::R8Snorm,
+        "r8ui" => Sf::R8Uint,
+        "r8i" => Sf::R8Sint,
+        "r16ui" => Sf::R16Uint,
+        "r16i" => Sf::R16Sint,
+        "r16f" => Sf::R16Float,
+        "rg8" => Sf::Rg8Unorm,
+        "rg8_snorm" => Sf::Rg8Snorm,
+        "rg8ui" => Sf::Rg8Uint,
+        "rg8i" => Sf::Rg8Sint,
+        "r32ui" => Sf::R32Uint,
+        "r32i" => Sf::R32Sint,
+        "r32f" => Sf::R32Float,
+        "rg16ui" => Sf::Rg16Uint,
+        "rg16i" => Sf::Rg16Sint,
+        "rg16f" => Sf::Rg16Float,
+        "rgba8" => Sf::Rgba8Unorm,
+        "rgba8_snorm" => Sf::Rgba8Snorm,
+        "rgba8ui" => Sf::Rgba8Uint,
+        "rgba8i" => Sf::Rgba8Sint,
+        "rgb10_a2" => Sf::Rgb10a2Unorm,
+        "r11f_g11f_b10f" => Sf::Rg11b10Float,
+        "rg32ui" => Sf::Rg32Uint,
+        "rg32i" => Sf::Rg32Sint,
+        "rg32f" => Sf::Rg32Float,
+        "rgba16ui" => Sf::Rgba16Uint,
+        "rgba16i" => Sf::Rgba16Sint,
+        "rgba16f" => Sf::Rgba16Float,
+        "rgba32ui" => Sf::Rgba32Uint,
+        "rgba32i" => Sf::Rgba32Sint,
+        "rgba32f" => Sf::Rgba32Float,
+        _ => return None,
+    })
+}
@@ -1,7 +1,7 @@
 use crate::{
-    Binding, Block, BuiltIn, Constant, Expression, GlobalVariable, Handle, ImageClass,
-    Interpolation, LocalVariable, ScalarKind, StorageAccess, StorageClass, SwizzleComponent, Type,
-    TypeInner, VectorSize,
+    ArraySize, Binding, Block, BuiltIn, Constant, ConstantInner, Expression, GlobalVariable,
+    Handle, ImageClass, Interpolation, LocalVariable, ScalarKind, ScalarValue, ShaderStage,
+    StorageAccess, StorageClass, StructMember, SwizzleComponent, Type, TypeInner, VectorSize,
 };
 
 use super::ast::*;
@@ -26,6 +26,10 @@ pub struct VarDeclaration<'a> {
     pub meta: SourceMetadata,
 }
 
+/// Minimum value of `gl_MaxClipDistances`, used as the size of
+/// `gl_ClipDistance` and `gl_CullDistance` when they aren't redeclared
+const MAX_CLIP_DISTANCES: u32 = 8;
+
 pub enum GlobalOrConstant {
     Global(Handle<GlobalVariable>),
     Constant(Handle<Constant>),
@@ -37,6 +41,7 @@ impl Program<'_> {
         ctx: &mut Context,
         body: &mut Block,
         name: &str,
+        meta: SourceMetadata,
     ) -> Result<Option<VariableReference>, ErrorKind> {
         if let Some(local_var) = ctx.lookup_local_var(name) {
             return Ok(Some(local_var));
@@ -45,6 +50,33 @@ impl Program<'_> {
             return Ok(Some(global_var));
         }
 
+        // Which entry points reach the single stage builtins is only known
+        // once they are added, the builtin is the next entry argument
+        let stage = match name {
+            "gl_VertexIndex" | "gl_InstanceIndex" | "gl_VertexID" | "gl_InstanceID" => {
+                Some(ShaderStage::Vertex)
+            }
+            "gl_PrimitiveID" => Some(ShaderStage::Fragment),
+            _ => None,
+        };
+        if let Some(stage) = stage {
+            self.stage_only_inputs
+                .push((self.entry_args.len(), name.into(), stage, meta));
+        }
+
+        // Clip and cull distances that weren't redeclared with an explicit size
+        // are zero initialized so that the unwritten distances never clip
+        let distances = match name {
+            "gl_ClipDistance" | "gl_CullDistance" => Some(self.add_distances_init()),
+            _ => None,
+        };
+        let init = distances.as_ref().map(|&(_, init)| init);
+
+        let sample_mask = match name {
+            "gl_SampleMask" | "gl_SampleMaskIn" => Some(self.add_sample_mask_type()),
+            _ => None,
+        };
+
         let mut add_builtin = |inner, builtin, mutable, prologue| {
             let ty = self
                 .module
@@ -56,13 +88,17 @@ impl Program<'_> {
                 class: StorageClass::Private,
                 binding: None,
                 ty,
-                init: None,
+                init,
                 storage_access: StorageAccess::empty(),
             });
 
             let idx = self.entry_args.len();
             self.entry_args.push(EntryArg {
-                name: None,
+                name: if self.preserve_names {
+                    Some(name.into())
+                } else {
+                    None
+                },
                 binding: Binding::BuiltIn(builtin),
                 handle,
                 prologue,
@@ -102,7 +138,47 @@ impl Program<'_> {
                 true,
                 PrologueStage::FRAGMENT,
             ),
-            "gl_VertexIndex" => add_builtin(
+            "gl_Layer" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::Layer,
+                true,
+                PrologueStage::FRAGMENT,
+            ),
+            "gl_ViewportIndex" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::ViewportIndex,
+                true,
+                PrologueStage::FRAGMENT,
+            ),
+            "gl_ClipDistance" | "gl_CullDistance" => {
+                let (inner, _) = distances.unwrap();
+                add_builtin(
+                    inner,
+                    match name {
+                        "gl_ClipDistance" => BuiltIn::ClipDistance,
+                        _ => BuiltIn::CullDistance,
+                    },
+                    true,
+                    PrologueStage::empty(),
+                )
+            }
+            "gl_FragCoord" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Quad,
+                    kind: ScalarKind::Float,
+                    width: 4,
+                },
+                BuiltIn::Position,
+                false,
+                PrologueStage::FRAGMENT,
+            ),
+            "gl_VertexIndex" | "gl_VertexID" => add_builtin(
                 TypeInner::Scalar {
                     kind: ScalarKind::Uint,
                     width: 4,
@@ -111,7 +187,7 @@ impl Program<'_> {
                 false,
                 PrologueStage::VERTEX,
             ),
-            "gl_InstanceIndex" => add_builtin(
+            "gl_InstanceIndex" | "gl_InstanceID" => add_builtin(
                 TypeInner::Scalar {
                     kind: ScalarKind::Uint,
                     width: 4,
@@ -130,6 +206,45 @@ impl Program<'_> {
                 false,
                 PrologueStage::COMPUTE,
             ),
+            "gl_LocalInvocationID" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Tri,
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::LocalInvocationId,
+                false,
+                PrologueStage::COMPUTE,
+            ),
+            "gl_LocalInvocationIndex" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::LocalInvocationIndex,
+                false,
+                PrologueStage::COMPUTE,
+            ),
+            "gl_WorkGroupID" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Tri,
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::WorkGroupId,
+                false,
+                PrologueStage::COMPUTE,
+            ),
+            "gl_NumWorkGroups" => add_builtin(
+                TypeInner::Vector {
+                    size: VectorSize::Tri,
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::NumWorkGroups,
+                false,
+                PrologueStage::COMPUTE,
+            ),
             "gl_FrontFacing" => add_builtin(
                 TypeInner::Scalar {
                     kind: ScalarKind::Bool,
@@ -139,10 +254,125 @@ impl Program<'_> {
                 false,
                 PrologueStage::FRAGMENT,
             ),
+            "gl_SampleID" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::SampleIndex,
+                false,
+                PrologueStage::FRAGMENT,
+            ),
+            "gl_SampleMask" => add_builtin(
+                sample_mask.unwrap(),
+                BuiltIn::SampleMask,
+                true,
+                PrologueStage::empty(),
+            ),
+            "gl_SampleMaskIn" => add_builtin(
+                sample_mask.unwrap(),
+                BuiltIn::SampleMask,
+                false,
+                PrologueStage::FRAGMENT,
+            ),
+            "gl_PrimitiveID" => add_builtin(
+                TypeInner::Scalar {
+                    kind: ScalarKind::Uint,
+                    width: 4,
+                },
+                BuiltIn::PrimitiveIndex,
+                false,
+                PrologueStage::FRAGMENT,
+            ),
+            // The IR has no geometry or tessellation stages
+            "gl_in" | "gl_out" => Err(ErrorKind::SemanticError(
+                meta,
+                format!(
+                    "\"{}\" is only available in geometry and tessellation shaders",
+                    name
+                )
+                .into(),
+            )),
             _ => Ok(None),
         }
     }
 
+    /// Adds a zero initialized array of [`MAX_CLIP_DISTANCES`] floats to be
+    /// used as the initializer of `gl_ClipDistance` or `gl_CullDistance`
+    fn add_distances_init(&mut self) -> (TypeInner, Handle<Constant>) {
+        let base = self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Scalar {
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
+        let size = self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Sint(MAX_CLIP_DISTANCES as i64),
+            },
+        });
+        let stride = self.module.types[base].inner.span(&self.module.constants);
+        let array = || TypeInner::Array {
+            base,
+            size: ArraySize::Constant(size),
+            stride,
+        };
+        let ty = self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: array(),
+        });
+
+        let zero = self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(0.0),
+            },
+        });
+        let init = self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty,
+                components: vec![zero; MAX_CLIP_DISTANCES as usize],
+            },
+        });
+
+        (array(), init)
+    }
+
+    /// Returns the type of `gl_SampleMask` and `gl_SampleMaskIn`, in GLSL they
+    /// are arrays of signed integers but the IR only supports the first 32
+    /// samples so a single element is used
+    fn add_sample_mask_type(&mut self) -> TypeInner {
+        let base = self.module.types.fetch_or_append(Type {
+            name: None,
+            inner: TypeInner::Scalar {
+                kind: ScalarKind::Sint,
+                width: 4,
+            },
+        });
+        let size = self.module.constants.fetch_or_append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Sint(1),
+            },
+        });
+
+        TypeInner::Array {
+            base,
+            size: ArraySize::Constant(size),
+            stride: self.module.types[base].inner.span(&self.module.constants),
+        }
+    }
+
     pub fn field_selection(
         &mut self,
         ctx: &mut Context,
@@ -152,8 +382,19 @@ impl Program<'_> {
         name: &str,
         meta: SourceMetadata,
     ) -> Result<Handle<Expression>, ErrorKind> {
+        let value_pointer;
         let (ty, is_pointer) = match *self.resolve_type(ctx, expression, meta)? {
             TypeInner::Pointer { base, .. } => (&self.module.types[base].inner, true),
+            // Matrix columns behind a pointer, like `m[1]` in `m[1].yz = v`
+            TypeInner::ValuePointer {
+                size, kind, width, ..
+            } => {
+                value_pointer = match size {
+                    Some(size) => TypeInner::Vector { size, kind, width },
+                    None => TypeInner::Scalar { kind, width },
+                };
+                (&value_pointer, true)
+            }
             ref ty => (ty, false),
         };
         match *ty {
@@ -195,7 +436,7 @@ impl Program<'_> {
                             return Err(ErrorKind::SemanticError(
                                 meta,
                                 format!(
-                                    "swizzle cannot have duplicate components in left-hand-side expression for \"{:?}\"",
+                                    "swizzle cannot have duplicate components in left-hand-side expression for \"{}\"",
                                     name
                                 )
                                 .into(),
@@ -279,6 +520,53 @@ impl Program<'_> {
                     ))
                 }
             }
+            // Scalars can be swizzled as if they were single component vectors (GLSL 4.20),
+            // so a `.x`/`.r`/`.s` selection yields the scalar itself and repeating the
+            // component (`.xxx`) splats it into a vector
+            TypeInner::Scalar { .. } => {
+                let first_component_only =
+                    |comp: char| !name.is_empty() && name.chars().all(|c| c == comp);
+                if !(first_component_only('x')
+                    || first_component_only('r')
+                    || first_component_only('s'))
+                {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!("Invalid swizzle for scalar \"{}\"", name).into(),
+                    ));
+                }
+
+                let size = match name.len() {
+                    1 => return Ok(expression),
+                    2 => VectorSize::Bi,
+                    3 => VectorSize::Tri,
+                    4 => VectorSize::Quad,
+                    _ => {
+                        return Err(ErrorKind::SemanticError(
+                            meta,
+                            format!("Bad swizzle size for \"{:?}\"", name).into(),
+                        ));
+                    }
+                };
+
+                if lhs {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        format!(
+                            "swizzle cannot have duplicate components in left-hand-side expression for \"{}\"",
+                            name
+                        )
+                        .into(),
+                    ));
+                }
+
+                let mut value = expression;
+                if is_pointer {
+                    value = ctx.add_expression(Expression::Load { pointer: value }, body);
+                }
+
+                Ok(ctx.add_expression(Expression::Splat { size, value }, body))
+            }
             _ => Err(ErrorKind::SemanticError(
                 meta,
                 format!("Can't lookup field on this type \"{}\"", name).into(),
@@ -303,6 +591,7 @@ impl Program<'_> {
         let mut sampling = None;
         let mut layout = None;
         let mut precision = None;
+        let mut format = None;
 
         for &(ref qualifier, meta) in qualifiers {
             match *qualifier {
@@ -357,6 +646,12 @@ impl Program<'_> {
                     meta,
                     "Cannot use more than one precision qualifier per declaration"
                 ),
+                TypeQualifier::StorageFormat(f) => qualifier_arm!(
+                    f,
+                    format,
+                    meta,
+                    "Cannot use more than one format qualifier per declaration"
+                ),
                 _ => {
                     return Err(ErrorKind::SemanticError(
                         meta,
@@ -366,6 +661,44 @@ impl Program<'_> {
             }
         }
 
+        let ty = match self.module.types[ty].inner {
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class: ImageClass::Storage(placeholder),
+            } => {
+                let format = format.ok_or_else(|| {
+                    ErrorKind::SemanticError(
+                        meta,
+                        "Storage images require a format qualifier".into(),
+                    )
+                })?;
+
+                if ScalarKind::from(format) != ScalarKind::from(placeholder) {
+                    return Err(ErrorKind::SemanticError(
+                        meta,
+                        "Format qualifier doesn't match the image type".into(),
+                    ));
+                }
+
+                self.module.types.fetch_or_append(Type {
+                    name: None,
+                    inner: TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class: ImageClass::Storage(format),
+                    },
+                })
+            }
+            _ if format.is_some() => {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    "Format qualifiers can only be used with storage images".into(),
+                ))
+            }
+            _ => ty,
+        };
+
         if binding.is_some() && storage != StorageQualifier::StorageClass(StorageClass::Uniform) {
             match storage {
                 StorageQualifier::StorageClass(StorageClass::PushConstant)
@@ -387,6 +720,14 @@ impl Program<'_> {
             ));
         }
 
+        if let Some(builtin) = match name.as_deref() {
+            Some("gl_ClipDistance") => Some(BuiltIn::ClipDistance),
+            Some("gl_CullDistance") => Some(BuiltIn::CullDistance),
+            _ => None,
+        } {
+            return self.redeclare_distances(builtin, name.unwrap(), storage, ty, meta);
+        }
+
         if let Some(location) = location {
             let input = storage == StorageQualifier::Input;
             let prologue = if input {
@@ -394,12 +735,29 @@ impl Program<'_> {
             } else {
                 PrologueStage::empty()
             };
-            let interpolation = self.module.types[ty].inner.scalar_kind().map(|kind| {
-                if let ScalarKind::Float = kind {
-                    Interpolation::Perspective
-                } else {
-                    Interpolation::Flat
+
+            // Integers and doubles can't be interpolated so fragment inputs
+            // of those types must be explicitly flat, which entry points the
+            // input belongs to is only known once they are added
+            let interpolable = match self.module.types[ty].inner {
+                TypeInner::Scalar { kind, width } | TypeInner::Vector { kind, width, .. } => {
+                    kind == ScalarKind::Float && width != 8
                 }
+                TypeInner::Matrix { width, .. } => width != 8,
+                _ => true,
+            };
+            if input && !interpolable && interpolation != Some(Interpolation::Flat) {
+                self.non_flat_inputs.push((self.entry_args.len(), meta));
+            }
+
+            let interpolation = interpolation.or_else(|| {
+                self.module.types[ty].inner.scalar_kind().map(|kind| {
+                    if let ScalarKind::Float = kind {
+                        Interpolation::Perspective
+                    } else {
+                        Interpolation::Flat
+                    }
+                })
             });
 
             let handle = self.module.global_variables.append(GlobalVariable {
@@ -452,6 +810,13 @@ impl Program<'_> {
             return Ok(GlobalOrConstant::Constant(init));
         }
 
+        if storage == StorageQualifier::StorageClass(StorageClass::WorkGroup) && init.is_some() {
+            return Err(ErrorKind::SemanticError(
+                meta,
+                "shared variables can't have an initializer".into(),
+            ));
+        }
+
         let (class, storage_access) = match self.module.types[ty].inner {
             TypeInner::Image { class, .. } => (
                 StorageClass::Handle,
@@ -479,6 +844,13 @@ impl Program<'_> {
             }
         };
 
+        // Uniforms are set by the host, so their initializer is only the
+        // default value it's reflected as and not part of the variable
+        let (init, default) = match class {
+            StorageClass::Uniform => (None, init),
+            _ => (init, None),
+        };
+
         let handle = self.module.global_variables.append(GlobalVariable {
             name: name.clone(),
             class,
@@ -488,6 +860,10 @@ impl Program<'_> {
             storage_access,
         });
 
+        if let Some(default) = default {
+            self.uniform_defaults.insert(handle, default);
+        }
+
         if let Some(name) = name {
             self.global_variables.push((
                 name,
@@ -502,11 +878,166 @@ impl Program<'_> {
         Ok(GlobalOrConstant::Global(handle))
     }
 
+    /// Expands an `in` or `out` interface block into one global per member,
+    /// members are assigned consecutive locations starting at the block's
+    /// location and named `instance.member` if the block has an instance name,
+    /// the interpolation and sampling qualifiers of a member override the
+    /// ones of the block
+    pub fn add_interface_block(
+        &mut self,
+        qualifiers: &[(TypeQualifier, SourceMetadata)],
+        members: Vec<(StructMember, Vec<(TypeQualifier, SourceMetadata)>)>,
+        instance: Option<String>,
+        meta: SourceMetadata,
+    ) -> Result<(), ErrorKind> {
+        let mut location = None;
+        let mut member_qualifiers = Vec::with_capacity(qualifiers.len());
+
+        for &(ref qualifier, meta) in qualifiers {
+            match *qualifier {
+                TypeQualifier::Location(l) => qualifier_arm!(
+                    l,
+                    location,
+                    meta,
+                    "Cannot use more than one binding per declaration"
+                ),
+                _ => member_qualifiers.push((qualifier.clone(), meta)),
+            }
+        }
+
+        let mut location = location.ok_or_else(|| {
+            ErrorKind::SemanticError(meta, "Interface blocks require a location".into())
+        })?;
+
+        for (member, own_qualifiers) in members {
+            let name = match (instance.as_ref(), member.name) {
+                (Some(instance), Some(name)) => Some(format!("{}.{}", instance, name)),
+                (_, name) => name,
+            };
+
+            let overridden = |qualifier: &TypeQualifier| {
+                own_qualifiers.iter().any(|own| {
+                    matches!(
+                        (&own.0, qualifier),
+                        (
+                            &TypeQualifier::Interpolation(_),
+                            &TypeQualifier::Interpolation(_)
+                        ) | (&TypeQualifier::Sampling(_), &TypeQualifier::Sampling(_))
+                    )
+                })
+            };
+            let mut qualifiers: Vec<_> = member_qualifiers
+                .iter()
+                .filter(|qualifier| !overridden(&qualifier.0))
+                .cloned()
+                .chain(own_qualifiers.iter().cloned())
+                .collect();
+            qualifiers.push((TypeQualifier::Location(location), meta));
+
+            self.add_global_var(VarDeclaration {
+                qualifiers: &qualifiers,
+                ty: member.ty,
+                name,
+                init: None,
+                meta,
+            })?;
+
+            location += self.location_count(member.ty);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of consecutive locations used by a varying of type `ty`
+    fn location_count(&self, ty: Handle<Type>) -> u32 {
+        match self.module.types[ty].inner {
+            TypeInner::Matrix { columns, .. } => columns as u32,
+            TypeInner::Array {
+                base,
+                size: ArraySize::Constant(constant),
+                ..
+            } => {
+                let len = self.module.constants[constant]
+                    .to_array_length()
+                    .unwrap_or(1);
+                len * self.location_count(base)
+            }
+            _ => 1,
+        }
+    }
+
+    /// Handles a redeclaration of `gl_ClipDistance` or `gl_CullDistance`
+    /// which sets the number of distances that are written
+    fn redeclare_distances(
+        &mut self,
+        builtin: BuiltIn,
+        name: String,
+        storage: StorageQualifier,
+        ty: Handle<Type>,
+        meta: SourceMetadata,
+    ) -> Result<GlobalOrConstant, ErrorKind> {
+        if storage != StorageQualifier::Output {
+            return Err(ErrorKind::SemanticError(
+                meta,
+                format!("\"{}\" can only be redeclared as an output", name).into(),
+            ));
+        }
+
+        match self.module.types[ty].inner {
+            TypeInner::Array {
+                base,
+                size: ArraySize::Constant(_),
+                ..
+            } if self.module.types[base].inner
+                == (TypeInner::Scalar {
+                    kind: ScalarKind::Float,
+                    width: 4,
+                }) => {}
+            _ => {
+                return Err(ErrorKind::SemanticError(
+                    meta,
+                    format!("\"{}\" must be redeclared as a sized float array", name).into(),
+                ))
+            }
+        }
+
+        let handle = self.module.global_variables.append(GlobalVariable {
+            name: Some(name.clone()),
+            class: StorageClass::Private,
+            binding: None,
+            ty,
+            init: None,
+            storage_access: StorageAccess::empty(),
+        });
+
+        let idx = self.entry_args.len();
+        self.entry_args.push(EntryArg {
+            name: if self.preserve_names {
+                Some(name.clone())
+            } else {
+                None
+            },
+            binding: Binding::BuiltIn(builtin),
+            handle,
+            prologue: PrologueStage::empty(),
+        });
+
+        self.global_variables.push((
+            name,
+            GlobalLookup {
+                kind: GlobalLookupKind::Variable(handle),
+                entry_arg: Some(idx),
+                mutable: true,
+            },
+        ));
+
+        Ok(GlobalOrConstant::Global(handle))
+    }
+
     pub fn add_local_var(
         &mut self,
         ctx: &mut Context,
         body: &mut Block,
-        #[cfg_attr(not(feature = "glsl-validate"), allow(unused_variables))]
         VarDeclaration {
             qualifiers,
             ty,
@@ -523,6 +1054,7 @@ impl Program<'_> {
         }
 
         let mut mutable = true;
+        let mut precision = None;
 
         for &(ref qualifier, meta) in qualifiers {
             match *qualifier {
@@ -536,6 +1068,12 @@ impl Program<'_> {
 
                     mutable = false;
                 }
+                TypeQualifier::Precision(ref p) => qualifier_arm!(
+                    p,
+                    precision,
+                    meta,
+                    "Cannot use more than one precision qualifier per declaration"
+                ),
                 _ => {
                     return Err(ErrorKind::SemanticError(
                         meta,
@@ -553,6 +1091,7 @@ impl Program<'_> {
         let expr = ctx.add_expression(Expression::LocalVariable(handle), body);
 
         if let Some(name) = name {
+            ctx.local_decls.push((expr, name.clone(), meta));
             ctx.add_local_var(name, expr, mutable);
         }
 
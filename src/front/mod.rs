@@ -69,6 +69,21 @@ impl Typifier {
         self.resolutions[expr_handle.index()].inner_with(types)
     }
 
+    /// Returns the type of `expr_handle` as a handle, adding it to `types`
+    /// if it was only resolved to a value
+    pub fn get_handle(
+        &self,
+        expr_handle: Handle<crate::Expression>,
+        types: &mut Arena<crate::Type>,
+    ) -> Handle<crate::Type> {
+        match self.resolutions[expr_handle.index()].clone() {
+            TypeResolution::Handle(handle) => handle,
+            TypeResolution::Value(inner) => {
+                types.fetch_or_append(crate::Type { name: None, inner })
+            }
+        }
+    }
+
     pub fn grow(
         &mut self,
         expr_handle: Handle<crate::Expression>,
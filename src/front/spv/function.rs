@@ -276,6 +276,7 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
                             members: ref sub_members,
                             ..
                         } => {
+                            let old_len = function.expressions.len();
                             for (index, sm) in sub_members.iter().enumerate() {
                                 if sm.binding.is_none() {
                                     // unrecognized binding, skip
@@ -289,6 +290,11 @@ impl<I: Iterator<Item = u32>> super::Parser<I> {
                                     },
                                 ));
                             }
+                            if function.expressions.len() != old_len {
+                                function.body.push(crate::Statement::Emit(
+                                    function.expressions.range_from(old_len),
+                                ));
+                            }
                         }
                         _ => {
                             members.push(crate::StructMember {
@@ -3480,8 +3480,11 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         crate::BuiltIn::BaseInstance
                         | crate::BuiltIn::BaseVertex
                         | crate::BuiltIn::InstanceIndex
+                        | crate::BuiltIn::PrimitiveIndex
                         | crate::BuiltIn::SampleIndex
                         | crate::BuiltIn::VertexIndex
+                        | crate::BuiltIn::Layer
+                        | crate::BuiltIn::ViewportIndex
                         | crate::BuiltIn::LocalInvocationIndex => Some(crate::TypeInner::Scalar {
                             kind: crate::ScalarKind::Uint,
                             width: 4,
@@ -3489,7 +3492,8 @@ impl<I: Iterator<Item = u32>> Parser<I> {
                         crate::BuiltIn::GlobalInvocationId
                         | crate::BuiltIn::LocalInvocationId
                         | crate::BuiltIn::WorkGroupId
-                        | crate::BuiltIn::WorkGroupSize => Some(crate::TypeInner::Vector {
+                        | crate::BuiltIn::WorkGroupSize
+                        | crate::BuiltIn::NumWorkGroups => Some(crate::TypeInner::Vector {
                             size: crate::VectorSize::Tri,
                             kind: crate::ScalarKind::Uint,
                             width: 4,
@@ -3638,4 +3642,17 @@ mod test {
         ];
         let _ = super::parse_u8_slice(&bin, &Default::default()).unwrap();
     }
+
+    #[test]
+    fn output_struct_members_emitted() {
+        // The outputs of `gl_PerVertex` are read through accesses of its members
+        let bin = include_bytes!("../../../tests/in/spv/quad-vert.spv");
+        let module = super::parse_u8_slice(bin, &Default::default()).unwrap();
+        crate::valid::Validator::new(
+            crate::valid::ValidationFlags::all(),
+            crate::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
 }
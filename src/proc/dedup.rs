@@ -0,0 +1,183 @@
+use crate::{Arena, ArraySize, Constant, ConstantInner, Expression, Handle, Module, TypeInner};
+
+/// Merge structurally identical constants, remapping every handle that
+/// refers to them.
+///
+/// Constants are compared by name, specialization and value, floats are
+/// compared bitwise so `0.0` and `-0.0` are kept apart. Modules where a
+/// composite refers to a constant declared after it don't validate and are
+/// left untouched.
+pub fn dedup_constants(module: &mut Module) {
+    let forward_reference =
+        module
+            .constants
+            .iter()
+            .any(|(handle, constant)| match constant.inner {
+                ConstantInner::Composite { ref components, .. } => {
+                    components.iter().any(|&component| handle <= component)
+                }
+                ConstantInner::Scalar { .. } => false,
+            });
+    if forward_reference {
+        return;
+    }
+
+    let old_constants = std::mem::take(&mut module.constants).into_inner();
+    let mut new_handles: Vec<Handle<Constant>> = Vec::with_capacity(old_constants.len());
+    let mut unique = crate::FastHashMap::default();
+
+    for mut constant in old_constants {
+        if let ConstantInner::Composite {
+            ref mut components, ..
+        } = constant.inner
+        {
+            for component in components.iter_mut() {
+                *component = new_handles[component.index()];
+            }
+        }
+
+        let key = (
+            constant.name.clone(),
+            constant.specialization,
+            constant.inner.clone(),
+        );
+        let handle = *unique
+            .entry(key)
+            .or_insert_with(|| module.constants.append(constant));
+        new_handles.push(handle);
+    }
+
+    let remap = |handle: &mut Handle<Constant>| *handle = new_handles[handle.index()];
+
+    for (_, ty) in module.types.iter_mut() {
+        if let TypeInner::Array {
+            size: ArraySize::Constant(ref mut size),
+            ..
+        } = ty.inner
+        {
+            remap(size);
+        }
+    }
+
+    for (_, var) in module.global_variables.iter_mut() {
+        if let Some(ref mut init) = var.init {
+            remap(init);
+        }
+    }
+
+    let functions = module
+        .functions
+        .iter_mut()
+        .map(|(_, function)| function)
+        .chain(module.entry_points.iter_mut().map(|ep| &mut ep.function));
+    for function in functions {
+        for (_, var) in function.local_variables.iter_mut() {
+            if let Some(ref mut init) = var.init {
+                remap(init);
+            }
+        }
+        remap_expressions(&mut function.expressions, &remap);
+    }
+}
+
+fn remap_expressions(expressions: &mut Arena<Expression>, remap: &impl Fn(&mut Handle<Constant>)) {
+    for (_, expr) in expressions.iter_mut() {
+        match *expr {
+            Expression::Constant(ref mut constant) => remap(constant),
+            Expression::ImageSample {
+                offset: Some(ref mut offset),
+                ..
+            } => remap(offset),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Constant, ConstantInner, Expression, Function, GlobalVariable, LocalVariable, Module,
+        ScalarKind, ScalarValue, StorageAccess, StorageClass, Type, TypeInner, VectorSize,
+    };
+
+    fn scalar(module: &mut Module, value: f64) -> crate::Handle<Constant> {
+        module.constants.append(Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Scalar {
+                width: 4,
+                value: ScalarValue::Float(value),
+            },
+        })
+    }
+
+    #[test]
+    fn dedup_constants() {
+        let mut module = Module::default();
+        let float = module.types.append(Type {
+            name: None,
+            inner: TypeInner::Scalar {
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
+        let vec2 = module.types.append(Type {
+            name: None,
+            inner: TypeInner::Vector {
+                size: VectorSize::Bi,
+                kind: ScalarKind::Float,
+                width: 4,
+            },
+        });
+
+        let one = scalar(&mut module, 1.0);
+        let other_one = scalar(&mut module, 1.0);
+        let zero = scalar(&mut module, 0.0);
+        let negative_zero = scalar(&mut module, -0.0);
+        let composite = |components| Constant {
+            name: None,
+            specialization: None,
+            inner: ConstantInner::Composite {
+                ty: vec2,
+                components,
+            },
+        };
+        module.constants.append(composite(vec![one, zero]));
+        let other_composite = module.constants.append(composite(vec![other_one, zero]));
+        module.constants.append(composite(vec![one, negative_zero]));
+
+        let global = module.global_variables.append(GlobalVariable {
+            name: None,
+            class: StorageClass::Private,
+            binding: None,
+            ty: float,
+            init: Some(other_one),
+            storage_access: StorageAccess::empty(),
+        });
+        let mut function = Function::default();
+        let local = function.local_variables.append(LocalVariable {
+            name: None,
+            ty: vec2,
+            init: Some(other_composite),
+        });
+        let expr = function.expressions.append(Expression::Constant(other_one));
+        let function = module.functions.append(function);
+
+        super::dedup_constants(&mut module);
+
+        // The duplicated one and the composite built from it are merged, the
+        // zeros of different signs aren't
+        assert_eq!(module.constants.len(), 5);
+        assert_eq!(module.global_variables[global].init, Some(one));
+
+        let function = &module.functions[function];
+        let first_composite = module
+            .constants
+            .fetch_if(|constant| matches!(constant.inner, ConstantInner::Composite { .. }));
+        assert_eq!(function.local_variables[local].init, first_composite);
+        assert!(matches!(
+            function.expressions[expr],
+            Expression::Constant(constant) if constant == one
+        ));
+    }
+}
@@ -1,5 +1,6 @@
 //! Module processing functionality.
 
+mod dedup;
 mod index;
 mod interpolator;
 mod layouter;
@@ -7,6 +8,7 @@ mod namer;
 mod terminator;
 mod typifier;
 
+pub use dedup::dedup_constants;
 pub use index::IndexableLength;
 pub use layouter::{Alignment, InvalidBaseType, Layouter, TypeLayout};
 pub use namer::{EntryPointIndex, NameKey, Namer};
@@ -30,12 +30,12 @@ pub fn ensure_block_returns(block: &mut crate::Block) {
             }
             ensure_block_returns(default);
         }
-        Some(&mut S::Emit(_))
-        | Some(&mut S::Break)
+        Some(&mut S::Break)
         | Some(&mut S::Continue)
         | Some(&mut S::Return { .. })
         | Some(&mut S::Kill) => (),
-        Some(&mut S::Loop { .. })
+        Some(&mut S::Emit(_))
+        | Some(&mut S::Loop { .. })
         | Some(&mut S::Store { .. })
         | Some(&mut S::ImageStore { .. })
         | Some(&mut S::Call { .. })
@@ -43,3 +43,18 @@ pub fn ensure_block_returns(block: &mut crate::Block) {
         | None => block.push(S::Return { value: None }),
     }
 }
+
+#[test]
+fn returns_after_emit() {
+    use crate::Statement as S;
+
+    let mut expressions = crate::Arena::new();
+    expressions.append(crate::Expression::GlobalVariable(crate::Handle::new(
+        std::num::NonZeroU32::new(1).unwrap(),
+    )));
+
+    // An emit isn't a terminator, so the block still falls off its end
+    let mut block = vec![S::Emit(expressions.range_from(0))];
+    ensure_block_returns(&mut block);
+    assert!(matches!(block[..], [S::Emit(_), S::Return { value: None }]));
+}
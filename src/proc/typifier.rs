@@ -565,7 +565,8 @@ impl<'a> ResolveContext<'a> {
                             format!("{:?}(_, None)", fun)
                         ))?;
                         match (res_arg.inner_with(types), past(arg1).inner_with(types)) {
-                            (&Ti::Vector {kind: _, size: columns,width}, &Ti::Vector{ size: rows, .. }) => TypeResolution::Value(Ti::Matrix { columns, rows, width }),
+                            // the first vector is a column and the second one a row
+                            (&Ti::Vector {kind: _, size: rows, width}, &Ti::Vector{ size: columns, .. }) => TypeResolution::Value(Ti::Matrix { columns, rows, width }),
                             (left, right) =>
                                 return Err(ResolveError::IncompatibleOperands(
                                     format!("{:?}({:?}, {:?})", fun, left, right)
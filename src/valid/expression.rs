@@ -389,6 +389,14 @@ impl super::Validator {
                     crate::ImageClass::Sampled {
                         kind: crate::ScalarKind::Float,
                         multi: false,
+                    }
+                    | crate::ImageClass::Sampled {
+                        kind: crate::ScalarKind::Sint,
+                        multi: false,
+                    }
+                    | crate::ImageClass::Sampled {
+                        kind: crate::ScalarKind::Uint,
+                        multi: false,
                     } => false,
                     crate::ImageClass::Depth => true,
                     _ => return Err(ExpressionError::InvalidImageClass(class)),
@@ -941,7 +949,6 @@ impl super::Validator {
                     | Mf::Log
                     | Mf::Log2
                     | Mf::Length
-                    | Mf::Sign
                     | Mf::Sqrt
                     | Mf::InverseSqrt => {
                         if arg1_ty.is_some() | arg2_ty.is_some() {
@@ -957,6 +964,22 @@ impl super::Validator {
                             _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
                         }
                     }
+                    Mf::Sign => {
+                        if arg1_ty.is_some() | arg2_ty.is_some() {
+                            return Err(ExpressionError::WrongArgumentCount(fun));
+                        }
+                        match *arg_ty {
+                            Ti::Scalar {
+                                kind: Sk::Float, ..
+                            }
+                            | Ti::Scalar { kind: Sk::Sint, .. }
+                            | Ti::Vector {
+                                kind: Sk::Float, ..
+                            }
+                            | Ti::Vector { kind: Sk::Sint, .. } => {}
+                            _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
+                        }
+                    }
                     Mf::Atan2 | Mf::Pow | Mf::Distance | Mf::Step => {
                         let arg1_ty = match (arg1_ty, arg2_ty) {
                             (Some(ty1), None) => ty1,
@@ -1014,7 +1037,36 @@ impl super::Validator {
                             ));
                         }
                     }
-                    Mf::Dot | Mf::Outer | Mf::Cross | Mf::Reflect => {
+                    Mf::Outer => {
+                        let arg1_ty = match (arg1_ty, arg2_ty) {
+                            (Some(ty1), None) => ty1,
+                            _ => return Err(ExpressionError::WrongArgumentCount(fun)),
+                        };
+                        let width = match *arg_ty {
+                            Ti::Vector {
+                                kind: Sk::Float,
+                                width,
+                                ..
+                            } => width,
+                            _ => return Err(ExpressionError::InvalidArgumentType(fun, 0, arg)),
+                        };
+                        // the operands may have different sizes
+                        match *arg1_ty {
+                            Ti::Vector {
+                                kind: Sk::Float,
+                                width: arg1_width,
+                                ..
+                            } if arg1_width == width => {}
+                            _ => {
+                                return Err(ExpressionError::InvalidArgumentType(
+                                    fun,
+                                    1,
+                                    arg1.unwrap(),
+                                ))
+                            }
+                        }
+                    }
+                    Mf::Dot | Mf::Cross | Mf::Reflect => {
                         let arg1_ty = match (arg1_ty, arg2_ty) {
                             (Some(ty1), None) => ty1,
                             _ => return Err(ExpressionError::WrongArgumentCount(fun)),
@@ -54,6 +54,13 @@ pub enum FunctionError {
     },
     #[error("Expression {0:?} can't be introduced - it's already in scope")]
     ExpressionAlreadyInScope(Handle<crate::Expression>),
+    #[error("Expression {0:?} isn't covered by any `Emit` statement")]
+    ExpressionNotEmitted(Handle<crate::Expression>),
+    #[error("Expression {expression:?} uses {operand:?} before it's emitted")]
+    OperandNotEmitted {
+        expression: Handle<crate::Expression>,
+        operand: Handle<crate::Expression>,
+    },
     #[error("Local variable {handle:?} '{name}' is invalid")]
     LocalVariable {
         handle: Handle<crate::LocalVariable>,
@@ -265,6 +272,21 @@ impl super::Validator {
             match *statement {
                 S::Emit(ref range) => {
                     for handle in range.clone() {
+                        if self.flags.contains(ValidationFlags::EMITS) {
+                            let mut operand_not_emitted = None;
+                            for_each_operand(context.get_expression(handle)?, |operand| {
+                                if !self.valid_expression_set.contains(operand.index()) {
+                                    operand_not_emitted.get_or_insert(operand);
+                                }
+                            });
+                            if let Some(operand) = operand_not_emitted {
+                                return Err(FunctionError::OperandNotEmitted {
+                                    expression: handle,
+                                    operand,
+                                });
+                            }
+                        }
+
                         if self.valid_expression_set.insert(handle.index()) {
                             self.valid_expression_list.push(handle);
                         } else {
@@ -378,6 +400,9 @@ impl super::Validator {
                     stages &= ShaderStages::COMPUTE;
                 }
                 S::Store { pointer, value } => {
+                    if self.flags.contains(ValidationFlags::EMITS) {
+                        let _ = context.resolve_type(pointer, &self.valid_expression_set)?;
+                    }
                     let mut current = pointer;
                     loop {
                         let _ = context.resolve_pointer_type(current)?;
@@ -615,6 +640,285 @@ impl super::Validator {
             )?;
             info.available_stages &= stages;
         }
+
+        if self.flags.contains(ValidationFlags::EMITS) {
+            let mut emitted = BitSet::new();
+            collect_emitted(&fun.body, &mut emitted);
+            for (handle, expr) in fun.expressions.iter() {
+                if !expr.needs_pre_emit() && !emitted.contains(handle.index()) {
+                    return Err(FunctionError::ExpressionNotEmitted(handle));
+                }
+            }
+        }
         Ok(info)
     }
 }
+
+/// Calls `f` with every expression `expr` uses as an operand.
+fn for_each_operand(expr: &crate::Expression, mut f: impl FnMut(Handle<crate::Expression>)) {
+    use crate::{Expression as E, SampleLevel as Sl};
+    match *expr {
+        E::Access { base, index } => {
+            f(base);
+            f(index);
+        }
+        E::AccessIndex { base, .. } => f(base),
+        E::Splat { value, .. } => f(value),
+        E::Swizzle { vector, .. } => f(vector),
+        E::Compose { ref components, .. } => {
+            for &component in components {
+                f(component);
+            }
+        }
+        E::Load { pointer } => f(pointer),
+        E::ImageSample {
+            image,
+            sampler,
+            coordinate,
+            array_index,
+            level,
+            depth_ref,
+            ..
+        } => {
+            f(image);
+            f(sampler);
+            f(coordinate);
+            if let Some(array_index) = array_index {
+                f(array_index);
+            }
+            match level {
+                Sl::Auto | Sl::Zero => {}
+                Sl::Exact(expr) | Sl::Bias(expr) => f(expr),
+                Sl::Gradient { x, y } => {
+                    f(x);
+                    f(y);
+                }
+            }
+            if let Some(depth_ref) = depth_ref {
+                f(depth_ref);
+            }
+        }
+        E::ImageLoad {
+            image,
+            coordinate,
+            array_index,
+            index,
+        } => {
+            f(image);
+            f(coordinate);
+            if let Some(array_index) = array_index {
+                f(array_index);
+            }
+            if let Some(index) = index {
+                f(index);
+            }
+        }
+        E::ImageQuery { image, query } => {
+            f(image);
+            if let crate::ImageQuery::Size { level: Some(level) } = query {
+                f(level);
+            }
+        }
+        E::Unary { expr, .. }
+        | E::Derivative { expr, .. }
+        | E::Relational { argument: expr, .. }
+        | E::As { expr, .. }
+        | E::ArrayLength(expr) => f(expr),
+        E::Binary { left, right, .. } => {
+            f(left);
+            f(right);
+        }
+        E::Select {
+            condition,
+            accept,
+            reject,
+        } => {
+            f(condition);
+            f(accept);
+            f(reject);
+        }
+        E::Math {
+            arg, arg1, arg2, ..
+        } => {
+            f(arg);
+            if let Some(arg1) = arg1 {
+                f(arg1);
+            }
+            if let Some(arg2) = arg2 {
+                f(arg2);
+            }
+        }
+        E::Constant(_)
+        | E::FunctionArgument(_)
+        | E::GlobalVariable(_)
+        | E::LocalVariable(_)
+        | E::Call(_) => {}
+    }
+}
+
+/// Collects the expressions introduced by the `Emit` statements and call
+/// results of `block` and all of its nested blocks.
+fn collect_emitted(block: &[crate::Statement], emitted: &mut BitSet) {
+    use crate::Statement as S;
+    for statement in block {
+        match *statement {
+            S::Emit(ref range) => {
+                for handle in range.clone() {
+                    emitted.insert(handle.index());
+                }
+            }
+            S::Call {
+                result: Some(result),
+                ..
+            } => {
+                emitted.insert(result.index());
+            }
+            S::Block(ref block) => collect_emitted(block, emitted),
+            S::If {
+                ref accept,
+                ref reject,
+                ..
+            } => {
+                collect_emitted(accept, emitted);
+                collect_emitted(reject, emitted);
+            }
+            S::Switch {
+                ref cases,
+                ref default,
+                ..
+            } => {
+                for case in cases {
+                    collect_emitted(&case.body, emitted);
+                }
+                collect_emitted(default, emitted);
+            }
+            S::Loop {
+                ref body,
+                ref continuing,
+            } => {
+                collect_emitted(body, emitted);
+                collect_emitted(continuing, emitted);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn expression_not_emitted() {
+    use crate::{Expression as E, Statement as S};
+
+    let mut module = crate::Module::default();
+    let ty = module.types.append(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let constant = module.constants.append(crate::Constant {
+        name: None,
+        specialization: None,
+        inner: crate::ConstantInner::Scalar {
+            width: 4,
+            value: crate::ScalarValue::Float(1.0),
+        },
+    });
+
+    let mut function = crate::Function {
+        result: Some(crate::FunctionResult { ty, binding: None }),
+        ..Default::default()
+    };
+    let value = function.expressions.append(E::Constant(constant));
+    let dangling = function.expressions.append(E::Unary {
+        op: crate::UnaryOperator::Negate,
+        expr: value,
+    });
+    function.body.push(S::Return { value: Some(value) });
+    let handle = module.functions.append(function);
+
+    let error = super::Validator::new(ValidationFlags::all(), Default::default())
+        .validate(&module)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        super::ValidationError::Function {
+            handle: error_handle,
+            error: FunctionError::ExpressionNotEmitted(expr),
+            ..
+        } if error_handle == handle && expr == dangling
+    ));
+
+    super::Validator::new(
+        ValidationFlags::all() - ValidationFlags::EMITS,
+        Default::default(),
+    )
+    .validate(&module)
+    .unwrap();
+}
+
+#[test]
+fn operand_not_emitted() {
+    use crate::{Expression as E, Statement as S};
+
+    let mut module = crate::Module::default();
+    let ty = module.types.append(crate::Type {
+        name: None,
+        inner: crate::TypeInner::Scalar {
+            kind: crate::ScalarKind::Float,
+            width: 4,
+        },
+    });
+    let constant = module.constants.append(crate::Constant {
+        name: None,
+        specialization: None,
+        inner: crate::ConstantInner::Scalar {
+            width: 4,
+            value: crate::ScalarValue::Float(1.0),
+        },
+    });
+
+    let mut function = crate::Function {
+        result: Some(crate::FunctionResult { ty, binding: None }),
+        ..Default::default()
+    };
+    let value = function.expressions.append(E::Constant(constant));
+    let negated = function.expressions.append(E::Unary {
+        op: crate::UnaryOperator::Negate,
+        expr: value,
+    });
+    let length = function.expressions.len();
+    let twice_negated = function.expressions.append(E::Unary {
+        op: crate::UnaryOperator::Negate,
+        expr: negated,
+    });
+    // Only the outer negation is emitted, before the one it uses
+    function
+        .body
+        .push(S::Emit(function.expressions.range_from(length)));
+    function.body.push(S::Return {
+        value: Some(twice_negated),
+    });
+    module.functions.append(function);
+
+    let error = super::Validator::new(ValidationFlags::all(), Default::default())
+        .validate(&module)
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        super::ValidationError::Function {
+            error: FunctionError::OperandNotEmitted {
+                expression,
+                operand,
+            },
+            ..
+        } if expression == twice_negated && operand == negated
+    ));
+
+    super::Validator::new(
+        ValidationFlags::all() - ValidationFlags::EMITS,
+        Default::default(),
+    )
+    .validate(&module)
+    .unwrap();
+}
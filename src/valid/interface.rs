@@ -43,8 +43,8 @@ pub enum VaryingError {
     MissingInterpolation,
     #[error("Built-in {0:?} is not available at this stage")]
     InvalidBuiltInStage(crate::BuiltIn),
-    #[error("Built-in type for {0:?} is invalid")]
-    InvalidBuiltInType(crate::BuiltIn),
+    #[error("Built-in {0:?} can't have the type {1:?}")]
+    InvalidBuiltInType(crate::BuiltIn, Handle<crate::Type>),
     #[error("Entry point arguments and return values must all have bindings")]
     MissingBinding,
     #[error("Struct member {0} is missing a binding")]
@@ -160,6 +160,18 @@ impl VaryingContext<'_> {
                                 width,
                             },
                     ),
+                    Bi::Layer | Bi::ViewportIndex => (
+                        match self.stage {
+                            St::Vertex => self.output,
+                            St::Fragment => !self.output,
+                            St::Compute => false,
+                        },
+                        *ty_inner
+                            == Ti::Scalar {
+                                kind: Sk::Uint,
+                                width,
+                            },
+                    ),
                     Bi::FragDepth => (
                         self.stage == St::Fragment && self.output,
                         *ty_inner
@@ -176,7 +188,7 @@ impl VaryingContext<'_> {
                                 width: crate::BOOL_WIDTH,
                             },
                     ),
-                    Bi::SampleIndex => (
+                    Bi::PrimitiveIndex | Bi::SampleIndex => (
                         self.stage == St::Fragment && !self.output,
                         *ty_inner
                             == Ti::Scalar {
@@ -203,7 +215,8 @@ impl VaryingContext<'_> {
                     Bi::GlobalInvocationId
                     | Bi::LocalInvocationId
                     | Bi::WorkGroupId
-                    | Bi::WorkGroupSize => (
+                    | Bi::WorkGroupSize
+                    | Bi::NumWorkGroups => (
                         self.stage == St::Compute && !self.output,
                         *ty_inner
                             == Ti::Vector {
@@ -218,8 +231,7 @@ impl VaryingContext<'_> {
                     return Err(VaryingError::InvalidBuiltInStage(built_in));
                 }
                 if !type_good {
-                    log::warn!("Wrong builtin type: {:?}", ty_inner);
-                    return Err(VaryingError::InvalidBuiltInType(built_in));
+                    return Err(VaryingError::InvalidBuiltInType(built_in, self.ty));
                 }
             }
             crate::Binding::Location {
@@ -38,6 +38,8 @@ bitflags::bitflags! {
         const STRUCT_LAYOUTS = 0x8;
         /// Constants.
         const CONSTANTS = 0x10;
+        /// Expressions being emitted before they are used.
+        const EMITS = 0x20;
     }
 }
 
@@ -0,0 +1,12 @@
+// Testing fragment entry points returning a struct of locations and built-ins
+
+struct FragmentOutput {
+    [[location(0)]] color: vec4<f32>;
+    [[location(1)]] normal: vec3<f32>;
+    [[builtin(frag_depth)]] depth: f32;
+};
+
+[[stage(fragment)]]
+fn main([[location(0)]] value: f32) -> FragmentOutput {
+    return FragmentOutput(vec4<f32>(value), vec3<f32>(0.0, 0.0, 1.0), value);
+}
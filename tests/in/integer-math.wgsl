@@ -0,0 +1,10 @@
+fn integer_math() -> vec2<i32> {
+    let a = sign(-3) + abs(-4);
+    let b = sign(vec2<i32>(-3, 4)) + abs(vec2<i32>(-5, 6));
+    return b + vec2<i32>(a);
+}
+
+[[stage(compute), workgroup_size(1)]]
+fn main() {
+    let a = integer_math();
+}
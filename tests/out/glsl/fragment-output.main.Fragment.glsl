@@ -0,0 +1,23 @@
+#version 310 es
+
+precision highp float;
+
+struct FragmentOutput {
+    vec4 color;
+    vec3 normal;
+    float depth;
+};
+
+smooth in float _vs2fs_location0;
+layout(location = 0) out vec4 _fs2p_location0;
+layout(location = 1) out vec3 _fs2p_location1;
+
+void main() {
+    float value = _vs2fs_location0;
+    FragmentOutput _tmp_return = FragmentOutput(vec4(value), vec3(0.0, 0.0, 1.0), value);
+    _fs2p_location0 = _tmp_return.color;
+    _fs2p_location1 = _tmp_return.normal;
+    gl_FragDepth = _tmp_return.depth;
+    return;
+}
+
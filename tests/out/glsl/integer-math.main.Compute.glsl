@@ -0,0 +1,18 @@
+#version 310 es
+
+precision highp float;
+
+layout(local_size_x = 1, local_size_y = 1, local_size_z = 1) in;
+
+
+ivec2 integer_math() {
+    int a = (sign(-3) + abs(-4));
+    ivec2 b = (sign(ivec2(-3, 4)) + abs(ivec2(-5, 6)));
+    return (b + ivec2(a));
+}
+
+void main() {
+    ivec2 _expr0 = integer_math();
+    return;
+}
+
@@ -0,0 +1,15 @@
+struct FragmentOutput {
+    float4 color : SV_Target0;
+    float3 normal : SV_Target1;
+    float depth : SV_Depth;
+};
+
+struct FragmentInput_main {
+    float value1 : LOC0;
+};
+
+FragmentOutput main(FragmentInput_main fragmentinput_main)
+{
+    const FragmentOutput fragmentoutput1 = { float4(fragmentinput_main.value1.xxxx), float3(0.0, 0.0, 1.0), fragmentinput_main.value1 };
+    return fragmentoutput1;
+}
@@ -0,0 +1,13 @@
+int2 integer_math()
+{
+    int a = (sign(-3) + abs(-4));
+    int2 b = (sign(int2(-3, 4)) + abs(int2(-5, 6)));
+    return (b + int2(a.xx));
+}
+
+[numthreads(1, 1, 1)]
+void main()
+{
+    const int2 _e0 = integer_math();
+    return;
+}
@@ -1,6 +1,7 @@
 fn main1() {
     var a: f32 = 1.0;
 
+    return;
 }
 
 [[stage(vertex)]]
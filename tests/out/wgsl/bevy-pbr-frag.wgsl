@@ -238,7 +238,7 @@ fn fresnel(f0_3: vec3<f32>, LoH: f32) -> vec3<f32> {
     f0_4 = f0_3;
     LoH1 = LoH;
     let _e44: vec3<f32> = f0_4;
-    f90_4 = clamp(dot(_e44, vec3<f32>((50.0 * 0.33000001311302185))), 0.0, 1.0);
+    f90_4 = clamp(dot(_e44, vec3<f32>(16.5, 16.5, 16.5)), 0.0, 1.0);
     let _e57: vec3<f32> = f0_4;
     let _e58: f32 = f90_4;
     let _e59: f32 = LoH1;
@@ -365,7 +365,7 @@ fn reinhard(color: vec3<f32>) -> vec3<f32> {
     color1 = color;
     let _e42: vec3<f32> = color1;
     let _e45: vec3<f32> = color1;
-    return (_e42 / (vec3<f32>(1.0) + _e45));
+    return (_e42 / (vec3<f32>(1.0, 1.0, 1.0) + _e45));
 }
 
 fn reinhard_extended(color2: vec3<f32>, max_white: f32) -> vec3<f32> {
@@ -379,10 +379,10 @@ fn reinhard_extended(color2: vec3<f32>, max_white: f32) -> vec3<f32> {
     let _e47: vec3<f32> = color3;
     let _e48: f32 = max_white1;
     let _e49: f32 = max_white1;
-    numerator = (_e44 * (vec3<f32>(1.0) + (_e47 / vec3<f32>((_e48 * _e49)))));
+    numerator = (_e44 * (vec3<f32>(1.0, 1.0, 1.0) + (_e47 / vec3<f32>((_e48 * _e49)))));
     let _e56: vec3<f32> = numerator;
     let _e59: vec3<f32> = color3;
-    return (_e56 / (vec3<f32>(1.0) + _e59));
+    return (_e56 / (vec3<f32>(1.0, 1.0, 1.0) + _e59));
 }
 
 fn luminance(v1: vec3<f32>) -> f32 {
@@ -711,7 +711,7 @@ fn main1() {
     let _e98: mat3x3<f32> = TBN;
     let _e99: vec2<f32> = v_Uv1;
     let _e100: vec4<f32> = textureSample(StandardMaterial_normal_map, StandardMaterial_normal_map_sampler, _e99);
-    N2 = (_e98 * normalize(((_e100.xyz * 2.0) - vec3<f32>(1.0))));
+    N2 = (_e98 * normalize(((_e100.xyz * 2.0) - vec3<f32>(1.0, 1.0, 1.0))));
     let _e109: vec2<f32> = v_Uv1;
     let _e110: vec4<f32> = textureSample(StandardMaterial_occlusion_texture, StandardMaterial_occlusion_texture_sampler, _e109);
     occlusion = _e110.x;
@@ -0,0 +1,11 @@
+fn integer_math() -> vec2<i32> {
+    let a: i32 = (clamp(-3, -1, 1) + abs(-4));
+    let b: vec2<i32> = (clamp(vec2<i32>(-3, 4), vec2<i32>(-1), vec2<i32>(1)) + abs(vec2<i32>(-5, 6)));
+    return (b + vec2<i32>(a));
+}
+
+[[stage(compute), workgroup_size(1, 1, 1)]]
+fn main() {
+    let _e0: vec2<i32> = integer_math();
+    return;
+}
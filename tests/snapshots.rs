@@ -384,6 +384,14 @@ fn convert_wgsl() {
             "texture-arg",
             Targets::METAL | Targets::GLSL | Targets::WGSL,
         ),
+        (
+            "integer-math",
+            Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
+        ),
+        (
+            "fragment-output",
+            Targets::METAL | Targets::GLSL | Targets::HLSL | Targets::WGSL,
+        ),
     ];
 
     for &(name, targets) in inputs.iter() {
@@ -496,7 +504,7 @@ fn convert_glsl_folder() {
             &fs::read_to_string(entry.path()).expect("Couldn't find glsl file"),
             &naga::front::glsl::Options {
                 entry_points,
-                defines: Default::default(),
+                ..Default::default()
             },
         )
         .unwrap();
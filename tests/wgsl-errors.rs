@@ -437,3 +437,55 @@ fn invalid_local_vars() {
         if local_var_name == "not_okay"
     }
 }
+
+#[test]
+fn invalid_builtin_types() {
+    check_validation_error! {
+        "
+        [[stage(vertex)]]
+        fn vertex() -> [[builtin(position)]] vec3<f32> {
+           return vec3<f32>(0.0);
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Vertex,
+            error: naga::valid::EntryPointError::Result(
+                naga::valid::VaryingError::InvalidBuiltInType(naga::BuiltIn::Position, _),
+            ),
+            ..
+        })
+    }
+
+    check_validation_error! {
+        "
+        [[stage(vertex)]]
+        fn vertex([[builtin(vertex_index)]] index: f32) -> [[builtin(position)]] vec4<f32> {
+           return vec4<f32>(index);
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Vertex,
+            error: naga::valid::EntryPointError::Argument(
+                0,
+                naga::valid::VaryingError::InvalidBuiltInType(naga::BuiltIn::VertexIndex, _),
+            ),
+            ..
+        })
+    }
+
+    check_validation_error! {
+        "
+        [[stage(fragment)]]
+        fn fragment() -> [[builtin(frag_depth)]] vec4<f32> {
+           return vec4<f32>(0.0);
+        }
+        ":
+        Err(naga::valid::ValidationError::EntryPoint {
+            stage: naga::ShaderStage::Fragment,
+            error: naga::valid::EntryPointError::Result(
+                naga::valid::VaryingError::InvalidBuiltInType(naga::BuiltIn::FragDepth, _),
+            ),
+            ..
+        })
+    }
+}